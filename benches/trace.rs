@@ -0,0 +1,139 @@
+//! Throughput of the dependence analysis on generated traces. Run with
+//! `cargo bench`, optionally followed by `-- <lines>` for the approximate
+//! number of trace lines (one million by default).
+//!
+//! Numbers before and after packing batch traces are in `trace.txt`. They
+//! were measured by running this bench in a checkout of each version. Before
+//! packed batch traces, i.e. at the parent of the commit introducing this
+//! bench, add the `[[bench]]` section of `Cargo.toml`, drop the `options`
+//! and pass only the trace to the analyses.
+
+extern crate vectorizer;
+
+use std::{env, io::Cursor, time::Instant};
+use vectorizer::dependencies::{self, binary};
+
+fn main() {
+    let lines = env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse::<usize>().ok())
+        .next()
+        .unwrap_or(1_000_000);
+
+    // Every iteration of the inner loop takes six lines
+    let n = ((lines / 6) as f64).sqrt().ceil() as i32 + 1;
+    let text = text_trace(n);
+    let binary = binary_trace(n);
+    let lines = text.iter().filter(|&&c| c == b'\n').count();
+    let options = dependencies::Options::default();
+
+    bench("text", lines, || {
+        dependencies::find_dependencies(Cursor::new(&text), None, &options).unwrap()
+    });
+    bench("text --stream", lines, || {
+        dependencies::find_dependencies_streaming(Cursor::new(&text), None, &options).unwrap()
+    });
+    bench("binary", lines, || {
+        dependencies::find_dependencies_binary(&binary, None, &options).unwrap()
+    });
+    bench("binary --stream", lines, || {
+        dependencies::find_dependencies_binary_streaming(&binary, None, &options).unwrap()
+    });
+}
+
+/// Best of three runs
+fn bench<T, F: Fn() -> T>(name: &str, lines: usize, f: F) {
+    let seconds = (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed().as_secs_f64()
+        })
+        .fold(f64::INFINITY, f64::min);
+
+    println!(
+        "{:<16} {:>9} lines {:>8.3} s {:>10.0} lines/s",
+        name,
+        lines,
+        seconds,
+        lines as f64 / seconds
+    );
+}
+
+/// Accesses of the loop nest
+///
+///     do i = 2, n
+///       do j = 1, n
+///         a(i, j) = a(i - 1, j) + b(i, j)
+///         b(i, j) = b(i, j) * 2
+fn accesses(i: i32, j: i32) -> [(&'static str, i32, &'static str, [i32; 2]); 5] {
+    [
+        ("004", 0, "DEF", [i, j]),
+        ("004", 0, "USE", [i - 1, j]),
+        ("004", 1, "USE", [i, j]),
+        ("005", 1, "DEF", [i, j]),
+        ("005", 1, "USE", [i, j]),
+    ]
+}
+
+const NAMES: [&str; 2] = ["a", "b"];
+
+fn text_trace(n: i32) -> Vec<u8> {
+    let mut trace = String::new();
+    trace.push_str(" 2 i loop begin\n");
+    for i in 2..=n {
+        trace.push_str(&format!(" 2 i {}\n 3 j loop begin\n", i));
+        for j in 1..=n {
+            trace.push_str(&format!(" 3 j {}\n", j));
+            for (statement, var, category, indices) in accesses(i, j).iter() {
+                trace.push_str(&format!(
+                    " {} {} {} {} {}\n",
+                    statement, NAMES[*var as usize], category, indices[0], indices[1]
+                ));
+            }
+        }
+        trace.push_str(" 3 j loop end\n");
+    }
+    trace.push_str(" 2 i loop end\n");
+
+    trace.into_bytes()
+}
+
+fn binary_trace(n: i32) -> Vec<u8> {
+    let mut trace = binary::MAGIC.as_bytes().to_vec();
+    let write = |trace: &mut Vec<u8>, values: &[i32]| {
+        for v in values {
+            trace.extend_from_slice(&v.to_ne_bytes());
+        }
+    };
+
+    write(&mut trace, &[binary::VERSION, NAMES.len() as i32]);
+    for name in NAMES.iter() {
+        write(&mut trace, &[2, name.len() as i32]);
+        trace.extend_from_slice(name.as_bytes());
+    }
+
+    write(&mut trace, &[binary::LOOP_BEGIN, 2]);
+    for i in 2..=n {
+        write(
+            &mut trace,
+            &[binary::LOOP_UPDATE, 2, i, binary::LOOP_BEGIN, 3],
+        );
+        for j in 1..=n {
+            write(&mut trace, &[binary::LOOP_UPDATE, 3, j]);
+            for (statement, var, category, indices) in accesses(i, j).iter() {
+                let tag = if *category == "DEF" {
+                    binary::DEF
+                } else {
+                    binary::USE
+                };
+                let statement = statement.parse().unwrap();
+                write(&mut trace, &[tag, statement, *var, indices[0], indices[1]]);
+            }
+        }
+        write(&mut trace, &[binary::LOOP_END, 3]);
+    }
+    write(&mut trace, &[binary::LOOP_END, 2]);
+
+    trace
+}
@@ -3,14 +3,18 @@ extern crate serde_json;
 extern crate vectorizer;
 
 use std::{
-    env,
+    env, fs,
     fs::File,
-    io::{BufReader, BufWriter},
+    io::{BufReader, BufWriter, Read},
 };
-use vectorizer::dependencies;
+use vectorizer::{dependencies, ir};
 
 fn print_usage(prog_name: &str) {
-    eprintln!("Usage: {} project_name", prog_name);
+    eprintln!(
+        "Usage: {} project_name [text|binary] [--stream] [--jobs N] [--witnesses N] \
+         [--value-based] [--input] [--ast] [--trace FILE]...",
+        prog_name
+    );
 }
 
 fn main() {
@@ -34,36 +38,183 @@ fn main() {
         }
     };
 
-    // Open trace file
-    let trace = match File::open(&format!("{}.trace", &project_name)) {
-        Ok(f) => f,
-        Err(e) => {
-            eprintln!("Could not open {}.trace: {}", &project_name, e);
-            return;
+    // Format of the traces, streaming analysis, analysis options,
+    // validation against the program and traces of several runs
+    let mut binary = false;
+    let mut streaming = false;
+    let mut options = dependencies::Options::default();
+    let mut check_ast = false;
+    let mut traces = Vec::new();
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "text" => binary = false,
+            "binary" => binary = true,
+            "--stream" => streaming = true,
+            "--value-based" => options.value_based = true,
+            "--input" => options.input = true,
+            "--ast" => check_ast = true,
+            "--trace" => match arg_iter.next() {
+                Some(file) => traces.push(file),
+                None => {
+                    print_usage(&prog_name);
+                    return;
+                }
+            },
+            "--jobs" => match arg_iter.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => options.jobs = n,
+                _ => {
+                    print_usage(&prog_name);
+                    return;
+                }
+            },
+            "--witnesses" => match arg_iter.next().and_then(|n| n.parse().ok()) {
+                Some(n) => options.witnesses = n,
+                None => {
+                    print_usage(&prog_name);
+                    return;
+                }
+            },
+            _ => {
+                print_usage(&prog_name);
+                return;
+            }
+        }
+    }
+
+    if streaming && options.jobs > 1 {
+        eprintln!("Warning: The streaming analysis runs on a single thread, ignoring --jobs");
+    }
+
+    // Read and parse AST to validate the trace against
+    let ast = if check_ast {
+        let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Could not open {}.ast: {}", &project_name, e);
+                return;
+            }
+        };
+
+        let mut ir_text = String::new();
+        if let Err(e) = ast_file.read_to_string(&mut ir_text) {
+            eprintln!("Error while reading IR from {}.ast: {}", &project_name, e);
+        }
+
+        // Fix IR input for parser
+        ir_text.push_str(" $");
+        let ir_text = ir_text.replace('\n', " ");
+        let ir_text = ir_text.replace('\t', " ");
+
+        match ir::parse_ast(&ir_text) {
+            Ok((_, ast)) => Some(ast),
+            Err(e) => {
+                eprintln!("Could not parse {}.ast: {}", &project_name, e);
+                return;
+            }
         }
+    } else {
+        None
     };
-    let inp = BufReader::new(trace);
+    let ast = ast.as_ref();
 
-    // Analyze dependencies
-    let graph = match dependencies::find_dependencies(inp) {
-        Ok(g) => g,
-        Err(e) => {
-            eprintln!("Could not find dependencies: {}", e);
+    // Without traces given, the trace of the project is analyzed
+    if traces.is_empty() {
+        let extension = if binary { "btrace" } else { "trace" };
+        traces.push(format!("{}.{}", &project_name, extension));
+    }
+
+    // Analyze dependencies of every run
+    let mut graphs = Vec::new();
+    for trace_name in traces.iter() {
+        let graph = if binary {
+            // Read binary trace file
+            let data = match fs::read(trace_name) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("Could not read {}: {}", trace_name, e);
+                    return;
+                }
+            };
+
+            if streaming {
+                dependencies::find_dependencies_binary_streaming(&data, ast, &options)
+            } else {
+                dependencies::find_dependencies_binary(&data, ast, &options)
+            }
+        } else {
+            // Open trace file
+            let trace = match File::open(trace_name) {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("Could not open {}: {}", trace_name, e);
+                    return;
+                }
+            };
+            let inp = BufReader::new(trace);
+
+            if streaming {
+                dependencies::find_dependencies_streaming(inp, ast, &options)
+            } else {
+                dependencies::find_dependencies(inp, ast, &options)
+            }
+        };
+
+        match graph {
+            Ok(g) => graphs.push(g),
+            Err(e) => {
+                eprintln!("Could not find dependencies in {}: {}", trace_name, e);
+                return;
+            }
+        }
+    }
+
+    // Merge the graphs of several runs and print the graph to the dot file
+    let dot_name = format!("{}.dot", &project_name);
+    let graph = if graphs.len() > 1 {
+        let (graph, runs) = dependencies::merge_graphs(&graphs);
+        if let Err(e) = dependencies::print_merged_graph(&graph, &runs, graphs.len(), &dot_name) {
+            eprintln!("Could not print graph to {}: {}", &dot_name, e);
+            return;
+        }
+
+        // Serialize the number of runs showing every edge
+        let runs_file = match File::create(format!("{}.runs", &project_name)) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!(
+                    "Could not open {}.runs file for writing: {}",
+                    &project_name, e
+                );
+                return;
+            }
+        };
+        if let Err(e) = serde_json::to_writer(BufWriter::new(runs_file), &runs) {
+            eprintln!("Could not serialize runs to {}.runs: {}", &project_name, e);
             return;
         }
+
+        graph
+    } else {
+        let graph = graphs.remove(0);
+        if let Err(e) = dependencies::print_graph(&graph, &dot_name) {
+            eprintln!("Could not print graph to {}: {}", &dot_name, e);
+            return;
+        }
+
+        graph
     };
 
-    // Print graph to dot file
-    match dependencies::print_graph(&graph, &format!("{}.dot", &project_name)) {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!("Could not print graph to {}.dot: {}", &project_name, e);
+    // Print the reuse shown by true and input dependencies
+    if options.input {
+        let reuse_name = format!("{}.reuse", &project_name);
+        if let Err(e) = dependencies::print_reuse_report(&graph, &reuse_name) {
+            eprintln!("Could not print reuse report to {}: {}", &reuse_name, e);
             return;
         }
     }
 
     // Serialize graph to graph file
-    let graph_file = match File::create(&format!("{}.graph", &project_name)) {
+    let graph_file = match File::create(format!("{}.graph", &project_name)) {
         Ok(f) => f,
         Err(e) => {
             eprintln!(
@@ -81,7 +232,6 @@ fn main() {
                 "Could not serialize graph to {}.graph: {}",
                 &project_name, e
             );
-            return;
         }
     }
 }
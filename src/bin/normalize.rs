@@ -0,0 +1,103 @@
+extern crate vectorizer;
+
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Read},
+};
+use vectorizer::{ir, transform};
+
+fn print_usage(prog_name: &str) {
+    eprintln!("Usage: {} project_name [lower_bound]", prog_name);
+}
+
+fn main() {
+    let mut arg_iter = env::args();
+
+    // Program name
+    let prog_name = match arg_iter.next() {
+        Some(p) => p,
+        None => {
+            print_usage("./normalize");
+            return;
+        }
+    };
+
+    // Project name
+    let project_name = match arg_iter.next() {
+        Some(n) => n,
+        None => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
+    // Lower bound of the normalized loops
+    let base = match arg_iter.next().map(|b| b.parse::<i32>()) {
+        Some(Ok(b)) => b,
+        Some(Err(e)) => {
+            eprintln!("Invalid lower bound: {}", e);
+            return;
+        }
+        None => 1,
+    };
+
+    // Open AST file
+    let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Read AST
+    let mut ir_text = String::new();
+    if let Err(e) = ast_file.read_to_string(&mut ir_text) {
+        eprintln!("Error while reading IR from {}.ast: {}", &project_name, e);
+    }
+
+    // Fix IR input for parser
+    ir_text.push_str(" $");
+    let ir_text = ir_text.replace('\n', " ");
+    let ir_text = ir_text.replace('\t', " ");
+
+    // Parse AST
+    let ast = match ir::parse_ast(&ir_text) {
+        Ok((_, ast)) => ast,
+        Err(e) => {
+            eprintln!("Could not parse {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Normalize all loops
+    let normalized = match transform::normalize(&ast, base) {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("Could not normalize loops of {}: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Open file for transformed AST, which has to be traced again
+    let file = match File::create(format!("{}_normalized.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!(
+                "Could not open {}_normalized.ast for writing: {}",
+                &project_name, e
+            );
+            return;
+        }
+    };
+
+    // Write transformed AST
+    let mut writer = BufWriter::new(file);
+    match ir::print_ast(&normalized, &mut writer) {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("Could not write {}_normalized.ast: {}", &project_name, e);
+        }
+    }
+}
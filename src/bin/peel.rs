@@ -0,0 +1,130 @@
+extern crate vectorizer;
+
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Read},
+};
+use vectorizer::{ir, transform};
+
+fn print_usage(prog_name: &str) {
+    eprintln!("Usage: {} project_name loop_label front back", prog_name);
+}
+
+fn main() {
+    let mut arg_iter = env::args();
+
+    // Program name
+    let prog_name = match arg_iter.next() {
+        Some(p) => p,
+        None => {
+            print_usage("./peel");
+            return;
+        }
+    };
+
+    // Project name
+    let project_name = match arg_iter.next() {
+        Some(n) => n,
+        None => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
+    // Label of the loop to peel
+    let label = match arg_iter.next().map(|l| l.parse::<i32>()) {
+        Some(Ok(l)) => l,
+        Some(Err(e)) => {
+            eprintln!("Invalid loop label: {}", e);
+            return;
+        }
+        None => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
+    // Number of iterations peeled off the start and the end
+    let mut counts = Vec::new();
+    for what in ["front", "back"].iter() {
+        match arg_iter.next().map(|c| c.parse::<i32>()) {
+            Some(Ok(c)) => counts.push(c),
+            Some(Err(e)) => {
+                eprintln!("Invalid number of {} iterations: {}", what, e);
+                return;
+            }
+            None => {
+                print_usage(&prog_name);
+                return;
+            }
+        }
+    }
+
+    // Open AST file
+    let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Read AST
+    let mut ir_text = String::new();
+    if let Err(e) = ast_file.read_to_string(&mut ir_text) {
+        eprintln!("Error while reading IR from {}.ast: {}", &project_name, e);
+    }
+
+    // Fix IR input for parser
+    ir_text.push_str(" $");
+    let ir_text = ir_text.replace('\n', " ");
+    let ir_text = ir_text.replace('\t', " ");
+
+    // Parse AST
+    let ast = match ir::parse_ast(&ir_text) {
+        Ok((_, ast)) => ast,
+        Err(e) => {
+            eprintln!("Could not parse {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Peel iterations off the loop
+    let peeled = match transform::peel(&ast, label, counts[0], counts[1]) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Could not peel loop {} of {}: {}", label, &project_name, e);
+            return;
+        }
+    };
+
+    // Open file for transformed AST, which has to be traced again
+    let file = match File::create(format!("{}_peeled.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!(
+                "Could not open {}_peeled.ast for writing: {}",
+                &project_name, e
+            );
+            return;
+        }
+    };
+
+    // Write transformed AST
+    let mut writer = BufWriter::new(file);
+    match ir::print_ast(&peeled, &mut writer) {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("Could not write {}_peeled.ast: {}", &project_name, e);
+            return;
+        }
+    }
+
+    // The remaining iterations are vectorized from a new trace
+    println!(
+        "Wrote {0}_peeled.ast, trace it with tracegen {0}_peeled and analyze it with depana \
+         {0}_peeled before vectorizing the remaining loop",
+        &project_name
+    );
+}
@@ -0,0 +1,105 @@
+extern crate vectorizer;
+
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Read},
+};
+use vectorizer::{ir, transform};
+
+fn print_usage(prog_name: &str) {
+    eprintln!("Usage: {} project_name", prog_name);
+}
+
+fn main() {
+    let mut arg_iter = env::args();
+
+    // Program name
+    let prog_name = match arg_iter.next() {
+        Some(p) => p,
+        None => {
+            print_usage("./substitute");
+            return;
+        }
+    };
+
+    // Project name
+    let project_name = match arg_iter.next() {
+        Some(n) => n,
+        None => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
+    // Open AST file
+    let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Read AST
+    let mut ir_text = String::new();
+    if let Err(e) = ast_file.read_to_string(&mut ir_text) {
+        eprintln!("Error while reading IR from {}.ast: {}", &project_name, e);
+    }
+
+    // Fix IR input for parser
+    ir_text.push_str(" $");
+    let ir_text = ir_text.replace('\n', " ");
+    let ir_text = ir_text.replace('\t', " ");
+
+    // Parse AST
+    let ast = match ir::parse_ast(&ir_text) {
+        Ok((_, ast)) => ast,
+        Err(e) => {
+            eprintln!("Could not parse {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Replace induction variables, then forward substitute temporaries
+    let substituted = transform::substitute_induction(&ast).and_then(|(ast, inductions)| {
+        let (ast, removed) = transform::forward_substitute(&ast)?;
+        Ok((ast, inductions, removed))
+    });
+    let substituted = match substituted {
+        Ok((s, inductions, removed)) => {
+            for (var, label) in inductions {
+                println!("Substituted induction variable {} in loop {}", var, label);
+            }
+            for label in removed {
+                println!("Removed forward substituted assignment {}", label);
+            }
+            s
+        }
+        Err(e) => {
+            eprintln!("Could not substitute variables of {}: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Open file for transformed AST, which has to be traced again
+    let file = match File::create(format!("{}_substituted.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!(
+                "Could not open {}_substituted.ast for writing: {}",
+                &project_name, e
+            );
+            return;
+        }
+    };
+
+    // Write transformed AST
+    let mut writer = BufWriter::new(file);
+    match ir::print_ast(&substituted, &mut writer) {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("Could not write {}_substituted.ast: {}", &project_name, e);
+        }
+    }
+}
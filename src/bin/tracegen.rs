@@ -10,7 +10,7 @@ use std::{
 use vectorizer::{codegen, ir};
 
 fn print_usage(prog_name: &str) {
-    eprintln!("Usage: {} project_name", prog_name);
+    eprintln!("Usage: {} project_name [f90|c|rs] [text|binary]", prog_name);
 }
 
 fn main() {
@@ -34,8 +34,37 @@ fn main() {
         }
     };
 
+    // Language of the tracing program
+    let lang = match arg_iter.next() {
+        Some(l) => match l.as_str() {
+            "f90" | "c" | "rs" => l,
+            _ => {
+                print_usage(&prog_name);
+                return;
+            }
+        },
+        None => "f90".to_owned(),
+    };
+
+    // Format of the trace written by the tracing program
+    let binary = match arg_iter.next() {
+        Some(f) => match f.as_str() {
+            "text" => false,
+            "binary" => true,
+            _ => {
+                print_usage(&prog_name);
+                return;
+            }
+        },
+        None => false,
+    };
+    if binary && lang != "f90" {
+        eprintln!("Binary traces are only written by Fortran tracing programs");
+        return;
+    }
+
     // Open AST file
-    let mut ast_file = match File::open(&format!("{}.ast", &project_name)) {
+    let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Could not open {}.ast: {}", &project_name, e);
@@ -64,24 +93,27 @@ fn main() {
     };
 
     // Open file for tracing output file
-    let file = match File::create(&format!("{}.f90", &project_name)) {
+    let file_name = format!("{}.{}", &project_name, &lang);
+    let file = match File::create(&file_name) {
         Ok(f) => f,
         Err(e) => {
-            eprintln!("Could not open {}.f90 for writing: {}", &project_name, e);
+            eprintln!("Could not open {} for writing: {}", &file_name, e);
             return;
         }
     };
 
     // Generate tracing code
     let writer = BufWriter::new(file);
-    match codegen::generate_trace(&ast, writer) {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!(
-                "Could not generate trace program {}.f90: {}",
-                &project_name, e
-            );
-            return;
+    let result = match lang.as_str() {
+        "c" => codegen::c::generate_c_trace(&ast, writer),
+        "rs" => codegen::rust::generate_rust_trace(&ast, writer),
+        _ if binary => {
+            let trace_name = format!("{}.btrace", &project_name);
+            codegen::generate_binary_trace(&ast, writer, &trace_name)
         }
+        _ => codegen::generate_trace(&ast, writer),
+    };
+    if let Err(e) = result {
+        eprintln!("Could not generate trace program {}: {}", &file_name, e);
     }
 }
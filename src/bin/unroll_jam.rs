@@ -0,0 +1,147 @@
+extern crate petgraph;
+extern crate serde_json;
+extern crate vectorizer;
+
+use std::{
+    env,
+    fs::File,
+    io::{BufReader, BufWriter, Read},
+};
+use vectorizer::{
+    dependencies::{LevelDependency, Statement},
+    ir, transform,
+};
+
+fn print_usage(prog_name: &str) {
+    eprintln!("Usage: {} project_name loop_label factor", prog_name);
+}
+
+fn main() {
+    let mut arg_iter = env::args();
+
+    // Program name
+    let prog_name = match arg_iter.next() {
+        Some(p) => p,
+        None => {
+            print_usage("./unroll_jam");
+            return;
+        }
+    };
+
+    // Project name
+    let project_name = match arg_iter.next() {
+        Some(n) => n,
+        None => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
+    // Label of the loop to unroll
+    let label = match arg_iter.next().map(|l| l.parse::<i32>()) {
+        Some(Ok(l)) => l,
+        Some(Err(e)) => {
+            eprintln!("Invalid loop label: {}", e);
+            return;
+        }
+        None => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
+    // Unroll factor
+    let factor = match arg_iter.next().map(|f| f.parse::<i32>()) {
+        Some(Ok(f)) => f,
+        Some(Err(e)) => {
+            eprintln!("Invalid unroll factor: {}", e);
+            return;
+        }
+        None => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
+    // Open dependency graph file
+    let graph_file = match File::open(format!("{}.graph", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}.graph: {}", &project_name, e);
+            return;
+        }
+    };
+    let inp = BufReader::new(graph_file);
+
+    // Deserialize dependency graph
+    let graph: petgraph::Graph<Statement, Vec<LevelDependency>> = match serde_json::from_reader(inp)
+    {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("Could not deserialize {}.graph: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Open AST file
+    let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Could not open {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Read AST
+    let mut ir_text = String::new();
+    if let Err(e) = ast_file.read_to_string(&mut ir_text) {
+        eprintln!("Error while reading IR from {}.ast: {}", &project_name, e);
+    }
+
+    // Fix IR input for parser
+    ir_text.push_str(" $");
+    let ir_text = ir_text.replace('\n', " ");
+    let ir_text = ir_text.replace('\t', " ");
+
+    // Parse AST
+    let ast = match ir::parse_ast(&ir_text) {
+        Ok((_, ast)) => ast,
+        Err(e) => {
+            eprintln!("Could not parse {}.ast: {}", &project_name, e);
+            return;
+        }
+    };
+
+    // Unroll and jam loop
+    let unrolled = match transform::unroll_and_jam(&ast, &graph, label, factor) {
+        Ok(u) => u,
+        Err(e) => {
+            eprintln!(
+                "Could not unroll loop {} of {}: {}",
+                label, &project_name, e
+            );
+            return;
+        }
+    };
+
+    // Open file for transformed AST, which can be traced again
+    let file = match File::create(format!("{}_unrolled.ast", &project_name)) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!(
+                "Could not open {}_unrolled.ast for writing: {}",
+                &project_name, e
+            );
+            return;
+        }
+    };
+
+    // Write transformed AST
+    let mut writer = BufWriter::new(file);
+    match ir::print_ast(&unrolled, &mut writer) {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("Could not write {}_unrolled.ast: {}", &project_name, e);
+        }
+    }
+}
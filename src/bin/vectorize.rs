@@ -9,10 +9,14 @@ use std::{
 };
 use vectorizer::{
     dependencies::{LevelDependency, Statement},
-    ir, vectorization, };
+    ir, vectorization,
+};
 
 fn print_usage(prog_name: &str) {
-    eprintln!("Usage: {} project_name", prog_name);
+    eprintln!(
+        "Usage: {} project_name [array|omp|concurrent|forall|c|c-ivdep|c-vector [width]|rust|numpy]",
+        prog_name
+    );
 }
 
 fn main() {
@@ -36,8 +40,36 @@ fn main() {
         }
     };
 
+    // Kind of code generated for vectorizable statements
+    let mode = match arg_iter.next() {
+        Some(m) => match vectorization::Mode::from_name(&m) {
+            Some(m) => m,
+            None => {
+                print_usage(&prog_name);
+                return;
+            }
+        },
+        None => vectorization::Mode::Array,
+    };
+
+    // Number of elements of the vector types
+    let mode = match (mode, arg_iter.next()) {
+        (vectorization::Mode::CVector(_), Some(w)) => match w.parse::<usize>() {
+            Ok(w) if w.is_power_of_two() => vectorization::Mode::CVector(w),
+            _ => {
+                eprintln!("Vector width has to be a power of two: {}", w);
+                return;
+            }
+        },
+        (m, None) => m,
+        (_, Some(_)) => {
+            print_usage(&prog_name);
+            return;
+        }
+    };
+
     // Open dependency graph file
-    let graph_file = match File::open(&format!("{}.graph", &project_name)) {
+    let graph_file = match File::open(format!("{}.graph", &project_name)) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Could not open {}.graph: {}", &project_name, e);
@@ -57,7 +89,7 @@ fn main() {
     };
 
     // Open AST file
-    let mut ast_file = match File::open(&format!("{}.ast", &project_name)) {
+    let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Could not open {}.ast: {}", &project_name, e);
@@ -86,24 +118,21 @@ fn main() {
     };
 
     // Open file for vectorization output
-    let file = match File::create(&format!("{}_vectorized.f90", &project_name)) {
+    let file_name = format!("{}_{}", &project_name, mode.file_suffix());
+    let file = match File::create(&file_name) {
         Ok(f) => f,
         Err(e) => {
-            eprintln!(
-                "Could not open {}_vectorized.f90 for writing: {}",
-                &project_name, e
-            );
+            eprintln!("Could not open {} for writing: {}", &file_name, e);
             return;
         }
     };
 
     // Generate vector code
     let writer = BufWriter::new(file);
-    match vectorization::vectorize(&graph, &ast, writer, false) {
+    match vectorization::vectorize_with(&graph, &ast, writer, false, mode) {
         Ok(_) => (),
         Err(e) => {
             eprintln!("Could not vectorize {}: {}", &project_name, e);
-            return;
         }
     }
 }
@@ -38,7 +38,7 @@ fn main() {
     };
 
     // Open dependency graph file
-    let graph_file = match File::open(&format!("{}.graph", &project_name)) {
+    let graph_file = match File::open(format!("{}.graph", &project_name)) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Could not open {}.graph: {}", &project_name, e);
@@ -58,7 +58,7 @@ fn main() {
     };
 
     // Open AST file
-    let mut ast_file = match File::open(&format!("{}.ast", &project_name)) {
+    let mut ast_file = match File::open(format!("{}.ast", &project_name)) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Could not open {}.ast: {}", &project_name, e);
@@ -87,7 +87,7 @@ fn main() {
     };
 
     // Open file for vectorization output
-    let file = match File::create(&format!("{}_vectorized_fold.f90", &project_name)) {
+    let file = match File::create(format!("{}_vectorized_fold.f90", &project_name)) {
         Ok(f) => f,
         Err(e) => {
             eprintln!(
@@ -104,7 +104,6 @@ fn main() {
         Ok(_) => (),
         Err(e) => {
             eprintln!("Could not vectorize {}: {}", &project_name, e);
-            return;
         }
     }
 }
@@ -0,0 +1,628 @@
+use super::precedence::{binop_parens, unop_parens};
+use ir::*;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+use transform::Affine;
+
+mod simd;
+
+pub fn generate_c_trace<T>(ast: &Ast, out: T) -> io::Result<()>
+where
+    T: io::Write,
+{
+    let mut cg = CCodegen::new(CTracer::default(), ast, out);
+
+    cg.generate_ast(ast)
+}
+
+pub fn generate_c_program<T>(ast: &Ast, out: T) -> io::Result<()>
+where
+    T: io::Write,
+{
+    let mut cg = CCodegen::new(CProgram, ast, out);
+
+    cg.generate_ast(ast)
+}
+
+/// C99 code generator, arrays are stored in column-major order like in
+/// Fortran and indexed relative to their declared lower bounds
+pub struct CCodegen<G, W> {
+    generator: G,
+    writer: CWriter<W>,
+    /// Number of elements of the vector types, if any are declared
+    vector_width: Option<usize>,
+}
+
+/// Output together with the declared variables
+pub struct CWriter<W> {
+    out: W,
+    dimensions: HashMap<String, Vec<(i32, i32)>>,
+    reals: HashSet<String>,
+}
+
+pub trait CGenerator<W> {
+    fn log_loop_begin(
+        &mut self,
+        w: &mut CWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()>;
+
+    fn log_loop_end(&mut self, w: &mut CWriter<W>, loop_node: &Loop, indent: u8) -> io::Result<()>;
+
+    fn log_loop_update(&self, w: &mut CWriter<W>, loop_node: &Loop, indent: u8) -> io::Result<()>;
+
+    fn log_use(
+        &self,
+        w: &mut CWriter<W>,
+        expr: &Expression,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()>;
+
+    fn log_def(&self, w: &mut CWriter<W>, var: &Variable, indent: u8, label: i32)
+        -> io::Result<()>;
+}
+
+/// Prints trace lines in the format read by the dependency analysis
+#[derive(Default)]
+pub struct CTracer {
+    loop_indices: HashSet<String>,
+}
+
+/// Plain program without trace output
+pub struct CProgram;
+
+impl CTracer {
+    fn log_access<W: io::Write>(
+        &self,
+        w: &mut CWriter<W>,
+        var: &Variable,
+        category: &str,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        write!(
+            w.out,
+            "    {}printf(\" {:03} {} {}",
+            &indentation(indent),
+            label,
+            &var.name,
+            category
+        )?;
+        for _ in var.indices.iter() {
+            write!(w.out, " %d")?;
+        }
+        write!(w.out, "\\n\"")?;
+
+        for expr in var.indices.iter() {
+            write!(w.out, ", ")?;
+            w.expression(expr)?;
+        }
+        writeln!(w.out, ");")?;
+
+        for expr in var.indices.iter() {
+            self.log_use_expression(w, expr, indent, label)?;
+        }
+
+        Ok(())
+    }
+
+    fn log_use_expression<W: io::Write>(
+        &self,
+        w: &mut CWriter<W>,
+        expr: &Expression,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        match expr {
+            Expression::Integer(_) | Expression::Real(_) => Ok(()),
+            Expression::Expression(expr) => self.log_use_expression(w, expr, indent, label),
+            Expression::Variable(var) => {
+                if self.loop_indices.contains(&var.name) {
+                    Ok(())
+                } else {
+                    self.log_access(w, var, "USE", indent, label)
+                }
+            }
+            Expression::UnOp(op) => self.log_use_expression(w, &op.right, indent, label),
+            Expression::BinOp(op) => {
+                self.log_use_expression(w, &op.left, indent, label)?;
+                self.log_use_expression(w, &op.right, indent, label)
+            }
+        }
+    }
+}
+
+impl<W: io::Write> CGenerator<W> for CTracer {
+    fn log_loop_begin(
+        &mut self,
+        w: &mut CWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()> {
+        self.loop_indices.insert(loop_node.var.to_owned());
+
+        writeln!(
+            w.out,
+            "    {}printf(\" {} {} loop begin\\n\");",
+            &indentation(indent),
+            loop_node.label,
+            &loop_node.var
+        )
+    }
+
+    fn log_loop_end(&mut self, w: &mut CWriter<W>, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        self.loop_indices.remove(&loop_node.var);
+
+        writeln!(
+            w.out,
+            "    {}printf(\" {} {} loop end\\n\");",
+            &indentation(indent),
+            loop_node.label,
+            &loop_node.var
+        )
+    }
+
+    fn log_loop_update(&self, w: &mut CWriter<W>, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        writeln!(
+            w.out,
+            "    {}printf(\" {} {} %d\\n\", {});",
+            &indentation(indent),
+            loop_node.label,
+            &loop_node.var,
+            &loop_node.var
+        )
+    }
+
+    fn log_use(
+        &self,
+        w: &mut CWriter<W>,
+        expr: &Expression,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        self.log_use_expression(w, expr, indent, label)
+    }
+
+    fn log_def(
+        &self,
+        w: &mut CWriter<W>,
+        var: &Variable,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        self.log_access(w, var, "DEF", indent, label)
+    }
+}
+
+impl<W: io::Write> CGenerator<W> for CProgram {
+    fn log_loop_begin(
+        &mut self,
+        _w: &mut CWriter<W>,
+        _loop_node: &Loop,
+        _indent: u8,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_loop_end(
+        &mut self,
+        _w: &mut CWriter<W>,
+        _loop_node: &Loop,
+        _indent: u8,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_loop_update(
+        &self,
+        _w: &mut CWriter<W>,
+        _loop_node: &Loop,
+        _indent: u8,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_use(
+        &self,
+        _w: &mut CWriter<W>,
+        _expr: &Expression,
+        _indent: u8,
+        _label: i32,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_def(
+        &self,
+        _w: &mut CWriter<W>,
+        _var: &Variable,
+        _indent: u8,
+        _label: i32,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<G: CGenerator<W>, W: io::Write> CCodegen<G, W> {
+    pub fn new(generator: G, ast: &Ast, out: W) -> Self {
+        let dimensions = ast
+            .vardef
+            .iter()
+            .map(|d| (d.name.to_owned(), d.dimensions.clone()))
+            .collect();
+        let reals = ast
+            .vardef
+            .iter()
+            .filter(|d| match d.dtype {
+                DefinitionType::Real => true,
+                DefinitionType::Integer => false,
+            })
+            .map(|d| d.name.to_owned())
+            .collect();
+
+        CCodegen {
+            generator,
+            writer: CWriter {
+                out,
+                dimensions,
+                reals,
+            },
+            vector_width: None,
+        }
+    }
+
+    /// Declares vector types of `width` elements and aligns the arrays to
+    /// their size
+    pub fn new_vector(generator: G, ast: &Ast, out: W, width: usize) -> Self {
+        let mut cg = CCodegen::new(generator, ast, out);
+        cg.vector_width = Some(width);
+
+        cg
+    }
+
+    pub fn generate_ast(&mut self, ast: &Ast) -> io::Result<()> {
+        self.generate_header(ast)?;
+        self.generate_stmtlist(&ast.statements.0, 0)?;
+        self.generate_footer(ast)
+    }
+
+    pub fn generate_header(&mut self, ast: &Ast) -> io::Result<()> {
+        writeln!(
+            self.writer.out,
+            "/* Compilers for Parallel Systems\n \
+             * 185.A64 SS 2018 H. Moritsch\n \
+             * C generated from EFL source\n \
+             */\n"
+        )?;
+
+        writeln!(self.writer.out, "#include <stdio.h>\n")?;
+        writeln!(
+            self.writer.out,
+            "#define MIN(a, b) ((a) < (b) ? (a) : (b))\n\
+             #define MAX(a, b) ((a) > (b) ? (a) : (b))\n"
+        )?;
+
+        if let Some(width) = self.vector_width {
+            self.generate_vector_types(width)?;
+        }
+
+        for def in ast.vardef.iter() {
+            self.generate_definition(def)?;
+        }
+
+        writeln!(self.writer.out, "\n/* program {} */", &ast.name)?;
+        writeln!(self.writer.out, "int main(void)\n{{")
+    }
+
+    pub fn generate_footer(&mut self, _ast: &Ast) -> io::Result<()> {
+        writeln!(self.writer.out, "\n    return 0;\n}}")
+    }
+
+    /// Declare arrays with reversed dimensions, which keeps the memory layout
+    /// of the Fortran program
+    fn generate_definition(&mut self, def: &Definition) -> io::Result<()> {
+        match def.dtype {
+            DefinitionType::Integer => write!(self.writer.out, "static int {}", &def.name)?,
+            DefinitionType::Real => write!(self.writer.out, "static float {}", &def.name)?,
+        }
+
+        for (lb, ub) in def.dimensions.iter().rev() {
+            write!(self.writer.out, "[{}]", ub - lb + 1)?;
+        }
+
+        if let Some(width) = self.vector_width {
+            if !def.dimensions.is_empty() {
+                write!(
+                    self.writer.out,
+                    " __attribute__((aligned({})))",
+                    width * ELEMENT_SIZE
+                )?;
+            }
+        }
+
+        if def.dimensions.is_empty() {
+            writeln!(self.writer.out, ";")
+        } else {
+            let bounds = def
+                .dimensions
+                .iter()
+                .map(|(lb, ub)| format!("{}:{}", lb, ub))
+                .collect::<Vec<_>>();
+            writeln!(
+                self.writer.out,
+                "; /* {}({}) */",
+                &def.name,
+                bounds.join(",")
+            )
+        }
+    }
+
+    fn generate_stmtlist(&mut self, stmtlist: &[Statement], indent: u8) -> io::Result<()> {
+        for statement in stmtlist {
+            match statement {
+                Statement::Loop(l) => self.generate_loop(l, indent)?,
+                Statement::Assignment(a) => self.generate_assignment(a, indent)?,
+                Statement::If(i) => self.generate_if(i, indent)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn generate_loop(&mut self, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        self.generate_loop_start(loop_node, indent)?;
+        self.generate_stmtlist(&loop_node.statements.0, indent + 1)?;
+        self.generate_loop_end(loop_node, indent)
+    }
+
+    pub fn generate_loop_start(&mut self, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        self.generator
+            .log_loop_begin(&mut self.writer, loop_node, indent)?;
+
+        let var = &loop_node.var;
+        write!(
+            self.writer.out,
+            "    {}for ({} = ",
+            &indentation(indent),
+            var
+        )?;
+        self.writer.expression(&loop_node.lower)?;
+
+        // The direction of the loop is only known for constant steps
+        match (&loop_node.step, constant_step(loop_node)) {
+            (None, _) => {
+                write!(self.writer.out, "; {} <= ", var)?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, "; {}++", var)?;
+            }
+            (Some(_), Some(s)) => {
+                let cmp = if s < 0 { ">=" } else { "<=" };
+                write!(self.writer.out, "; {} {} ", var, cmp)?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, "; {} += {}", var, s)?;
+            }
+            (Some(step), None) => {
+                write!(self.writer.out, "; (")?;
+                self.writer.expression(step)?;
+                write!(self.writer.out, ") > 0 ? {} <= ", var)?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, " : {} >= ", var)?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, "; {} += ", var)?;
+                self.writer.expression(step)?;
+            }
+        }
+        writeln!(self.writer.out, ") {{ // {:03}", loop_node.label)?;
+
+        self.generator
+            .log_loop_update(&mut self.writer, loop_node, indent + 1)
+    }
+
+    pub fn generate_loop_end(&mut self, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        writeln!(self.writer.out, "    {}}}", &indentation(indent))?;
+
+        self.generator
+            .log_loop_end(&mut self.writer, loop_node, indent)
+    }
+
+    pub fn generate_assignment(&mut self, assignment: &Assign, indent: u8) -> io::Result<()> {
+        self.generator
+            .log_def(&mut self.writer, &assignment.lhs, indent, assignment.label)?;
+        self.generator
+            .log_use(&mut self.writer, &assignment.rhs, indent, assignment.label)?;
+
+        write!(self.writer.out, "    {}", &indentation(indent))?;
+        self.writer.variable(&assignment.lhs)?;
+        write!(self.writer.out, " = ")?;
+        self.writer.expression(&assignment.rhs)?;
+        writeln!(self.writer.out, "; // {:03}", assignment.label)
+    }
+
+    fn generate_if(&mut self, if_stat: &If, indent: u8) -> io::Result<()> {
+        write!(self.writer.out, "    {}if (", &indentation(indent))?;
+        self.writer.expression(&if_stat.expr)?;
+        writeln!(self.writer.out, ") {{ // {:03}", if_stat.label)?;
+
+        self.generate_stmtlist(&if_stat.then_branch.0, indent + 1)?;
+        writeln!(self.writer.out, "    {}}} else {{", &indentation(indent))?;
+        self.generate_stmtlist(&if_stat.else_branch.0, indent + 1)?;
+
+        writeln!(self.writer.out, "    {}}}", &indentation(indent))
+    }
+
+    /// Line in front of the next loop, e.g. a pragma
+    pub fn generate_line(&mut self, line: &str, indent: u8) -> io::Result<()> {
+        writeln!(self.writer.out, "    {}{}", &indentation(indent), line)
+    }
+}
+
+impl<W: io::Write> CWriter<W> {
+    fn variable(&mut self, var: &Variable) -> io::Result<()> {
+        write!(self.out, "{}", &var.name)?;
+
+        let dimensions = self.dimensions.get(&var.name).cloned().unwrap_or_default();
+        for (i, expr) in var.indices.iter().enumerate().rev() {
+            let lb = dimensions.get(i).map(|(lb, _)| *lb).unwrap_or(1);
+            write!(self.out, "[")?;
+            self.index(expr, lb)?;
+            write!(self.out, "]")?;
+        }
+
+        Ok(())
+    }
+
+    /// Index relative to the lower bound of the dimension
+    fn index(&mut self, expr: &Expression, lb: i32) -> io::Result<()> {
+        if lb == 0 {
+            return self.expression(expr);
+        }
+
+        match Affine::from_expr(expr).and_then(|a| a.add(&Affine::constant(-lb))) {
+            Some(a) => self.expression(&a.to_expr()),
+            None => {
+                write!(self.out, "(")?;
+                self.expression(expr)?;
+                write!(self.out, ") - {}", lb)
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> io::Result<()> {
+        match expr {
+            Expression::Integer(i) => write!(self.out, "{}", i),
+            Expression::Real(f) => write!(self.out, "{:?}", f),
+            Expression::Variable(var) => self.variable(var),
+            Expression::BinOp(op) => self.binop(op),
+            Expression::UnOp(op) => self.unop(op),
+            Expression::Expression(expr) => self.expression(expr),
+        }
+    }
+
+    fn operand(&mut self, expr: &Expression, parens: bool) -> io::Result<()> {
+        if parens {
+            write!(self.out, "(")?;
+            self.expression(expr)?;
+            write!(self.out, ")")
+        } else {
+            self.expression(expr)
+        }
+    }
+
+    fn unop(&mut self, op: &UnOp) -> io::Result<()> {
+        match op.op {
+            OpType::Minus => write!(self.out, "-")?,
+            OpType::Not => write!(self.out, "!")?,
+            _ => (),
+        }
+
+        self.operand(&op.right, unop_parens(&op.op, &op.right))
+    }
+
+    fn binop(&mut self, op: &BinOp) -> io::Result<()> {
+        if is_intrinsic(&op.op) {
+            match op.op {
+                OpType::Max => write!(self.out, "MAX(")?,
+                _ => write!(self.out, "MIN(")?,
+            }
+            self.expression(&op.left)?;
+            write!(self.out, ", ")?;
+            self.expression(&op.right)?;
+            return write!(self.out, ")");
+        }
+
+        self.operand(&op.left, binop_parens(&op.op, &op.left, false))?;
+
+        match operator(&op.op) {
+            Some(symbol) => write!(self.out, " {} ", symbol)?,
+            None => {
+                return Err(io::Error::other(format!(
+                    "Invalid binary operator {:?}",
+                    op.op
+                )))
+            }
+        }
+
+        self.operand(&op.right, binop_parens(&op.op, &op.right, true))
+    }
+}
+
+/// Size of `int` and `float` in bytes
+const ELEMENT_SIZE: usize = 4;
+
+/// Step of the loop, if it is a constant
+pub fn constant_step(loop_node: &Loop) -> Option<i32> {
+    match &loop_node.step {
+        Some(step) => Affine::from_expr(step)
+            .filter(|a| a.is_constant())
+            .map(|a| a.constant),
+        None => Some(1),
+    }
+}
+
+fn operator(op: &OpType) -> Option<&'static str> {
+    match op {
+        OpType::Plus => Some("+"),
+        OpType::Minus => Some("-"),
+        OpType::Mul => Some("*"),
+        OpType::Div => Some("/"),
+        OpType::Equal => Some("=="),
+        OpType::NotEqual => Some("!="),
+        OpType::Greater => Some(">"),
+        OpType::GreaterEqual => Some(">="),
+        OpType::Lower => Some("<"),
+        OpType::LowerEqual => Some("<="),
+        OpType::And => Some("&&"),
+        OpType::Or => Some("||"),
+        OpType::Not | OpType::Min | OpType::Max => None,
+    }
+}
+
+fn is_intrinsic(op: &OpType) -> bool {
+    matches!(op, OpType::Min | OpType::Max)
+}
+
+fn indentation(indent: u8) -> String {
+    " ".repeat(4 * indent as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::tests::parse;
+    use vectorization::{
+        tests::{generate, graph, NEST},
+        Mode,
+    };
+
+    #[test]
+    fn traces_column_major_arrays() {
+        let mut out = Vec::new();
+        generate_c_trace(&parse(NEST), &mut out).unwrap();
+        let trace = String::from_utf8(out).unwrap();
+
+        assert!(trace.contains("static float a[20][20]; /* a(1:20,1:20) */\n"));
+        assert!(trace.contains(
+            "            printf(\" 004 a DEF %d %d\\n\", i, j);
+            printf(\" 004 a USE %d %d\\n\", i, j);
+            a[j - 1][i - 1] = a[j - 1][i - 1] * 2.0; // 004
+"
+        ));
+        assert!(trace.contains("        b[i - 1] = b[i - 2] + 1.0; // 002\n"));
+    }
+
+    #[test]
+    fn annotates_independent_loops() {
+        for (mode, pragma) in [(Mode::C, "omp simd"), (Mode::CIvdep, "GCC ivdep")].iter() {
+            let program = generate(NEST, &graph(), *mode);
+            assert!(program.contains(&format!(
+                "        #pragma {}\n        for (j = 1; j <= 10; j++) {{ // 003\n",
+                pragma
+            )));
+            assert_eq!(program.matches("#pragma").count(), 1);
+        }
+    }
+}
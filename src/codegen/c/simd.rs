@@ -0,0 +1,297 @@
+use super::*;
+use transform::{binop, contiguous, paren, uses_var};
+
+/// Innermost loop executed with vector types
+struct Vector<'a> {
+    var: &'a str,
+    /// Constant lower bound of the loop, needed for aligned accesses
+    lower: Option<i32>,
+    width: usize,
+    scalar: &'static str,
+}
+
+impl<G: CGenerator<W>, W: io::Write> CCodegen<G, W> {
+    /// Aligned and unaligned vector types, which may alias their elements
+    pub(super) fn generate_vector_types(&mut self, width: usize) -> io::Result<()> {
+        for scalar in &["float", "int"] {
+            writeln!(
+                self.writer.out,
+                "typedef {0} v{0} __attribute__((vector_size({1}), may_alias));",
+                scalar,
+                width * ELEMENT_SIZE
+            )?;
+            writeln!(
+                self.writer.out,
+                "typedef {0} v{0}_u __attribute__((vector_size({1}), aligned({2}), may_alias));",
+                scalar,
+                width * ELEMENT_SIZE,
+                ELEMENT_SIZE
+            )?;
+        }
+
+        writeln!(self.writer.out)
+    }
+
+    /// Innermost loop of a statement without carried dependencies. The loop
+    /// executes `width` iterations at once with vector types and the
+    /// remaining iterations in a scalar epilogue.
+    pub fn generate_vector_loop(
+        &mut self,
+        assignment: &Assign,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()> {
+        let width = match self.vector_width {
+            Some(w) => w,
+            None => return Err(io::Error::other("No vector types declared")),
+        };
+
+        let var = &loop_node.var;
+        if constant_step(loop_node) != Some(1) || !self.writer.vectorizable(assignment, var) {
+            eprintln!(
+                "Statement {} can not use vector types, generating a scalar loop",
+                assignment.label
+            );
+            self.generate_loop_start(loop_node, indent)?;
+            self.generate_assignment(assignment, indent + 1)?;
+            return self.generate_loop_end(loop_node, indent);
+        }
+
+        let vector = Vector {
+            var,
+            lower: Affine::from_expr(&loop_node.lower)
+                .filter(|a| a.is_constant())
+                .map(|a| a.constant),
+            width,
+            scalar: if self.writer.reals.contains(&assignment.lhs.name) {
+                "float"
+            } else {
+                "int"
+            },
+        };
+
+        // Last iteration, at which a whole vector still fits
+        let upper = Affine::from_expr(&loop_node.upper)
+            .and_then(|ub| ub.add(&Affine::constant(1 - width as i32)))
+            .map(|a| a.to_expr())
+            .unwrap_or_else(|| {
+                binop(
+                    OpType::Minus,
+                    paren(&loop_node.upper),
+                    Expression::Integer(width as i32 - 1),
+                )
+            });
+
+        write!(
+            self.writer.out,
+            "    {}for ({} = ",
+            &indentation(indent),
+            var
+        )?;
+        self.writer.expression(&loop_node.lower)?;
+        write!(self.writer.out, "; {} <= ", var)?;
+        self.writer.expression(&upper)?;
+        writeln!(
+            self.writer.out,
+            "; {} += {}) {{ // {:03}",
+            var, width, loop_node.label
+        )?;
+
+        write!(self.writer.out, "    {}", &indentation(indent + 1))?;
+        self.writer.vector_access(&assignment.lhs, &vector)?;
+        write!(self.writer.out, " = ")?;
+        if !uses_var(&assignment.rhs, var) {
+            write!(self.writer.out, "(v{}){{0}} + ", vector.scalar)?;
+        }
+        self.writer.vector_expression(&assignment.rhs, &vector)?;
+        writeln!(self.writer.out, "; // {:03}", assignment.label)?;
+        writeln!(self.writer.out, "    {}}}", &indentation(indent))?;
+
+        // Scalar epilogue
+        write!(
+            self.writer.out,
+            "    {}for (; {} <= ",
+            &indentation(indent),
+            var
+        )?;
+        self.writer.expression(&loop_node.upper)?;
+        writeln!(self.writer.out, "; {}++) {{ // {:03}", var, loop_node.label)?;
+        self.generate_assignment(assignment, indent + 1)?;
+        writeln!(self.writer.out, "    {}}}", &indentation(indent))
+    }
+}
+
+impl<W: io::Write> CWriter<W> {
+    /// Arithmetic on contiguous accesses of the element type of the
+    /// assigned array
+    fn vectorizable(&self, assignment: &Assign, var: &str) -> bool {
+        let real = self.reals.contains(&assignment.lhs.name);
+
+        !assignment.lhs.indices.is_empty()
+            && contiguous(&assignment.lhs, var)
+            && self.vectorizable_expression(&assignment.rhs, var, real)
+    }
+
+    fn vectorizable_expression(&self, expr: &Expression, var: &str, real: bool) -> bool {
+        // Invariant parts are converted to the element type
+        if !uses_var(expr, var) {
+            return real || !self.is_real(expr);
+        }
+
+        match expr {
+            Expression::Integer(_) | Expression::Real(_) => true,
+            Expression::Variable(v) => {
+                !v.indices.is_empty() && contiguous(v, var) && self.reals.contains(&v.name) == real
+            }
+            Expression::UnOp(op) => {
+                matches!(op.op, OpType::Minus) && self.vectorizable_expression(&op.right, var, real)
+            }
+            Expression::BinOp(op) => {
+                matches!(
+                    op.op,
+                    OpType::Plus | OpType::Minus | OpType::Mul | OpType::Div
+                ) && self.vectorizable_expression(&op.left, var, real)
+                    && self.vectorizable_expression(&op.right, var, real)
+            }
+            Expression::Expression(expr) => self.vectorizable_expression(expr, var, real),
+        }
+    }
+
+    /// Whether the access starts at a multiple of the vector size from the
+    /// aligned start of the array in every iteration of the vector loop
+    fn aligned(&self, v: &Variable, vector: &Vector) -> bool {
+        let (lower, dimensions) = match (vector.lower, self.dimensions.get(&v.name)) {
+            (Some(lower), Some(dimensions)) => (lower, dimensions),
+            _ => return false,
+        };
+
+        let width = vector.width as i64;
+        let mut offset = 0;
+        let mut stride = 1;
+        for (i, (index, (lb, ub))) in v.indices.iter().zip(dimensions.iter()).enumerate() {
+            let index = Affine::from_expr(index).map(|a| {
+                if i == 0 {
+                    a.without(vector.var).add(&Affine::constant(lower))
+                } else {
+                    Some(a)
+                }
+            });
+
+            match index {
+                Some(Some(ref a)) if a.is_constant() => {
+                    offset += i64::from(a.constant - lb) * stride;
+                }
+                _ if i == 0 => return false,
+                _ => {
+                    if stride % width != 0 {
+                        return false;
+                    }
+                }
+            }
+
+            stride *= i64::from(ub - lb + 1);
+        }
+
+        offset % width == 0
+    }
+
+    fn vector_access(&mut self, v: &Variable, vector: &Vector) -> io::Result<()> {
+        let suffix = if self.aligned(v, vector) { "" } else { "_u" };
+        write!(self.out, "*(v{}{} *)&", vector.scalar, suffix)?;
+
+        self.variable(v)
+    }
+
+    fn vector_expression(&mut self, expr: &Expression, vector: &Vector) -> io::Result<()> {
+        if !uses_var(expr, vector.var) {
+            return match expr {
+                Expression::Integer(i) => write!(self.out, "{}", i),
+                Expression::Real(f) => write!(self.out, "{:?}f", f),
+                _ => {
+                    write!(self.out, "({})(", vector.scalar)?;
+                    self.expression(expr)?;
+                    write!(self.out, ")")
+                }
+            };
+        }
+
+        match expr {
+            Expression::Variable(v) => self.vector_access(v, vector),
+            Expression::UnOp(op) => {
+                write!(self.out, "-")?;
+                self.vector_operand(&op.right, vector, unop_parens(&op.op, &op.right))
+            }
+            Expression::BinOp(op) => {
+                self.vector_operand(&op.left, vector, binop_parens(&op.op, &op.left, false))?;
+                if let Some(symbol) = operator(&op.op) {
+                    write!(self.out, " {} ", symbol)?;
+                }
+                self.vector_operand(&op.right, vector, binop_parens(&op.op, &op.right, true))
+            }
+            Expression::Expression(expr) => self.vector_expression(expr, vector),
+            Expression::Integer(_) | Expression::Real(_) => Ok(()),
+        }
+    }
+
+    /// Invariant operands are printed as a cast and need no parentheses
+    fn vector_operand(
+        &mut self,
+        expr: &Expression,
+        vector: &Vector,
+        parens: bool,
+    ) -> io::Result<()> {
+        if parens && uses_var(expr, vector.var) {
+            write!(self.out, "(")?;
+            self.vector_expression(expr, vector)?;
+            write!(self.out, ")")
+        } else {
+            self.vector_expression(expr, vector)
+        }
+    }
+
+    fn is_real(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Integer(_) => false,
+            Expression::Real(_) => true,
+            Expression::Variable(v) => self.reals.contains(&v.name),
+            Expression::UnOp(op) => self.is_real(&op.right),
+            Expression::BinOp(op) => self.is_real(&op.left) || self.is_real(&op.right),
+            Expression::Expression(expr) => self.is_real(expr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::Graph;
+    use vectorization::{
+        tests::{generate, graph, NEST, SHIFTED},
+        Mode,
+    };
+
+    #[test]
+    fn vector_loop_with_scalar_epilogue() {
+        let mut graph = Graph::new();
+        graph.add_node(2);
+
+        let program = generate(SHIFTED, &graph, Mode::CVector(4));
+        assert!(program.contains(
+            "    for (i = 1; i <= 15; i += 4) { // 001
+        *(vfloat *)&a[i - 1] = *(vfloat_u *)&b[i] * 2.0f; // 002
+    }
+    for (; i <= 18; i++) { // 001
+        a[i - 1] = b[i] * 2.0; // 002
+    }
+"
+        ));
+    }
+
+    #[test]
+    fn strided_accesses_stay_scalar() {
+        let program = generate(NEST, &graph(), Mode::CVector(4));
+        assert!(program.contains(
+            "        for (j = 1; j <= 10; j++) { // 003\n            a[j - 1][i - 1] = a[j - 1][i - 1] * 2.0; // 004\n"
+        ));
+        assert!(!program.contains("*(vfloat"));
+    }
+}
@@ -1,9 +1,18 @@
+pub mod c;
+pub mod numpy;
+pub mod rust;
+
+mod precedence;
+
+use self::precedence::{binop_parens, unop_parens};
+use dependencies::binary;
 use ir::*;
 use std::{
     collections::{HashMap, HashSet},
     io,
     marker::PhantomData,
 };
+use transform::{fresh_name, simplify, used_names, uses_var, Affine};
 
 pub fn generate_trace<T>(ast: &Ast, out: T) -> io::Result<()>
 where
@@ -14,6 +23,37 @@ where
     cg.generate_ast(ast)
 }
 
+/// Tracing program writing a binary trace to `file_name` with unformatted
+/// stream I/O, see `dependencies::binary` for the format
+pub fn generate_binary_trace<T>(ast: &Ast, out: T, file_name: &str) -> io::Result<()>
+where
+    T: io::Write,
+{
+    let mut cg: Codegen<Tracer<_>, _> = Codegen::new(out);
+    cg.generator.binary = Some(BinaryTrace::new(ast, file_name));
+
+    cg.generate_ast(ast)
+}
+
+pub fn generate_program<T>(ast: &Ast, out: T) -> io::Result<()>
+where
+    T: io::Write,
+{
+    // Without loop replacements the vectorizer prints statements unchanged
+    let mut cg: Codegen<Vectorizer<_>, _> = Codegen::new(out);
+
+    cg.generate_ast(ast)
+}
+
+/// Fortran source of a single expression, e.g. to refer to it in messages
+pub fn expression_source(expr: &Expression) -> String {
+    let mut cg: Codegen<Vectorizer<_>, _> = Codegen::new(Vec::new());
+
+    // Writing to memory does not fail
+    let _ = generate_expression(&cg.generator, &mut cg.out, expr);
+    String::from_utf8_lossy(&cg.out).into_owned()
+}
+
 pub struct Codegen<G, W> {
     generator: G,
     out: W,
@@ -21,13 +61,82 @@ pub struct Codegen<G, W> {
 
 struct Tracer<'a, W: 'a> {
     loop_indices: HashSet<String>,
+    binary: Option<BinaryTrace>,
     tracer_type: PhantomData<&'a W>,
 }
 
+/// Unit of the binary trace file and ids of the variables in its string
+/// table
+struct BinaryTrace {
+    file_name: String,
+    unit: String,
+    ids: HashMap<String, usize>,
+}
+
+impl BinaryTrace {
+    fn new(ast: &Ast, file_name: &str) -> Self {
+        let ids = ast
+            .vardef
+            .iter()
+            .enumerate()
+            .map(|(id, def)| (def.name.to_owned(), id))
+            .collect();
+
+        BinaryTrace {
+            file_name: file_name.to_owned(),
+            unit: fresh_name("trace", &mut used_names(ast)),
+            ids,
+        }
+    }
+
+    /// Statement writing a record, which starts with the given fields and
+    /// ends with the values of `expressions`
+    fn write_record<'a, W, G>(
+        &self,
+        gen: &G,
+        out: &mut W,
+        indent: u8,
+        fields: &[i32],
+        expressions: &'a [Expression],
+    ) -> io::Result<()>
+    where
+        W: io::Write,
+        G: Generator<'a, G, W>,
+    {
+        write!(out, "    {}write ({}) ", &indentation(indent), &self.unit)?;
+
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                write!(out, ", ")?;
+            }
+            write!(out, "{}", field)?;
+        }
+
+        for expr in expressions {
+            write!(out, ", ")?;
+            generate_expression(gen, out, expr)?;
+        }
+
+        writeln!(out)
+    }
+
+    fn variable_id(&self, name: &str) -> io::Result<i32> {
+        match self.ids.get(name) {
+            Some(id) => Ok(*id as i32),
+            None => Err(io::Error::other(format!(
+                "Variable {} is not declared",
+                name
+            ))),
+        }
+    }
+}
+
 pub struct Vectorizer<'a, W: 'a> {
     loop_replacement: HashMap<String, &'a Loop>,
     tracer_type: PhantomData<W>,
     folding: bool,
+    /// Statement currently generated, named in folding diagnostics
+    label: i32,
 }
 
 impl<'a, W: io::Write> Tracer<'a, W> {
@@ -54,8 +163,15 @@ impl<'a, W: io::Write> Tracer<'a, W> {
         indent: u8,
         label: i32,
     ) -> io::Result<()> {
-        if !self.loop_indices.contains(&var.name) {
-            if var.indices.len() > 0 {
+        if let Some(binary) = self
+            .binary
+            .as_ref()
+            .filter(|_| !self.loop_indices.contains(&var.name))
+        {
+            let id = binary.variable_id(&var.name)?;
+            binary.write_record(self, out, indent, &[binary::USE, label, id], &var.indices)?;
+        } else if !self.loop_indices.contains(&var.name) {
+            if !var.indices.is_empty() {
                 write!(
                     out,
                     "    {}write (*,'(a,{}(x,i0))') ' {:03} {} USE'",
@@ -70,7 +186,7 @@ impl<'a, W: io::Write> Tracer<'a, W> {
                     generate_expression(self, out, expr)?;
                 }
 
-                writeln!(out, "")?;
+                writeln!(out)?;
             } else {
                 writeln!(
                     out,
@@ -108,6 +224,10 @@ impl<'a, W: io::Write> Tracer<'a, W> {
 pub trait Generator<'a, G, W> {
     fn instantiate(folding: bool) -> Self;
 
+    fn log_header(&mut self, out: &mut W, ast: &'a Ast) -> io::Result<()>;
+
+    fn log_footer(&mut self, out: &mut W) -> io::Result<()>;
+
     fn log_loop_begin(&mut self, out: &mut W, loop_node: &'a Loop, indent: u8) -> io::Result<()>;
 
     fn log_loop_end(&mut self, out: &mut W, loop_node: &'a Loop, indent: u8) -> io::Result<()>;
@@ -121,12 +241,14 @@ pub trait Generator<'a, G, W> {
     fn index_expression(&self, out: &mut W, expr: &'a Expression) -> io::Result<()>;
 
     fn set_loop_data(&mut self, loop_replacement: HashMap<String, &'a Loop>);
+
+    fn set_label(&mut self, label: i32);
 }
 
 impl<'a, W> Vectorizer<'a, W> {
     fn check_expr(&self, expr: &'a Expression) -> bool {
         match expr {
-            Expression::Variable(var) => self.loop_replacement.get(&var.name).is_some(),
+            Expression::Variable(var) => self.loop_replacement.contains_key(&var.name),
             Expression::Integer(_) | Expression::Real(_) => false,
             Expression::UnOp(op) => self.check_expr(&op.right),
             Expression::BinOp(op) => self.check_expr(&op.left) || self.check_expr(&op.right),
@@ -134,19 +256,46 @@ impl<'a, W> Vectorizer<'a, W> {
         }
     }
 
-    fn build_expr(&self, expr: &'a Expression, upper: bool, replacing: bool) -> Expression {
+    /// Stride of the section built for `expr`, derived from the coefficient
+    /// of the replaced loop variable and the step of its loop
+    fn section_stride(&self, expr: &'a Expression) -> Option<Expression> {
+        let affine = Affine::from_expr(expr)?;
+        let (var, l) = self
+            .loop_replacement
+            .iter()
+            .find(|(var, _)| affine.uses(var))?;
+        let coefficient = affine.coefficient(var);
+
+        match (&l.step, coefficient) {
+            (None, 1) => None,
+            (None, c) => Some(Expression::Integer(c)),
+            (Some(Expression::Integer(s)), c) => match s.checked_mul(c) {
+                Some(1) => None,
+                Some(stride) => Some(Expression::Integer(stride)),
+                None => None,
+            },
+            (Some(step), 1) => Some(step.clone()),
+            (Some(step), c) => Some(Expression::BinOp(Box::new(BinOp {
+                op: OpType::Mul,
+                left: Expression::Integer(c),
+                right: step.clone(),
+            }))),
+        }
+    }
+
+    fn build_expr(&self, expr: &'a Expression, upper: bool) -> Expression {
         match expr {
             Expression::Variable(var) => {
                 if let Some(l) = self.loop_replacement.get(&var.name) {
                     if upper {
-                        self.build_expr(&l.upper, upper, true)
+                        self.build_expr(&l.upper, upper)
                     } else {
-                        self.build_expr(&l.lower, upper, true)
+                        self.build_expr(&l.lower, upper)
                     }
                 } else {
                     let mut indices = Vec::new();
                     for index in var.indices.iter() {
-                        indices.push(self.build_expr(index, upper, false));
+                        indices.push(self.build_expr(index, upper));
                     }
 
                     Expression::Variable(Variable {
@@ -157,233 +306,17 @@ impl<'a, W> Vectorizer<'a, W> {
             }
             Expression::Integer(i) => Expression::Integer(*i),
             Expression::Real(r) => Expression::Real(*r),
-            Expression::BinOp(op) => {
-                if replacing {
-                    Expression::Expression(Box::new(Expression::BinOp(Box::new(BinOp {
-                        op: op.op.clone(),
-                        left: self.build_expr(&op.left, upper, false),
-                        right: self.build_expr(&op.right, upper, false),
-                    }))))
-                } else {
-                    Expression::BinOp(Box::new(BinOp {
-                        op: op.op.clone(),
-                        left: self.build_expr(&op.left, upper, false),
-                        right: self.build_expr(&op.right, upper, false),
-                    }))
-                }
-            }
-            Expression::UnOp(op) => {
-                if replacing {
-                    Expression::Expression(Box::new(Expression::UnOp(Box::new(UnOp {
-                        op: op.op.clone(),
-                        right: self.build_expr(&op.right, upper, false),
-                    }))))
-                } else {
-                    Expression::UnOp(Box::new(UnOp {
-                        op: op.op.clone(),
-                        right: self.build_expr(&op.right, upper, false),
-                    }))
-                }
-            }
-            Expression::Expression(expr) => {
-                Expression::Expression(Box::new(self.build_expr(expr, upper, false)))
-            }
-        }
-    }
-
-    fn fold_expr(&self, expr: &'a Expression) -> Expression {
-        match expr {
-            Expression::Variable(var) => {
-                let mut indices = Vec::new();
-                for index in var.indices.iter() {
-                    indices.push(self.fold_expr(index));
-                }
-
-                Expression::Variable(Variable {
-                    name: var.name.to_owned(),
-                    indices,
-                })
-            }
-            Expression::Integer(i) => Expression::Integer(*i),
-            Expression::Real(r) => Expression::Real(*r),
-            Expression::BinOp(op) => {
-                let left = self.fold_expr(&op.left);
-                let right = self.fold_expr(&op.right);
-
-                match left {
-                    Expression::Integer(i_left) => match right {
-                        Expression::Integer(i_right) => match op.op {
-                            OpType::Plus => Expression::Integer(i_left + i_right),
-                            OpType::Minus => Expression::Integer(i_left - i_right),
-                            OpType::Mul => Expression::Integer(i_left * i_right),
-                            OpType::Div => Expression::Integer(i_left / i_right),
-                            OpType::Equal => Expression::Integer((i_left == i_right) as i32),
-                            OpType::NotEqual => Expression::Integer((i_left != i_right) as i32),
-                            OpType::Greater => Expression::Integer((i_left > i_right) as i32),
-                            OpType::GreaterEqual => Expression::Integer((i_left >= i_right) as i32),
-                            OpType::Lower => Expression::Integer((i_left < i_right) as i32),
-                            OpType::LowerEqual => Expression::Integer((i_left <= i_right) as i32),
-                            OpType::And => {
-                                Expression::Integer(((i_left != 0) && (i_right != 0)) as i32)
-                            }
-                            OpType::Or => {
-                                Expression::Integer(((i_left != 0) || (i_right != 0)) as i32)
-                            }
-                            OpType::Not => Expression::Integer((i_right == 0) as i32),
-                        },
-                        Expression::Real(r_right) => match op.op {
-                            OpType::Plus => Expression::Real(i_left as f64 + r_right),
-                            OpType::Minus => Expression::Real(i_left as f64 - r_right),
-                            OpType::Mul => Expression::Real(i_left as f64 * r_right),
-                            OpType::Div => Expression::Real(i_left as f64 / r_right),
-                            OpType::Equal => Expression::Integer((i_left as f64 == r_right) as i32),
-                            OpType::NotEqual => {
-                                Expression::Integer((i_left as f64 != r_right) as i32)
-                            }
-                            OpType::Greater => {
-                                Expression::Integer((i_left as f64 > r_right) as i32)
-                            }
-                            OpType::GreaterEqual => {
-                                Expression::Integer((i_left as f64 >= r_right) as i32)
-                            }
-                            OpType::Lower => {
-                                Expression::Integer(((i_left as f64) < r_right) as i32)
-                            }
-                            OpType::LowerEqual => {
-                                Expression::Integer((i_left as f64 <= r_right) as i32)
-                            }
-                            OpType::And => {
-                                Expression::Integer(((i_left != 0) && (r_right != 0.0)) as i32)
-                            }
-                            OpType::Or => {
-                                Expression::Integer(((i_left != 0) || (r_right != 0.0)) as i32)
-                            }
-                            OpType::Not => Expression::Integer((r_right == 0.0) as i32),
-                        },
-                        _ => Expression::BinOp(Box::new(BinOp {
-                            op: op.op.clone(),
-                            left,
-                            right,
-                        })),
-                    },
-                    Expression::Real(r_left) => match right {
-                        Expression::Integer(i_right) => match op.op {
-                            OpType::Plus => Expression::Real(r_left + (i_right as f64)),
-                            OpType::Minus => Expression::Real(r_left - (i_right as f64)),
-                            OpType::Mul => Expression::Real(r_left * (i_right as f64)),
-                            OpType::Div => Expression::Real(r_left / (i_right as f64)),
-                            OpType::Equal => {
-                                Expression::Integer((r_left == (i_right as f64)) as i32)
-                            }
-                            OpType::NotEqual => {
-                                Expression::Integer((r_left != (i_right as f64)) as i32)
-                            }
-                            OpType::Greater => {
-                                Expression::Integer((r_left > (i_right as f64)) as i32)
-                            }
-                            OpType::GreaterEqual => {
-                                Expression::Integer((r_left >= (i_right as f64)) as i32)
-                            }
-                            OpType::Lower => {
-                                Expression::Integer((r_left < (i_right as f64)) as i32)
-                            }
-                            OpType::LowerEqual => {
-                                Expression::Integer((r_left <= (i_right as f64)) as i32)
-                            }
-                            OpType::And => {
-                                Expression::Integer(((r_left != 0.0) && (i_right != 0)) as i32)
-                            }
-                            OpType::Or => {
-                                Expression::Integer(((r_left != 0.0) || (i_right != 0)) as i32)
-                            }
-                            OpType::Not => Expression::Integer((i_right == 0) as i32),
-                        },
-                        Expression::Real(r_right) => match op.op {
-                            OpType::Plus => Expression::Real(r_left + r_right),
-                            OpType::Minus => Expression::Real(r_left - r_right),
-                            OpType::Mul => Expression::Real(r_left * r_right),
-                            OpType::Div => Expression::Real(r_left / r_right),
-                            OpType::Equal => Expression::Integer((r_left == r_right) as i32),
-                            OpType::NotEqual => Expression::Integer((r_left != r_right) as i32),
-                            OpType::Greater => Expression::Integer((r_left > r_right) as i32),
-                            OpType::GreaterEqual => Expression::Integer((r_left >= r_right) as i32),
-                            OpType::Lower => Expression::Integer((r_left < r_right) as i32),
-                            OpType::LowerEqual => Expression::Integer((r_left <= r_right) as i32),
-                            OpType::And => {
-                                Expression::Integer(((r_left != 0.0) && (r_right != 0.0)) as i32)
-                            }
-                            OpType::Or => {
-                                Expression::Integer(((r_left != 0.0) || (r_right != 0.0)) as i32)
-                            }
-                            OpType::Not => Expression::Integer((r_right == 0.0) as i32),
-                        },
-                        _ => Expression::BinOp(Box::new(BinOp {
-                            op: op.op.clone(),
-                            left,
-                            right,
-                        })),
-                    },
-                    _ => Expression::BinOp(Box::new(BinOp {
-                        op: op.op.clone(),
-                        left,
-                        right,
-                    })),
-                }
-            }
-            Expression::UnOp(op) => {
-                let right = self.fold_expr(&op.right);
-
-                match right {
-                    Expression::Integer(i_right) => match op.op {
-                        OpType::Plus => Expression::Integer(i_right),
-                        OpType::Minus => Expression::Integer(-i_right),
-                        OpType::Mul => Expression::Integer(i_right),
-                        OpType::Div => Expression::Integer(i_right),
-                        OpType::Equal => Expression::Integer((i_right == i_right) as i32),
-                        OpType::NotEqual => Expression::Integer((i_right != i_right) as i32),
-                        OpType::Greater => Expression::Integer((i_right > i_right) as i32),
-                        OpType::GreaterEqual => Expression::Integer((i_right >= i_right) as i32),
-                        OpType::Lower => Expression::Integer((i_right < i_right) as i32),
-                        OpType::LowerEqual => Expression::Integer((i_right <= i_right) as i32),
-                        OpType::And => {
-                            Expression::Integer(((i_right != 0) && (i_right != 0)) as i32)
-                        }
-                        OpType::Or => {
-                            Expression::Integer(((i_right != 0) || (i_right != 0)) as i32)
-                        }
-                        OpType::Not => Expression::Integer((i_right == 0) as i32),
-                    },
-                    Expression::Real(r_right) => match op.op {
-                        OpType::Plus => Expression::Real(r_right),
-                        OpType::Minus => Expression::Real(-r_right),
-                        OpType::Mul => Expression::Real(r_right),
-                        OpType::Div => Expression::Real(r_right),
-                        OpType::Equal => Expression::Integer((r_right == r_right) as i32),
-                        OpType::NotEqual => Expression::Integer((r_right != r_right) as i32),
-                        OpType::Greater => Expression::Integer((r_right > r_right) as i32),
-                        OpType::GreaterEqual => Expression::Integer((r_right >= r_right) as i32),
-                        OpType::Lower => Expression::Integer((r_right < r_right) as i32),
-                        OpType::LowerEqual => Expression::Integer((r_right <= r_right) as i32),
-                        OpType::And => {
-                            Expression::Integer(((r_right != 0.0) && (r_right != 0.0)) as i32)
-                        }
-                        OpType::Or => {
-                            Expression::Integer(((r_right != 0.0) || (r_right != 0.0)) as i32)
-                        }
-                        OpType::Not => Expression::Integer((r_right == 0.0) as i32),
-                    },
-                    _ => Expression::UnOp(Box::new(UnOp {
-                        op: op.op.clone(),
-                        right,
-                    })),
-                }
-            }
+            Expression::BinOp(op) => Expression::BinOp(Box::new(BinOp {
+                op: op.op.clone(),
+                left: self.build_expr(&op.left, upper),
+                right: self.build_expr(&op.right, upper),
+            })),
+            Expression::UnOp(op) => Expression::UnOp(Box::new(UnOp {
+                op: op.op.clone(),
+                right: self.build_expr(&op.right, upper),
+            })),
             Expression::Expression(expr) => {
-                let expr = self.fold_expr(expr);
-                match expr {
-                    Expression::Integer(_) | Expression::Real(_) => expr,
-                    _ => Expression::Expression(Box::new(expr)),
-                }
+                Expression::Expression(Box::new(self.build_expr(expr, upper)))
             }
         }
     }
@@ -395,9 +328,18 @@ impl<'a, W: io::Write> Generator<'a, Vectorizer<'a, W>, W> for Vectorizer<'a, W>
             loop_replacement: HashMap::new(),
             tracer_type: PhantomData,
             folding,
+            label: 0,
         }
     }
 
+    fn log_header(&mut self, _out: &mut W, _ast: &'a Ast) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_footer(&mut self, _out: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
     fn log_loop_begin(
         &mut self,
         _out: &mut W,
@@ -438,9 +380,9 @@ impl<'a, W: io::Write> Generator<'a, Vectorizer<'a, W>, W> for Vectorizer<'a, W>
     fn index_expression(&self, out: &mut W, expr: &'a Expression) -> io::Result<()> {
         if self.check_expr(expr) {
             let lower = {
-                let expr = self.build_expr(expr, false, false);
+                let expr = self.build_expr(expr, false);
                 if self.folding {
-                    self.fold_expr(&expr)
+                    simplify(&fold_expr(&expr, self.label)?)
                 } else {
                     expr
                 }
@@ -450,14 +392,23 @@ impl<'a, W: io::Write> Generator<'a, Vectorizer<'a, W>, W> for Vectorizer<'a, W>
             write!(out, ":")?;
 
             let upper = {
-                let expr = self.build_expr(expr, true, false);
+                let expr = self.build_expr(expr, true);
                 if self.folding {
-                    self.fold_expr(&expr)
+                    simplify(&fold_expr(&expr, self.label)?)
                 } else {
                     expr
                 }
             };
-            generate_expression(self, out, &upper)
+            generate_expression(self, out, &upper)?;
+
+            // Decreasing subscripts and loops with a step select every n-th
+            // element of the section
+            if let Some(stride) = self.section_stride(expr) {
+                write!(out, ":")?;
+                generate_expression(self, out, &stride)?;
+            }
+
+            Ok(())
         } else {
             generate_expression(self, out, expr)
         }
@@ -466,19 +417,78 @@ impl<'a, W: io::Write> Generator<'a, Vectorizer<'a, W>, W> for Vectorizer<'a, W>
     fn set_loop_data(&mut self, loop_replacement: HashMap<String, &'a Loop>) {
         self.loop_replacement = loop_replacement;
     }
+
+    fn set_label(&mut self, label: i32) {
+        self.label = label;
+    }
 }
 
 impl<'a, W: io::Write> Generator<'a, Tracer<'a, W>, W> for Tracer<'a, W> {
     fn instantiate(_folding: bool) -> Self {
         Tracer {
             loop_indices: HashSet::new(),
+            binary: None,
             tracer_type: PhantomData,
         }
     }
 
+    /// Declare and open the binary trace file and write its string table
+    fn log_header(&mut self, out: &mut W, ast: &'a Ast) -> io::Result<()> {
+        let binary = match &self.binary {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+
+        writeln!(out, "integer :: {}\n", &binary.unit)?;
+        writeln!(
+            out,
+            "open (newunit={}, file='{}', access='stream', form='unformatted', \
+             status='replace')",
+            &binary.unit, &binary.file_name
+        )?;
+        writeln!(
+            out,
+            "write ({}) '{}', {}, {}",
+            &binary.unit,
+            binary::MAGIC,
+            binary::VERSION,
+            ast.vardef.len()
+        )?;
+
+        for def in ast.vardef.iter() {
+            writeln!(
+                out,
+                "write ({}) {}, {}, '{}'",
+                &binary.unit,
+                def.dimensions.len(),
+                def.name.len(),
+                &def.name
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn log_footer(&mut self, out: &mut W) -> io::Result<()> {
+        match &self.binary {
+            Some(binary) => writeln!(out, "\nclose ({})", &binary.unit),
+            None => Ok(()),
+        }
+    }
+
     fn log_loop_begin(&mut self, out: &mut W, loop_node: &'a Loop, indent: u8) -> io::Result<()> {
         self.loop_indices.insert(loop_node.var.to_owned());
 
+        if let Some(binary) = &self.binary {
+            return binary.write_record(
+                self,
+                out,
+                indent,
+                &[binary::LOOP_BEGIN, loop_node.label],
+                &[],
+            );
+        }
+
         writeln!(
             out,
             "    {1:}write (*,'(a)')         ' {0:} {2:} loop begin'",
@@ -491,6 +501,16 @@ impl<'a, W: io::Write> Generator<'a, Tracer<'a, W>, W> for Tracer<'a, W> {
     fn log_loop_end(&mut self, out: &mut W, loop_node: &'a Loop, indent: u8) -> io::Result<()> {
         self.loop_indices.remove(&loop_node.var);
 
+        if let Some(binary) = &self.binary {
+            return binary.write_record(
+                self,
+                out,
+                indent,
+                &[binary::LOOP_END, loop_node.label],
+                &[],
+            );
+        }
+
         writeln!(
             out,
             "    {1:}write (*,'(a)')         ' {0:} {2:} loop end'",
@@ -501,6 +521,18 @@ impl<'a, W: io::Write> Generator<'a, Tracer<'a, W>, W> for Tracer<'a, W> {
     }
 
     fn log_loop_update(&self, out: &mut W, loop_node: &'a Loop, indent: u8) -> io::Result<()> {
+        if let Some(binary) = &self.binary {
+            return writeln!(
+                out,
+                "    {}write ({}) {}, {}, {}",
+                &indentation(indent),
+                &binary.unit,
+                binary::LOOP_UPDATE,
+                loop_node.label,
+                &loop_node.var
+            );
+        }
+
         writeln!(
             out,
             "    {1:}write (*,'(a,i0)')      ' {0:} {2:} ', {2:}",
@@ -515,7 +547,10 @@ impl<'a, W: io::Write> Generator<'a, Tracer<'a, W>, W> for Tracer<'a, W> {
     }
 
     fn log_def(&self, out: &mut W, var: &'a Variable, indent: u8, label: i32) -> io::Result<()> {
-        if var.indices.len() > 0 {
+        if let Some(binary) = &self.binary {
+            let id = binary.variable_id(&var.name)?;
+            binary.write_record(self, out, indent, &[binary::DEF, label, id], &var.indices)?;
+        } else if !var.indices.is_empty() {
             write!(
                 out,
                 "    {}write (*,'(a,{}(x,i0))') ' {:03} {} DEF'",
@@ -530,7 +565,7 @@ impl<'a, W: io::Write> Generator<'a, Tracer<'a, W>, W> for Tracer<'a, W> {
                 generate_expression(self, out, expr)?;
             }
 
-            writeln!(out, "")?;
+            writeln!(out)?;
         } else {
             writeln!(
                 out,
@@ -553,6 +588,8 @@ impl<'a, W: io::Write> Generator<'a, Tracer<'a, W>, W> for Tracer<'a, W> {
     }
 
     fn set_loop_data(&mut self, _loop_replacement: HashMap<String, &'a Loop>) {}
+
+    fn set_label(&mut self, _label: i32) {}
 }
 
 impl<'a, G: Generator<'a, G, W>, W: io::Write> Codegen<G, W> {
@@ -599,17 +636,19 @@ impl<'a, G: Generator<'a, G, W>, W: io::Write> Codegen<G, W> {
         for def in ast.vardef.iter() {
             self.generate_definition(def)?;
         }
-        writeln!(self.out, "")
+        self.generator.log_header(&mut self.out, ast)?;
+        writeln!(self.out)
     }
 
     pub fn generate_footer(&mut self, ast: &'a Ast) -> io::Result<()> {
-        writeln!(self.out, "")?;
+        self.generator.log_footer(&mut self.out)?;
+        writeln!(self.out)?;
         writeln!(self.out, "end program {}", &ast.name)
     }
 
     pub fn generate_loop_vec_start(&mut self, loop_node: &'a Loop, c: i32) -> io::Result<()> {
         self.generator
-            .log_loop_begin(&mut self.out, &loop_node, c as u8)?;
+            .log_loop_begin(&mut self.out, loop_node, c as u8)?;
 
         write!(
             self.out,
@@ -620,17 +659,147 @@ impl<'a, G: Generator<'a, G, W>, W: io::Write> Codegen<G, W> {
         generate_expression(&self.generator, &mut self.out, &loop_node.lower)?;
         write!(self.out, ", ")?;
         generate_expression(&self.generator, &mut self.out, &loop_node.upper)?;
-        writeln!(self.out, "")?;
+        if let Some(step) = &loop_node.step {
+            write!(self.out, ", ")?;
+            generate_expression(&self.generator, &mut self.out, step)?;
+        }
+        writeln!(self.out)?;
 
         self.generator
-            .log_loop_update(&mut self.out, &loop_node, c as u8 + 1)
+            .log_loop_update(&mut self.out, loop_node, c as u8 + 1)
     }
 
     pub fn generate_loop_vec_end(&mut self, loop_node: &'a Loop, c: i32) -> io::Result<()> {
         writeln!(&mut self.out, "    {}end do", &indentation(c as u8))?;
 
         self.generator
-            .log_loop_end(&mut self.out, &loop_node, c as u8)
+            .log_loop_end(&mut self.out, loop_node, c as u8)
+    }
+
+    /// Directive running the following loop in parallel
+    pub fn generate_omp_parallel_do(
+        &mut self,
+        private: &[&str],
+        simd: bool,
+        c: i32,
+    ) -> io::Result<()> {
+        write!(self.out, "    {}!$omp parallel do", &indentation(c as u8))?;
+        if simd {
+            write!(self.out, " simd")?;
+        }
+        if !private.is_empty() {
+            write!(self.out, " private({})", private.join(", "))?;
+        }
+        writeln!(self.out)
+    }
+
+    /// Loops around a statement without carried dependencies. Unless the
+    /// statement is already `parallel`, the outermost loop is run in
+    /// parallel, the innermost one is a SIMD loop.
+    pub fn generate_omp_loops(
+        &mut self,
+        assignment: &'a Assign,
+        loops: &[&'a Loop],
+        c: i32,
+        parallel: bool,
+    ) -> io::Result<()> {
+        for (i, loop_node) in loops.iter().enumerate() {
+            let innermost = i == loops.len() - 1;
+            if i == 0 && !parallel {
+                let private = loops[1..]
+                    .iter()
+                    .map(|l| l.var.as_str())
+                    .collect::<Vec<_>>();
+                self.generate_omp_parallel_do(&private, innermost, c)?;
+            } else if innermost {
+                writeln!(
+                    self.out,
+                    "    {}!$omp simd",
+                    &indentation(c as u8 + i as u8)
+                )?;
+            }
+
+            self.generate_loop_vec_start(loop_node, c + i as i32)?;
+        }
+
+        self.generate_assignment(assignment, (c as usize + loops.len()) as u8)?;
+
+        for (i, loop_node) in loops.iter().enumerate().rev() {
+            self.generate_loop_vec_end(loop_node, c + i as i32)?;
+        }
+
+        Ok(())
+    }
+
+    /// `do concurrent` or `forall` constructs around a statement without
+    /// carried dependencies. Loops share a construct unless their bounds
+    /// depend on one of its indices.
+    pub fn generate_concurrent_loops(
+        &mut self,
+        assignment: &'a Assign,
+        loops: &[&'a Loop],
+        c: i32,
+        forall: bool,
+    ) -> io::Result<()> {
+        if loops.is_empty() {
+            return self.generate_assignment(assignment, c as u8);
+        }
+
+        let mut groups: Vec<Vec<&'a Loop>> = Vec::new();
+        for l in loops {
+            let dependent = groups.last().is_none_or(|group| {
+                group.iter().any(|outer| {
+                    uses_var(&l.lower, &outer.var)
+                        || uses_var(&l.upper, &outer.var)
+                        || l.step.as_ref().is_some_and(|s| uses_var(s, &outer.var))
+                })
+            });
+
+            if dependent {
+                groups.push(vec![l]);
+            } else if let Some(group) = groups.last_mut() {
+                group.push(l);
+            }
+        }
+
+        for (i, group) in groups.iter().enumerate() {
+            let keyword = if forall { "forall" } else { "do concurrent" };
+            write!(
+                self.out,
+                "    {}{} (",
+                &indentation(c as u8 + i as u8),
+                keyword
+            )?;
+
+            for (j, l) in group.iter().enumerate() {
+                if j > 0 {
+                    write!(self.out, ", ")?;
+                }
+                write!(self.out, "{} = ", &l.var)?;
+                generate_expression(&self.generator, &mut self.out, &l.lower)?;
+                write!(self.out, ":")?;
+                generate_expression(&self.generator, &mut self.out, &l.upper)?;
+                if let Some(step) = &l.step {
+                    write!(self.out, ":")?;
+                    generate_expression(&self.generator, &mut self.out, step)?;
+                }
+            }
+            writeln!(self.out, ")")?;
+        }
+
+        self.generate_assignment(assignment, (c as usize + groups.len()) as u8)?;
+
+        for i in (0..groups.len()).rev() {
+            let keyword = if forall { "end forall" } else { "end do" };
+            writeln!(
+                self.out,
+                "    {}{}",
+                &indentation(c as u8 + i as u8),
+                keyword
+            )?;
+        }
+
+        Ok(())
     }
 
     fn generate_definition(&mut self, def: &'a Definition) -> io::Result<()> {
@@ -643,7 +812,7 @@ impl<'a, G: Generator<'a, G, W>, W: io::Write> Codegen<G, W> {
         if let Some((lb, ub)) = iter.next() {
             write!(self.out, ", dimension({}:{}", lb, ub)?;
 
-            while let Some((lb, ub)) = iter.next() {
+            for (lb, ub) in iter {
                 write!(self.out, ",{}:{}", lb, ub)?;
             }
 
@@ -670,8 +839,9 @@ impl<'a, G: Generator<'a, G, W>, W: io::Write> Codegen<G, W> {
     }
 
     fn generate_loop(&mut self, loop_node: &'a Loop, indent: u8) -> io::Result<()> {
+        self.generator.set_label(loop_node.label);
         self.generator
-            .log_loop_begin(&mut self.out, &loop_node, indent)?;
+            .log_loop_begin(&mut self.out, loop_node, indent)?;
 
         write!(
             self.out,
@@ -684,18 +854,23 @@ impl<'a, G: Generator<'a, G, W>, W: io::Write> Codegen<G, W> {
         generate_expression(&self.generator, &mut self.out, &loop_node.lower)?;
         write!(self.out, ", ")?;
         generate_expression(&self.generator, &mut self.out, &loop_node.upper)?;
-        writeln!(self.out, "")?;
+        if let Some(step) = &loop_node.step {
+            write!(self.out, ", ")?;
+            generate_expression(&self.generator, &mut self.out, step)?;
+        }
+        writeln!(self.out)?;
 
         self.generator
-            .log_loop_update(&mut self.out, &loop_node, indent + 1)?;
+            .log_loop_update(&mut self.out, loop_node, indent + 1)?;
         self.generate_stmtlist(&loop_node.statements.0, indent + 1)?;
 
         writeln!(self.out, "    {}end do", &indentation(indent))?;
         self.generator
-            .log_loop_end(&mut self.out, &loop_node, indent)
+            .log_loop_end(&mut self.out, loop_node, indent)
     }
 
     pub fn generate_assignment(&mut self, assignment: &'a Assign, indent: u8) -> io::Result<()> {
+        self.generator.set_label(assignment.label);
         self.generator
             .log_def(&mut self.out, &assignment.lhs, indent, assignment.label)?;
         self.generator
@@ -706,10 +881,11 @@ impl<'a, G: Generator<'a, G, W>, W: io::Write> Codegen<G, W> {
         generate_variable(&self.generator, &mut self.out, &assignment.lhs)?;
         write!(self.out, " = ")?;
         generate_expression(&self.generator, &mut self.out, &assignment.rhs)?;
-        writeln!(self.out, "")
+        writeln!(self.out)
     }
 
     fn generate_if(&mut self, if_stat: &'a If, indent: u8) -> io::Result<()> {
+        self.generator.set_label(if_stat.label);
         write!(
             self.out,
             "{:03} {}if (",
@@ -739,7 +915,7 @@ where
         write!(out, "(")?;
         gen.index_expression(out, expr)?;
 
-        while let Some(expr) = iter.next() {
+        for expr in iter {
             write!(out, ",")?;
             gen.index_expression(out, expr)?;
         }
@@ -761,11 +937,26 @@ where
         Expression::Variable(var) => generate_variable(gen, out, var),
         Expression::BinOp(op) => generate_binop(gen, out, op),
         Expression::UnOp(op) => generate_unop(gen, out, op),
-        Expression::Expression(expr) => {
-            write!(out, "(")?;
-            generate_expression(gen, out, expr)?;
-            write!(out, ")")
-        }
+        Expression::Expression(expr) => generate_expression(gen, out, expr),
+    }
+}
+
+fn generate_operand<'a, G, W>(
+    gen: &G,
+    out: &mut W,
+    expr: &'a Expression,
+    parens: bool,
+) -> io::Result<()>
+where
+    W: io::Write,
+    G: Generator<'a, G, W>,
+{
+    if parens {
+        write!(out, "(")?;
+        generate_expression(gen, out, expr)?;
+        write!(out, ")")
+    } else {
+        generate_expression(gen, out, expr)
     }
 }
 
@@ -788,9 +979,10 @@ where
         OpType::And => write!(out, " .and. ")?,
         OpType::Or => write!(out, " .or. ")?,
         OpType::Not => write!(out, " .not. ")?,
+        OpType::Min | OpType::Max => return generate_intrinsic(gen, out, &op.op, &[&op.right]),
     }
 
-    generate_expression(gen, out, &op.right)
+    generate_operand(gen, out, &op.right, unop_parens(&op.op, &op.right))
 }
 
 fn generate_binop<'a, G, W>(gen: &G, out: &mut W, op: &'a BinOp) -> io::Result<()>
@@ -798,7 +990,11 @@ where
     W: io::Write,
     G: Generator<'a, G, W>,
 {
-    generate_expression(gen, out, &op.left)?;
+    if let OpType::Min | OpType::Max = op.op {
+        return generate_intrinsic(gen, out, &op.op, &[&op.left, &op.right]);
+    }
+
+    generate_operand(gen, out, &op.left, binop_parens(&op.op, &op.left, false))?;
 
     match op.op {
         OpType::Plus => write!(out, "+")?,
@@ -814,9 +1010,35 @@ where
         OpType::And => write!(out, " .and. ")?,
         OpType::Or => write!(out, " .or. ")?,
         OpType::Not => write!(out, " .not. ")?,
+        OpType::Min | OpType::Max => unreachable!(),
+    }
+
+    generate_operand(gen, out, &op.right, binop_parens(&op.op, &op.right, true))
+}
+
+fn generate_intrinsic<'a, G, W>(
+    gen: &G,
+    out: &mut W,
+    op: &OpType,
+    args: &[&'a Expression],
+) -> io::Result<()>
+where
+    W: io::Write,
+    G: Generator<'a, G, W>,
+{
+    match op {
+        OpType::Max => write!(out, "max(")?,
+        _ => write!(out, "min(")?,
+    }
+
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        generate_expression(gen, out, arg)?;
     }
 
-    generate_expression(gen, out, &op.right)
+    write!(out, ")")
 }
 
 pub fn indentation(indent: u8) -> String {
@@ -0,0 +1,713 @@
+use super::c::constant_step;
+use super::precedence::{binop_parens, unop_parens};
+use ir::*;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+use transform::{collect_reads, loop_vars, substitute, unbound_names, uses_var, Access, Affine};
+
+/// Python code generator using NumPy, arrays keep the shape of their Fortran
+/// declaration and are indexed relative to its lower bounds
+pub struct NumpyCodegen<W> {
+    writer: PyWriter<W>,
+    /// Scalars passed as arguments and returned as result
+    scalars: Vec<String>,
+    statements: usize,
+}
+
+/// Output together with the declared variables
+pub struct PyWriter<W> {
+    out: W,
+    dimensions: HashMap<String, Vec<(i32, i32)>>,
+    reals: HashSet<String>,
+    /// Loops replaced by slices in the current statement
+    axes: Vec<Axis>,
+    /// Order of the axes of the assigned array section
+    order: Vec<String>,
+}
+
+/// Loop replaced by slices
+struct Axis {
+    var: String,
+    lower: Expression,
+    upper: Expression,
+    step: i32,
+}
+
+/// Reduction `s = s op e` of the expression `e` over the innermost loop
+struct Reduction<'a> {
+    op: OpType,
+    expr: &'a Expression,
+}
+
+impl<W: io::Write> NumpyCodegen<W> {
+    pub fn new(ast: &Ast, out: W) -> Self {
+        let dimensions = ast
+            .vardef
+            .iter()
+            .map(|d| (d.name.to_owned(), d.dimensions.clone()))
+            .collect();
+        let reals = ast
+            .vardef
+            .iter()
+            .filter(|d| match d.dtype {
+                DefinitionType::Real => true,
+                DefinitionType::Integer => false,
+            })
+            .map(|d| d.name.to_owned())
+            .collect();
+
+        NumpyCodegen {
+            writer: PyWriter {
+                out,
+                dimensions,
+                reals,
+                axes: Vec::new(),
+                order: Vec::new(),
+            },
+            scalars: Vec::new(),
+            statements: 0,
+        }
+    }
+
+    pub fn generate_header(&mut self, ast: &Ast) -> io::Result<()> {
+        writeln!(
+            self.writer.out,
+            "# Compilers for Parallel Systems\n\
+             # 185.A64 SS 2018 H. Moritsch\n\
+             # Python generated from EFL source\n"
+        )?;
+        writeln!(self.writer.out, "import numpy as np\n\n")?;
+        writeln!(
+            self.writer.out,
+            "def _div(a, b):\n    \
+             \"\"\"Integer division truncating towards zero like in Fortran\"\"\"\n    \
+             return np.trunc(np.true_divide(a, b)).astype(np.int32)\n\n"
+        )?;
+
+        // Loop variables are bound by the loops themselves, unless they are
+        // referenced outside of them
+        let mut loop_names = HashSet::new();
+        loop_vars(&ast.statements.0, &mut loop_names);
+        let mut outside = HashSet::new();
+        unbound_names(&ast.statements.0, &mut Vec::new(), &mut outside);
+
+        let mut arguments = Vec::new();
+        let mut docs = Vec::new();
+        for def in ast.vardef.iter() {
+            let (dtype, zero) = match def.dtype {
+                DefinitionType::Integer => ("int32", "0"),
+                DefinitionType::Real => ("float32", "0.0"),
+            };
+
+            if !def.dimensions.is_empty() {
+                let bounds = def
+                    .dimensions
+                    .iter()
+                    .map(|(lb, ub)| format!("{}:{}", lb, ub))
+                    .collect::<Vec<_>>();
+                let shape = def
+                    .dimensions
+                    .iter()
+                    .map(|(lb, ub)| format!("{}", ub - lb + 1))
+                    .collect::<Vec<_>>();
+                arguments.insert(docs.len(), def.name.to_owned());
+                docs.push(format!(
+                    "    {} -- {} array {}({}) of shape ({}{})",
+                    &def.name,
+                    dtype,
+                    &def.name,
+                    bounds.join(","),
+                    shape.join(", "),
+                    if shape.len() == 1 { "," } else { "" }
+                ));
+            } else if !loop_names.contains(&def.name) || outside.contains(&def.name) {
+                self.scalars.push(def.name.to_owned());
+                arguments.push(format!("{}={}", &def.name, zero));
+            }
+        }
+
+        writeln!(
+            self.writer.out,
+            "def {}({}):",
+            &ast.name,
+            arguments.join(", ")
+        )?;
+        writeln!(self.writer.out, "    \"\"\"program {}", &ast.name)?;
+        if !docs.is_empty() {
+            writeln!(self.writer.out)?;
+            for doc in docs {
+                writeln!(self.writer.out, "{}", doc)?;
+            }
+        }
+        if !self.scalars.is_empty() {
+            writeln!(self.writer.out)?;
+            writeln!(
+                self.writer.out,
+                "    Returns the scalars ({}).",
+                self.scalars.join(", ")
+            )?;
+        }
+        writeln!(self.writer.out, "    \"\"\"")
+    }
+
+    pub fn generate_footer(&mut self, _ast: &Ast) -> io::Result<()> {
+        match self.scalars.len() {
+            0 if self.statements == 0 => writeln!(self.writer.out, "    pass"),
+            0 => Ok(()),
+            _ => writeln!(self.writer.out, "    return {}", self.scalars.join(", ")),
+        }
+    }
+
+    pub fn generate_loop_start(&mut self, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        write!(
+            self.writer.out,
+            "    {}for {} in range(",
+            &indentation(indent),
+            &loop_node.var
+        )?;
+        self.writer.expression(&loop_node.lower)?;
+        write!(self.writer.out, ", ")?;
+
+        // Stop one step past the upper bound in the direction of the loop
+        match (&loop_node.step, constant_step(loop_node)) {
+            (_, Some(s)) => {
+                let upper = Affine::from_expr(&loop_node.upper)
+                    .and_then(|ub| ub.add(&Affine::constant(s.signum())));
+                match upper {
+                    Some(ub) => self.writer.expression(&ub.to_expr())?,
+                    None => {
+                        self.writer.operand(
+                            &loop_node.upper,
+                            binop_parens(&OpType::Plus, &loop_node.upper, false),
+                        )?;
+                        write!(self.writer.out, " {} 1", if s < 0 { "-" } else { "+" })?;
+                    }
+                }
+                if s != 1 {
+                    write!(self.writer.out, ", {}", s)?;
+                }
+            }
+            (Some(step), None) => {
+                self.writer.operand(
+                    &loop_node.upper,
+                    binop_parens(&OpType::Plus, &loop_node.upper, false),
+                )?;
+                write!(self.writer.out, " + np.sign(")?;
+                self.writer.expression(step)?;
+                write!(self.writer.out, "), ")?;
+                self.writer.expression(step)?;
+            }
+            (None, None) => (),
+        }
+
+        writeln!(self.writer.out, "):  # {:03}", loop_node.label)
+    }
+
+    /// Python loops end with their indentation
+    pub fn generate_loop_end(&mut self, _loop_node: &Loop, _indent: u8) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Statement inside of loops without carried dependencies. Loops are
+    /// replaced by slices, as long as every access has at most one of them
+    /// in each subscript and the sections can be broadcast to the section of
+    /// the assigned array. The remaining loops are kept.
+    pub fn generate_statement(
+        &mut self,
+        assignment: &Assign,
+        loops: &[&Loop],
+        indent: u8,
+    ) -> io::Result<()> {
+        let axes = slice_axes(assignment, loops, None);
+
+        let mut indent = indent;
+        for l in loops.iter().filter(|l| !axes.contains(&l.var)) {
+            self.generate_loop_start(l, indent)?;
+            indent += 1;
+        }
+
+        self.set_axes(loops, &axes, order(&assignment.lhs, &axes));
+        let result = self.generate_assignment(assignment, indent);
+        self.writer.axes.clear();
+
+        result
+    }
+
+    /// Scalar reductions over the innermost loop are generated with the
+    /// NumPy reduction functions. Returns false for any other statement.
+    pub fn generate_reduction(
+        &mut self,
+        assignment: &Assign,
+        loops: &[&Loop],
+        indent: u8,
+    ) -> io::Result<bool> {
+        let (innermost, outer) = match loops.split_last() {
+            Some(l) => l,
+            None => return Ok(false),
+        };
+        let reduction = match find_reduction(assignment) {
+            Some(r) => r,
+            None => return Ok(false),
+        };
+        let axes = slice_axes(assignment, &[innermost], Some(reduction.expr));
+        if axes.is_empty() {
+            return Ok(false);
+        }
+
+        let mut indent = indent;
+        for l in outer.iter() {
+            self.generate_loop_start(l, indent)?;
+            indent += 1;
+        }
+
+        self.statements += 1;
+        self.set_axes(&[innermost], &axes, axes.clone());
+
+        let s = &assignment.lhs.name;
+        write!(self.writer.out, "    {}{} = ", &indentation(indent), s)?;
+        match reduction.op {
+            OpType::Plus | OpType::Minus | OpType::Mul => {
+                let (symbol, function) = match reduction.op {
+                    OpType::Plus => ("+", "sum"),
+                    OpType::Minus => ("-", "sum"),
+                    _ => ("*", "prod"),
+                };
+                write!(self.writer.out, "{} {} np.{}(", s, symbol, function)?;
+                self.writer.expression(reduction.expr)?;
+                write!(self.writer.out, ")")?;
+            }
+            _ => {
+                let function = match reduction.op {
+                    OpType::Max => "max",
+                    _ => "min",
+                };
+                write!(self.writer.out, "np.{}(", function)?;
+                self.writer.expression(reduction.expr)?;
+                write!(self.writer.out, ", initial={})", s)?;
+            }
+        }
+        writeln!(self.writer.out, "  # {:03}", assignment.label)?;
+        self.writer.axes.clear();
+
+        Ok(true)
+    }
+
+    fn set_axes(&mut self, loops: &[&Loop], axes: &[String], order: Vec<String>) {
+        self.writer.axes = loops
+            .iter()
+            .filter(|l| axes.contains(&l.var))
+            .map(|l| Axis {
+                var: l.var.to_owned(),
+                lower: l.lower.clone(),
+                upper: l.upper.clone(),
+                step: constant_step(l).unwrap_or(1),
+            })
+            .collect();
+        self.writer.order = order;
+    }
+
+    pub fn generate_assignment(&mut self, assignment: &Assign, indent: u8) -> io::Result<()> {
+        self.statements += 1;
+
+        write!(self.writer.out, "    {}", &indentation(indent))?;
+        self.writer.variable(&assignment.lhs)?;
+        write!(self.writer.out, " = ")?;
+
+        // Only scalars have to be converted, arrays keep their type
+        let real = self.writer.is_real(&assignment.rhs);
+        if assignment.lhs.indices.is_empty()
+            && !self.writer.reals.contains(&assignment.lhs.name)
+            && real
+        {
+            write!(self.writer.out, "int(")?;
+            self.writer.expression(&assignment.rhs)?;
+            write!(self.writer.out, ")")?;
+        } else {
+            self.writer.expression(&assignment.rhs)?;
+        }
+
+        writeln!(self.writer.out, "  # {:03}", assignment.label)
+    }
+}
+
+impl<W: io::Write> PyWriter<W> {
+    fn variable(&mut self, var: &Variable) -> io::Result<()> {
+        write!(self.out, "{}", &var.name)?;
+        if var.indices.is_empty() {
+            return Ok(());
+        }
+
+        let dimensions = self.dimensions.get(&var.name).cloned().unwrap_or_default();
+
+        write!(self.out, "[")?;
+        for (i, index) in var.indices.iter().enumerate() {
+            if i > 0 {
+                write!(self.out, ", ")?;
+            }
+
+            let lb = dimensions.get(i).map(|(lb, _)| *lb).unwrap_or(1);
+            let axis = self.axes.iter().position(|axis| uses_var(index, &axis.var));
+            match axis {
+                Some(axis) => self.slice(index, axis, lb)?,
+                None => self.offset(index, lb, 0)?,
+            }
+        }
+        write!(self.out, "]")?;
+
+        // Insert the axes missing for broadcasting to the assigned section
+        let axes = order(var, &self.order);
+        if !axes.is_empty() && !self.order.ends_with(&axes) {
+            let expand = self
+                .order
+                .iter()
+                .map(|v| if axes.contains(v) { ":" } else { "None" })
+                .collect::<Vec<_>>();
+            write!(self.out, "[{}]", expand.join(", "))?;
+        }
+
+        Ok(())
+    }
+
+    /// Section of the subscript over all iterations of the loop
+    fn slice(&mut self, index: &Expression, axis: usize, lb: i32) -> io::Result<()> {
+        let (start, stop, step) = {
+            let axis = &self.axes[axis];
+            let coefficient = Affine::from_expr(index)
+                .map(|a| a.coefficient(&axis.var))
+                .unwrap_or(1);
+            (
+                substitute(index, &axis.var, &axis.lower),
+                substitute(index, &axis.var, &axis.upper),
+                coefficient * axis.step,
+            )
+        };
+
+        self.offset(&start, lb, 0)?;
+        write!(self.out, ":")?;
+        self.offset(&stop, lb, 1)?;
+        if step != 1 {
+            write!(self.out, ":{}", step)?;
+        }
+
+        Ok(())
+    }
+
+    /// Subscript relative to the lower bound of the dimension, increased by
+    /// `extra`
+    fn offset(&mut self, index: &Expression, lb: i32, extra: i32) -> io::Result<()> {
+        let offset = extra - lb;
+        match Affine::from_expr(index).and_then(|a| a.add(&Affine::constant(offset))) {
+            Some(a) => self.expression(&a.to_expr()),
+            None if offset == 0 => self.expression(index),
+            None => {
+                self.operand(index, binop_parens(&OpType::Plus, index, false))?;
+                if offset < 0 {
+                    write!(self.out, " - {}", -offset)
+                } else {
+                    write!(self.out, " + {}", offset)
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) -> io::Result<()> {
+        match expr {
+            Expression::Integer(i) => write!(self.out, "{}", i),
+            Expression::Real(f) => write!(self.out, "{:?}", f),
+            Expression::Variable(var) => self.variable(var),
+            Expression::BinOp(op) => self.binop(op),
+            Expression::UnOp(op) => self.unop(op),
+            Expression::Expression(expr) => self.expression(expr),
+        }
+    }
+
+    fn operand(&mut self, expr: &Expression, parens: bool) -> io::Result<()> {
+        if parens {
+            write!(self.out, "(")?;
+            self.expression(expr)?;
+            write!(self.out, ")")
+        } else {
+            self.expression(expr)
+        }
+    }
+
+    fn unop(&mut self, op: &UnOp) -> io::Result<()> {
+        match op.op {
+            OpType::Minus => write!(self.out, "-")?,
+            OpType::Not => write!(self.out, "not ")?,
+            _ => (),
+        }
+
+        self.operand(&op.right, unop_parens(&op.op, &op.right))
+    }
+
+    fn binop(&mut self, op: &BinOp) -> io::Result<()> {
+        let real = self.is_real(&op.left) || self.is_real(&op.right);
+
+        if is_function(op, real) {
+            match op.op {
+                OpType::Max => write!(self.out, "np.maximum(")?,
+                OpType::Min => write!(self.out, "np.minimum(")?,
+                _ => write!(self.out, "_div(")?,
+            }
+            self.expression(&op.left)?;
+            write!(self.out, ", ")?;
+            self.expression(&op.right)?;
+            return write!(self.out, ")");
+        }
+
+        let symbol = match op.op {
+            OpType::Plus => "+",
+            OpType::Minus => "-",
+            OpType::Mul => "*",
+            OpType::Div => "/",
+            OpType::Equal => "==",
+            OpType::NotEqual => "!=",
+            OpType::Greater => ">",
+            OpType::GreaterEqual => ">=",
+            OpType::Lower => "<",
+            OpType::LowerEqual => "<=",
+            OpType::And => "and",
+            OpType::Or => "or",
+            OpType::Not | OpType::Min | OpType::Max => {
+                return Err(io::Error::other(format!(
+                    "Invalid binary operator {:?}",
+                    op.op
+                )))
+            }
+        };
+
+        let left = binop_parens(&op.op, &op.left, false) && !self.is_call(&op.left);
+        let right = binop_parens(&op.op, &op.right, true) && !self.is_call(&op.right);
+        self.operand(&op.left, left)?;
+        write!(self.out, " {} ", symbol)?;
+        self.operand(&op.right, right)
+    }
+
+    /// Operations printed as function calls never need parentheses
+    fn is_call(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::BinOp(op) => {
+                is_function(op, self.is_real(&op.left) || self.is_real(&op.right))
+            }
+            Expression::Expression(expr) => self.is_call(expr),
+            _ => false,
+        }
+    }
+
+    fn is_real(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Integer(_) => false,
+            Expression::Real(_) => true,
+            Expression::Variable(v) => self.reals.contains(&v.name),
+            Expression::UnOp(op) => self.is_real(&op.right),
+            Expression::BinOp(op) => match op.op {
+                OpType::Plus
+                | OpType::Minus
+                | OpType::Mul
+                | OpType::Div
+                | OpType::Min
+                | OpType::Max => self.is_real(&op.left) || self.is_real(&op.right),
+                _ => false,
+            },
+            Expression::Expression(expr) => self.is_real(expr),
+        }
+    }
+}
+
+/// Operations printed as function calls, integer divisions have to truncate
+/// towards zero
+fn is_function(op: &BinOp, real: bool) -> bool {
+    match op.op {
+        OpType::Min | OpType::Max => true,
+        OpType::Div => !real,
+        _ => false,
+    }
+}
+
+/// Recognize `s = s op e` and `s = e op s` for commutative operations
+fn find_reduction(assignment: &Assign) -> Option<Reduction<'_>> {
+    let s = &assignment.lhs.name;
+    if !assignment.lhs.indices.is_empty() {
+        return None;
+    }
+
+    let is_s = |expr: &Expression| match expr {
+        Expression::Variable(v) => &v.name == s && v.indices.is_empty(),
+        _ => false,
+    };
+
+    let op = match &assignment.rhs {
+        Expression::BinOp(op) => op,
+        _ => return None,
+    };
+    let commutative = match op.op {
+        OpType::Plus | OpType::Mul | OpType::Min | OpType::Max => true,
+        OpType::Minus => false,
+        _ => return None,
+    };
+
+    let expr = if is_s(&op.left) {
+        &op.right
+    } else if commutative && is_s(&op.right) {
+        &op.left
+    } else {
+        return None;
+    };
+
+    if uses_var(expr, s) {
+        return None;
+    }
+
+    Some(Reduction {
+        op: op.op.clone(),
+        expr,
+    })
+}
+
+/// Loops of the nest, which can be replaced by slices in the statement. For
+/// reductions of `expr` there is no assigned section.
+fn slice_axes(assignment: &Assign, loops: &[&Loop], reduction: Option<&Expression>) -> Vec<String> {
+    let mut axes = loops
+        .iter()
+        .filter(|l| constant_step(l).is_some_and(|s| s > 0))
+        .map(|l| l.var.to_owned())
+        .collect::<Vec<_>>();
+
+    while let Some(var) = conflict(assignment, loops, &axes, reduction) {
+        axes.retain(|v| v != &var);
+    }
+
+    axes
+}
+
+/// Loop variable, which prevents the current axes
+fn conflict(
+    assignment: &Assign,
+    loops: &[&Loop],
+    axes: &[String],
+    reduction: Option<&Expression>,
+) -> Option<String> {
+    // Bounds of a sliced loop may not depend on another one
+    for l in loops.iter().filter(|l| axes.contains(&l.var)) {
+        if axes
+            .iter()
+            .any(|v| uses_var(&l.lower, v) || uses_var(&l.upper, v))
+        {
+            return Some(l.var.to_owned());
+        }
+    }
+
+    // Loop variables may only be used in subscripts
+    for v in axes {
+        if value_use(&assignment.rhs, v) {
+            return Some(v.to_owned());
+        }
+    }
+
+    let mut accesses = vec![Access {
+        var: &assignment.lhs,
+        write: true,
+    }];
+    for index in assignment.lhs.indices.iter() {
+        collect_reads(index, &mut accesses);
+    }
+    collect_reads(&assignment.rhs, &mut accesses);
+
+    let mut orders = Vec::new();
+    for access in accesses.iter() {
+        let mut axes_order: Vec<&String> = Vec::new();
+        for index in access.var.indices.iter() {
+            let used = axes
+                .iter()
+                .filter(|v| uses_var(index, v))
+                .collect::<Vec<_>>();
+            let v = match used.len() {
+                0 => continue,
+                1 => used[0],
+                _ => return used.last().map(|v| v.to_string()),
+            };
+
+            let affine = Affine::from_expr(index);
+            if affine.is_none_or(|a| a.coefficient(v) <= 0) || axes_order.contains(&v) {
+                return Some(v.to_owned());
+            }
+            axes_order.push(v);
+        }
+        orders.push(axes_order);
+    }
+
+    // Every section is broadcast to the assigned one
+    let target = match reduction {
+        Some(expr) => {
+            if let Some(v) = axes.iter().find(|v| !uses_var(expr, v)) {
+                return Some(v.to_owned());
+            }
+            axes.iter().collect::<Vec<_>>()
+        }
+        None => {
+            if let Some(v) = axes.iter().find(|v| !orders[0].contains(v)) {
+                return Some(v.to_owned());
+            }
+            orders[0].clone()
+        }
+    };
+
+    for order in orders.iter() {
+        let mut remaining = target.iter();
+        if !order.iter().all(|v| remaining.any(|t| t == v)) {
+            return axes.last().cloned();
+        }
+    }
+
+    None
+}
+
+/// Axes of the access in the order of its dimensions
+fn order(access: &Variable, axes: &[String]) -> Vec<String> {
+    access
+        .indices
+        .iter()
+        .filter_map(|index| axes.iter().find(|v| uses_var(index, v)))
+        .cloned()
+        .collect()
+}
+
+/// Use of the variable outside of subscripts
+fn value_use(expr: &Expression, var: &str) -> bool {
+    match expr {
+        Expression::Integer(_) | Expression::Real(_) => false,
+        Expression::Variable(v) => v.name == var && v.indices.is_empty(),
+        Expression::UnOp(op) => value_use(&op.right, var),
+        Expression::BinOp(op) => value_use(&op.left, var) || value_use(&op.right, var),
+        Expression::Expression(expr) => value_use(expr, var),
+    }
+}
+
+fn indentation(indent: u8) -> String {
+    " ".repeat(4 * indent as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use vectorization::{
+        tests::{generate, graph, NEST},
+        Mode,
+    };
+
+    #[test]
+    fn slices_and_reductions() {
+        let function = generate(NEST, &graph(), Mode::NumPy);
+        assert!(function.contains("def nest(a, b, s=0.0, n=0):\n"));
+        assert!(function.contains(
+            "    a[1:n, 0:10] = a[1:n, 0:10] * 2.0  # 004
+    for i in range(2, n + 1):  # 001
+        b[i - 1] = b[i - 2] + 1.0  # 002
+    s = s + np.sum(b[1:n])  # 005
+    return s, n
+"
+        ));
+    }
+}
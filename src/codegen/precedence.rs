@@ -0,0 +1,164 @@
+use ir::*;
+
+/// Binding strength of an expression when printed in infix notation. The
+/// levels are valid for every backend: comparisons share one level and never
+/// associate, logical negation only takes operands which bind tighter than
+/// any operator.
+pub fn precedence(expr: &Expression) -> u8 {
+    match expr {
+        Expression::Integer(i) if *i < 0 => ADDITIVE,
+        Expression::Real(f) if f.is_sign_negative() => ADDITIVE,
+        Expression::Integer(_) | Expression::Real(_) | Expression::Variable(_) => ATOM,
+        Expression::UnOp(op) => match op.op {
+            OpType::Not => NOT,
+            OpType::Min | OpType::Max => ATOM,
+            // Fortran gives the sign the level of addition
+            _ => ADDITIVE,
+        },
+        Expression::BinOp(op) => operator_precedence(&op.op),
+        Expression::Expression(expr) => precedence(expr),
+    }
+}
+
+/// Whether the operand of a binary operation has to be parenthesized. All
+/// operations associate to the left, so an operand on the right side needs
+/// parentheses already at the same level.
+pub fn binop_parens(parent: &OpType, child: &Expression, right: bool) -> bool {
+    let parent = operator_precedence(parent);
+    let child = precedence(child);
+
+    child < parent || (child == parent && (right || parent == COMPARISON))
+}
+
+/// Whether the operand of a unary operation has to be parenthesized
+pub fn unop_parens(parent: &OpType, child: &Expression) -> bool {
+    match parent {
+        OpType::Not => precedence(child) < ATOM,
+        _ => precedence(child) <= ADDITIVE,
+    }
+}
+
+fn operator_precedence(op: &OpType) -> u8 {
+    match op {
+        OpType::Or => OR,
+        OpType::And => AND,
+        OpType::Not => NOT,
+        OpType::Equal
+        | OpType::NotEqual
+        | OpType::Greater
+        | OpType::GreaterEqual
+        | OpType::Lower
+        | OpType::LowerEqual => COMPARISON,
+        OpType::Plus | OpType::Minus => ADDITIVE,
+        OpType::Mul | OpType::Div => MULTIPLICATIVE,
+        OpType::Min | OpType::Max => ATOM,
+    }
+}
+
+const OR: u8 = 1;
+const AND: u8 = 2;
+const NOT: u8 = 3;
+const COMPARISON: u8 = 4;
+const ADDITIVE: u8 = 5;
+const MULTIPLICATIVE: u8 = 6;
+const ATOM: u8 = 7;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codegen::expression_source;
+    use transform::{binop, paren, var_expr};
+
+    fn unop(op: OpType, right: Expression) -> Expression {
+        Expression::UnOp(Box::new(UnOp { op, right }))
+    }
+
+    #[test]
+    fn parenthesizes_only_where_needed() {
+        let (a, b, c) = (var_expr("a"), var_expr("b"), var_expr("c"));
+        let cases = vec![
+            (
+                binop(
+                    OpType::Minus,
+                    paren(&binop(OpType::Minus, a.clone(), b.clone())),
+                    c.clone(),
+                ),
+                "a-b-c",
+            ),
+            (
+                binop(
+                    OpType::Minus,
+                    a.clone(),
+                    paren(&binop(OpType::Minus, b.clone(), c.clone())),
+                ),
+                "a-(b-c)",
+            ),
+            (
+                binop(
+                    OpType::Plus,
+                    a.clone(),
+                    paren(&binop(OpType::Mul, b.clone(), c.clone())),
+                ),
+                "a+b*c",
+            ),
+            (
+                binop(
+                    OpType::Mul,
+                    a.clone(),
+                    paren(&binop(OpType::Plus, b.clone(), c.clone())),
+                ),
+                "a*(b+c)",
+            ),
+            (
+                binop(
+                    OpType::Div,
+                    a.clone(),
+                    paren(&binop(OpType::Mul, b.clone(), c.clone())),
+                ),
+                "a/(b*c)",
+            ),
+            (
+                binop(OpType::Plus, a.clone(), unop(OpType::Minus, b.clone())),
+                "a+(-b)",
+            ),
+            (
+                unop(
+                    OpType::Minus,
+                    paren(&binop(OpType::Plus, a.clone(), b.clone())),
+                ),
+                "-(a+b)",
+            ),
+            (
+                binop(OpType::Mul, a.clone(), Expression::Integer(-2)),
+                "a*(-2)",
+            ),
+            (
+                binop(
+                    OpType::Equal,
+                    paren(&binop(OpType::Lower, a.clone(), b.clone())),
+                    c.clone(),
+                ),
+                "(a < b) == c",
+            ),
+            (
+                binop(
+                    OpType::Or,
+                    a.clone(),
+                    paren(&binop(OpType::And, b.clone(), c.clone())),
+                ),
+                "a .or. b .and. c",
+            ),
+            (
+                unop(
+                    OpType::Not,
+                    paren(&binop(OpType::And, a.clone(), b.clone())),
+                ),
+                " .not. (a .and. b)",
+            ),
+        ];
+
+        for (expr, source) in cases {
+            assert_eq!(expression_source(&expr), source);
+        }
+    }
+}
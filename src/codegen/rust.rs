@@ -0,0 +1,1026 @@
+use super::c::constant_step;
+use super::precedence::{binop_parens, unop_parens};
+use ir::*;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+};
+use transform::{
+    contiguous, fresh_name, loop_vars, substitute, unbound_names, used_names, uses_var, Affine,
+};
+
+pub fn generate_rust_trace<T>(ast: &Ast, out: T) -> io::Result<()>
+where
+    T: io::Write,
+{
+    let mut cg = RustCodegen::new(RustTracer::default(), ast, out);
+
+    cg.generate_ast(ast)
+}
+
+pub fn generate_rust_kernel<T>(ast: &Ast, out: T) -> io::Result<()>
+where
+    T: io::Write,
+{
+    let mut cg = RustCodegen::new_kernel(RustProgram, ast, out);
+
+    cg.generate_ast(ast)
+}
+
+/// Rust code generator, arrays are flat and stored in column-major order like
+/// in Fortran, indices are offset by the declared lower bounds
+pub struct RustCodegen<G, W> {
+    generator: G,
+    writer: RustWriter<W>,
+    /// Generate a function taking the variables as arguments instead of a
+    /// program declaring them
+    kernel: bool,
+    names: HashSet<String>,
+    /// References shadowed by the variables of the current loops
+    shadowed: Vec<String>,
+}
+
+/// Output together with the declared variables
+pub struct RustWriter<W> {
+    out: W,
+    dimensions: HashMap<String, Vec<(i32, i32)>>,
+    reals: HashSet<String>,
+    /// Scalars passed by reference, which are dereferenced when used
+    references: HashSet<String>,
+}
+
+pub trait RustGenerator<W> {
+    fn log_loop_begin(
+        &mut self,
+        w: &mut RustWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()>;
+
+    fn log_loop_end(
+        &mut self,
+        w: &mut RustWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()>;
+
+    fn log_loop_update(
+        &self,
+        w: &mut RustWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()>;
+
+    fn log_use(
+        &self,
+        w: &mut RustWriter<W>,
+        expr: &Expression,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()>;
+
+    fn log_def(
+        &self,
+        w: &mut RustWriter<W>,
+        var: &Variable,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()>;
+}
+
+/// Prints trace lines in the format read by the dependency analysis
+#[derive(Default)]
+pub struct RustTracer {
+    loop_indices: HashSet<String>,
+}
+
+/// Plain code without trace output
+pub struct RustProgram;
+
+impl RustTracer {
+    fn log_access<W: io::Write>(
+        &self,
+        w: &mut RustWriter<W>,
+        var: &Variable,
+        category: &str,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        write!(
+            w.out,
+            "    {}println!(\" {:03} {} {}",
+            &indentation(indent),
+            label,
+            &var.name,
+            category
+        )?;
+        for _ in var.indices.iter() {
+            write!(w.out, " {{}}")?;
+        }
+        write!(w.out, "\"")?;
+
+        for expr in var.indices.iter() {
+            write!(w.out, ", ")?;
+            w.expression(expr)?;
+        }
+        writeln!(w.out, ");")?;
+
+        for expr in var.indices.iter() {
+            self.log_use_expression(w, expr, indent, label)?;
+        }
+
+        Ok(())
+    }
+
+    fn log_use_expression<W: io::Write>(
+        &self,
+        w: &mut RustWriter<W>,
+        expr: &Expression,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        match expr {
+            Expression::Integer(_) | Expression::Real(_) => Ok(()),
+            Expression::Expression(expr) => self.log_use_expression(w, expr, indent, label),
+            Expression::Variable(var) => {
+                if self.loop_indices.contains(&var.name) {
+                    Ok(())
+                } else {
+                    self.log_access(w, var, "USE", indent, label)
+                }
+            }
+            Expression::UnOp(op) => self.log_use_expression(w, &op.right, indent, label),
+            Expression::BinOp(op) => {
+                self.log_use_expression(w, &op.left, indent, label)?;
+                self.log_use_expression(w, &op.right, indent, label)
+            }
+        }
+    }
+}
+
+impl<W: io::Write> RustGenerator<W> for RustTracer {
+    fn log_loop_begin(
+        &mut self,
+        w: &mut RustWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()> {
+        self.loop_indices.insert(loop_node.var.to_owned());
+
+        writeln!(
+            w.out,
+            "    {}println!(\" {} {} loop begin\");",
+            &indentation(indent),
+            loop_node.label,
+            &loop_node.var
+        )
+    }
+
+    fn log_loop_end(
+        &mut self,
+        w: &mut RustWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()> {
+        self.loop_indices.remove(&loop_node.var);
+
+        writeln!(
+            w.out,
+            "    {}println!(\" {} {} loop end\");",
+            &indentation(indent),
+            loop_node.label,
+            &loop_node.var
+        )
+    }
+
+    fn log_loop_update(
+        &self,
+        w: &mut RustWriter<W>,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()> {
+        writeln!(
+            w.out,
+            "    {}println!(\" {} {} {{}}\", {});",
+            &indentation(indent),
+            loop_node.label,
+            &loop_node.var,
+            &loop_node.var
+        )
+    }
+
+    fn log_use(
+        &self,
+        w: &mut RustWriter<W>,
+        expr: &Expression,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        self.log_use_expression(w, expr, indent, label)
+    }
+
+    fn log_def(
+        &self,
+        w: &mut RustWriter<W>,
+        var: &Variable,
+        indent: u8,
+        label: i32,
+    ) -> io::Result<()> {
+        self.log_access(w, var, "DEF", indent, label)
+    }
+}
+
+impl<W: io::Write> RustGenerator<W> for RustProgram {
+    fn log_loop_begin(
+        &mut self,
+        _w: &mut RustWriter<W>,
+        _loop_node: &Loop,
+        _indent: u8,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_loop_end(
+        &mut self,
+        _w: &mut RustWriter<W>,
+        _loop_node: &Loop,
+        _indent: u8,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_loop_update(
+        &self,
+        _w: &mut RustWriter<W>,
+        _loop_node: &Loop,
+        _indent: u8,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_use(
+        &self,
+        _w: &mut RustWriter<W>,
+        _expr: &Expression,
+        _indent: u8,
+        _label: i32,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn log_def(
+        &self,
+        _w: &mut RustWriter<W>,
+        _var: &Variable,
+        _indent: u8,
+        _label: i32,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<G: RustGenerator<W>, W: io::Write> RustCodegen<G, W> {
+    pub fn new(generator: G, ast: &Ast, out: W) -> Self {
+        let dimensions = ast
+            .vardef
+            .iter()
+            .map(|d| (d.name.to_owned(), d.dimensions.clone()))
+            .collect();
+        let reals = ast
+            .vardef
+            .iter()
+            .filter(|d| match d.dtype {
+                DefinitionType::Real => true,
+                DefinitionType::Integer => false,
+            })
+            .map(|d| d.name.to_owned())
+            .collect();
+
+        RustCodegen {
+            generator,
+            writer: RustWriter {
+                out,
+                dimensions,
+                reals,
+                references: HashSet::new(),
+            },
+            kernel: false,
+            names: used_names(ast),
+            shadowed: Vec::new(),
+        }
+    }
+
+    /// Generate a function taking arrays as slices and scalars, which are
+    /// not loop variables, as mutable references
+    pub fn new_kernel(generator: G, ast: &Ast, out: W) -> Self {
+        let mut cg = RustCodegen::new(generator, ast, out);
+        cg.kernel = true;
+
+        cg
+    }
+
+    pub fn generate_ast(&mut self, ast: &Ast) -> io::Result<()> {
+        self.generate_header(ast)?;
+        self.generate_stmtlist(&ast.statements.0, 0)?;
+        self.generate_footer(ast)
+    }
+
+    pub fn generate_header(&mut self, ast: &Ast) -> io::Result<()> {
+        writeln!(
+            self.writer.out,
+            "// Compilers for Parallel Systems\n\
+             // 185.A64 SS 2018 H. Moritsch\n\
+             // Rust generated from EFL source\n"
+        )?;
+
+        // Loop variables are bound by the loops themselves, unless they are
+        // referenced outside of them
+        let mut loop_names = HashSet::new();
+        loop_vars(&ast.statements.0, &mut loop_names);
+        let mut outside = HashSet::new();
+        unbound_names(&ast.statements.0, &mut Vec::new(), &mut outside);
+        let vardef = ast
+            .vardef
+            .iter()
+            .filter(|d| !loop_names.contains(&d.name) || outside.contains(&d.name))
+            .collect::<Vec<_>>();
+
+        if !self.kernel {
+            writeln!(self.writer.out, "// program {}", &ast.name)?;
+            writeln!(
+                self.writer.out,
+                "#[allow(unused_assignments, unused_mut, unused_parens, unused_variables)]"
+            )?;
+            writeln!(self.writer.out, "fn main() {{")?;
+            for def in vardef {
+                self.generate_definition(def)?;
+            }
+            return writeln!(self.writer.out);
+        }
+
+        let arguments = vardef
+            .iter()
+            .map(|d| {
+                let dtype = rust_type(&d.dtype);
+                if d.dimensions.is_empty() {
+                    self.writer.references.insert(d.name.to_owned());
+                    format!("{}: &mut {}", &d.name, dtype)
+                } else {
+                    format!("{}: &mut [{}]", &d.name, dtype)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        writeln!(self.writer.out, "/// program {}", &ast.name)?;
+        writeln!(
+            self.writer.out,
+            "#[allow(unused_assignments, unused_parens, unused_variables)]"
+        )?;
+        writeln!(
+            self.writer.out,
+            "pub fn {}({}) {{",
+            &ast.name,
+            arguments.join(", ")
+        )?;
+        for def in vardef.iter().filter(|d| !d.dimensions.is_empty()) {
+            writeln!(
+                self.writer.out,
+                "    assert!({}.len() >= {}); // {}",
+                &def.name,
+                size(def),
+                bounds(def)
+            )?;
+        }
+
+        writeln!(self.writer.out)
+    }
+
+    pub fn generate_footer(&mut self, _ast: &Ast) -> io::Result<()> {
+        writeln!(self.writer.out, "}}")
+    }
+
+    fn generate_definition(&mut self, def: &Definition) -> io::Result<()> {
+        let dtype = rust_type(&def.dtype);
+        let zero = match def.dtype {
+            DefinitionType::Integer => "0",
+            DefinitionType::Real => "0.0",
+        };
+
+        if def.dimensions.is_empty() {
+            writeln!(
+                self.writer.out,
+                "    let mut {}: {} = {};",
+                &def.name, dtype, zero
+            )
+        } else {
+            writeln!(
+                self.writer.out,
+                "    let mut {} = vec![{}{}; {}]; // {}",
+                &def.name,
+                zero,
+                dtype,
+                size(def),
+                bounds(def)
+            )
+        }
+    }
+
+    fn generate_stmtlist(&mut self, stmtlist: &[Statement], indent: u8) -> io::Result<()> {
+        for statement in stmtlist {
+            match statement {
+                Statement::Loop(l) => self.generate_loop(l, indent)?,
+                Statement::Assignment(a) => self.generate_assignment(a, indent)?,
+                Statement::If(i) => self.generate_if(i, indent)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn generate_loop(&mut self, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        self.generate_loop_start(loop_node, indent)?;
+        self.generate_stmtlist(&loop_node.statements.0, indent + 1)?;
+        self.generate_loop_end(loop_node, indent)
+    }
+
+    pub fn generate_loop_start(&mut self, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        self.generator
+            .log_loop_begin(&mut self.writer, loop_node, indent)?;
+
+        // The loop binding shadows a scalar passed by reference
+        if self.writer.references.remove(&loop_node.var) {
+            self.shadowed.push(loop_node.var.to_owned());
+        }
+
+        let var = &loop_node.var;
+        let ind = indentation(indent);
+        match (&loop_node.step, constant_step(loop_node)) {
+            (None, _) | (Some(_), Some(1)) => {
+                write!(self.writer.out, "    {}for {} in ", &ind, var)?;
+                self.writer.expression(&loop_node.lower)?;
+                write!(self.writer.out, "..=")?;
+                self.writer.expression(&loop_node.upper)?;
+            }
+            (Some(_), Some(s)) if s > 0 => {
+                write!(self.writer.out, "    {}for {} in (", &ind, var)?;
+                self.writer.expression(&loop_node.lower)?;
+                write!(self.writer.out, "..=")?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, ").step_by({})", s)?;
+            }
+            (Some(_), Some(s)) if s < 0 => {
+                write!(self.writer.out, "    {}for {} in (", &ind, var)?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, "..=")?;
+                self.writer.expression(&loop_node.lower)?;
+                write!(self.writer.out, ").rev().step_by({})", -s)?;
+            }
+            (Some(step), _) => {
+                // The direction of the loop is only known at runtime
+                write!(self.writer.out, "    {}let mut {} = ", &ind, var)?;
+                self.writer.expression(&loop_node.lower)?;
+                writeln!(self.writer.out, ";")?;
+                write!(self.writer.out, "    {}while (", &ind)?;
+                self.writer.expression(step)?;
+                write!(self.writer.out, " > 0 && {} <= ", var)?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, ") || (")?;
+                self.writer.expression(step)?;
+                write!(self.writer.out, " < 0 && {} >= ", var)?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, ")")?;
+            }
+        }
+        writeln!(self.writer.out, " {{ // {:03}", loop_node.label)?;
+
+        self.generator
+            .log_loop_update(&mut self.writer, loop_node, indent + 1)
+    }
+
+    pub fn generate_loop_end(&mut self, loop_node: &Loop, indent: u8) -> io::Result<()> {
+        if let (Some(step), None) = (&loop_node.step, constant_step(loop_node)) {
+            write!(
+                self.writer.out,
+                "    {}{} += ",
+                &indentation(indent + 1),
+                &loop_node.var
+            )?;
+            self.writer.expression(step)?;
+            writeln!(self.writer.out, ";")?;
+        }
+        writeln!(self.writer.out, "    {}}}", &indentation(indent))?;
+
+        if self.shadowed.last() == Some(&loop_node.var) {
+            self.shadowed.pop();
+            self.writer.references.insert(loop_node.var.to_owned());
+        }
+
+        self.generator
+            .log_loop_end(&mut self.writer, loop_node, indent)
+    }
+
+    pub fn generate_assignment(&mut self, assignment: &Assign, indent: u8) -> io::Result<()> {
+        self.generator
+            .log_def(&mut self.writer, &assignment.lhs, indent, assignment.label)?;
+        self.generator
+            .log_use(&mut self.writer, &assignment.rhs, indent, assignment.label)?;
+
+        write!(self.writer.out, "    {}", &indentation(indent))?;
+        self.writer.variable(&assignment.lhs)?;
+        write!(self.writer.out, " = ")?;
+        let real = self.writer.reals.contains(&assignment.lhs.name);
+        self.writer.converted(&assignment.rhs, real)?;
+        writeln!(self.writer.out, "; // {:03}", assignment.label)
+    }
+
+    fn generate_if(&mut self, if_stat: &If, indent: u8) -> io::Result<()> {
+        write!(self.writer.out, "    {}if ", &indentation(indent))?;
+        self.writer.expression(&if_stat.expr)?;
+        writeln!(self.writer.out, " {{ // {:03}", if_stat.label)?;
+
+        self.generate_stmtlist(&if_stat.then_branch.0, indent + 1)?;
+        writeln!(self.writer.out, "    {}}} else {{", &indentation(indent))?;
+        self.generate_stmtlist(&if_stat.else_branch.0, indent + 1)?;
+
+        writeln!(self.writer.out, "    {}}}", &indentation(indent))
+    }
+
+    /// Innermost loop of a statement without carried dependencies as
+    /// iterators zipped over the contiguous slices accessed by the loop.
+    /// Statements, which would need overlapping borrows of the assigned
+    /// array, are generated as plain loops.
+    pub fn generate_iterator_loop(
+        &mut self,
+        assignment: &Assign,
+        loop_node: &Loop,
+        indent: u8,
+    ) -> io::Result<()> {
+        let var = &loop_node.var;
+        let lhs = &assignment.lhs;
+
+        let mut reads = Vec::new();
+        let iterable = constant_step(loop_node) == Some(1)
+            && !lhs.indices.is_empty()
+            && contiguous(lhs, var)
+            && slice_reads(&assignment.rhs, lhs, var, &mut reads);
+        if !iterable {
+            self.generate_loop_start(loop_node, indent)?;
+            self.generate_assignment(assignment, indent + 1)?;
+            return self.generate_loop_end(loop_node, indent);
+        }
+
+        // Number of iterations
+        let trips = Affine::from_expr(&loop_node.upper)
+            .and_then(|ub| Some((ub, Affine::from_expr(&loop_node.lower)?)))
+            .and_then(|(ub, lb)| ub.add(&lb.scale(-1)?)?.add(&Affine::constant(1)));
+        let mut indent = indent;
+        let guarded = match trips {
+            Some(ref t) if t.is_constant() && t.constant <= 0 => return Ok(()),
+            Some(ref t) if t.is_constant() => false,
+            _ => {
+                write!(self.writer.out, "    {}if ", &indentation(indent))?;
+                self.writer.expression(&loop_node.lower)?;
+                write!(self.writer.out, " <= ")?;
+                self.writer.expression(&loop_node.upper)?;
+                writeln!(self.writer.out, " {{")?;
+                indent += 1;
+                true
+            }
+        };
+
+        // Element bindings of the assigned array and the distinct reads
+        let mut elements = vec![fresh_name("x", &mut self.names)];
+        for _ in reads.iter() {
+            elements.push(fresh_name("x", &mut self.names));
+        }
+
+        let mut pattern = elements[0].to_owned();
+        for element in elements.iter().skip(1) {
+            pattern = format!("({}, {})", pattern, element);
+        }
+
+        write!(
+            self.writer.out,
+            "    {}for {} in {}[",
+            &indentation(indent),
+            pattern,
+            &lhs.name
+        )?;
+        self.writer.first_offset(lhs, loop_node)?;
+        write!(self.writer.out, "..][..")?;
+        match trips {
+            Some(ref t) if t.is_constant() => write!(self.writer.out, "{}", t.constant)?,
+            Some(ref t) => {
+                write!(self.writer.out, "(")?;
+                self.writer.expression(&t.to_expr())?;
+                write!(self.writer.out, ") as usize")?;
+            }
+            None => {
+                write!(self.writer.out, "(")?;
+                self.writer.expression(&loop_node.upper)?;
+                write!(self.writer.out, " - ")?;
+                self.writer.operand(
+                    &loop_node.lower,
+                    binop_parens(&OpType::Minus, &loop_node.lower, true),
+                )?;
+                write!(self.writer.out, " + 1) as usize")?;
+            }
+        }
+        write!(self.writer.out, "].iter_mut()")?;
+        for read in reads.iter() {
+            write!(
+                self.writer.out,
+                "\n    {}    .zip({}[",
+                &indentation(indent),
+                &read.name
+            )?;
+            self.writer.first_offset(read, loop_node)?;
+            write!(self.writer.out, "..].iter())")?;
+        }
+        writeln!(self.writer.out, " {{ // {:03}", loop_node.label)?;
+
+        // Replace the accesses by their elements
+        let mut rhs = substitute_access(&assignment.rhs, lhs, &elements[0]);
+        for (read, element) in reads.iter().zip(elements.iter().skip(1)) {
+            rhs = substitute_access(&rhs, read, element);
+        }
+
+        let real = self.writer.reals.contains(&lhs.name);
+        for (access, element) in Some(lhs)
+            .into_iter()
+            .chain(reads.iter())
+            .zip(elements.iter())
+        {
+            self.writer.references.insert(element.to_owned());
+            if self.writer.reals.contains(&access.name) {
+                self.writer.reals.insert(element.to_owned());
+            }
+        }
+
+        write!(
+            self.writer.out,
+            "    {}*{} = ",
+            &indentation(indent + 1),
+            &elements[0]
+        )?;
+        self.writer.converted(&rhs, real)?;
+        writeln!(self.writer.out, "; // {:03}", assignment.label)?;
+        writeln!(self.writer.out, "    {}}}", &indentation(indent))?;
+
+        for element in elements.iter() {
+            self.writer.references.remove(element);
+            self.writer.reals.remove(element);
+        }
+
+        if guarded {
+            writeln!(self.writer.out, "    {}}}", &indentation(indent - 1))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: io::Write> RustWriter<W> {
+    fn variable(&mut self, var: &Variable) -> io::Result<()> {
+        if var.indices.is_empty() {
+            if self.references.contains(&var.name) {
+                write!(self.out, "*")?;
+            }
+            return write!(self.out, "{}", &var.name);
+        }
+
+        write!(self.out, "{}[", &var.name)?;
+        self.offset(var)?;
+        write!(self.out, "]")
+    }
+
+    /// Offset of the element in the flat array
+    fn offset(&mut self, var: &Variable) -> io::Result<()> {
+        let dimensions = self.dimensions.get(&var.name).cloned().unwrap_or_default();
+
+        let mut affine = Some(Affine::constant(0));
+        let mut stride = 1;
+        for (i, index) in var.indices.iter().enumerate() {
+            let (lb, ub) = dimensions.get(i).cloned().unwrap_or((1, 1));
+            affine = affine.and_then(|a| {
+                let index = Affine::from_expr(index)?.add(&Affine::constant(-lb))?;
+                a.add(&index.scale(stride)?)
+            });
+            stride *= ub - lb + 1;
+        }
+
+        match affine {
+            Some(ref a) if a.is_constant() => write!(self.out, "{}", a.constant),
+            Some(a) => match a.to_expr() {
+                Expression::Variable(ref v) => {
+                    self.variable(v)?;
+                    write!(self.out, " as usize")
+                }
+                expr => {
+                    write!(self.out, "(")?;
+                    self.expression(&expr)?;
+                    write!(self.out, ") as usize")
+                }
+            },
+            None => {
+                write!(self.out, "(")?;
+                let mut stride = 1;
+                for (i, index) in var.indices.iter().enumerate() {
+                    let (lb, ub) = dimensions.get(i).cloned().unwrap_or((1, 1));
+                    if i > 0 {
+                        write!(self.out, " + ")?;
+                    }
+                    write!(self.out, "(")?;
+                    self.expression(index)?;
+                    write!(self.out, " - {}) * {}", lb, stride)?;
+                    stride *= ub - lb + 1;
+                }
+                write!(self.out, ") as usize")
+            }
+        }
+    }
+
+    /// Offset of the access in the first iteration of the loop
+    fn first_offset(&mut self, var: &Variable, loop_node: &Loop) -> io::Result<()> {
+        let first = Variable {
+            name: var.name.to_owned(),
+            indices: var
+                .indices
+                .iter()
+                .map(|index| {
+                    let index = substitute(index, &loop_node.var, &loop_node.lower);
+                    Affine::from_expr(&index)
+                        .map(|a| a.to_expr())
+                        .unwrap_or(index)
+                })
+                .collect(),
+        };
+
+        self.offset(&first)
+    }
+
+    fn expression(&mut self, expr: &Expression) -> io::Result<()> {
+        match expr {
+            Expression::Integer(i) => write!(self.out, "{}", i),
+            Expression::Real(f) => write!(self.out, "{:?}", f),
+            Expression::Variable(var) => self.variable(var),
+            Expression::BinOp(op) => self.binop(op),
+            Expression::UnOp(op) => self.unop(op),
+            Expression::Expression(expr) => self.expression(expr),
+        }
+    }
+
+    /// Integer expressions used as real values are converted explicitly
+    fn converted(&mut self, expr: &Expression, real: bool) -> io::Result<()> {
+        match (real, self.is_real(expr), expr) {
+            (true, false, Expression::Integer(i)) => write!(self.out, "{}.0", i),
+            (true, false, Expression::Variable(_)) => {
+                write!(self.out, "(")?;
+                self.expression(expr)?;
+                write!(self.out, " as f32)")
+            }
+            (true, false, _) => {
+                write!(self.out, "((")?;
+                self.expression(expr)?;
+                write!(self.out, ") as f32)")
+            }
+            (false, true, _) => {
+                write!(self.out, "((")?;
+                self.expression(expr)?;
+                write!(self.out, ") as i32)")
+            }
+            _ => self.expression(expr),
+        }
+    }
+
+    fn operand(&mut self, expr: &Expression, parens: bool) -> io::Result<()> {
+        if parens {
+            write!(self.out, "(")?;
+            self.expression(expr)?;
+            write!(self.out, ")")
+        } else {
+            self.expression(expr)
+        }
+    }
+
+    fn converted_operand(&mut self, expr: &Expression, real: bool, parens: bool) -> io::Result<()> {
+        if real && !self.is_real(expr) {
+            self.converted(expr, real)
+        } else {
+            self.operand(expr, parens)
+        }
+    }
+
+    fn unop(&mut self, op: &UnOp) -> io::Result<()> {
+        match op.op {
+            OpType::Minus => write!(self.out, "-")?,
+            OpType::Not => write!(self.out, "!")?,
+            _ => (),
+        }
+
+        self.operand(&op.right, unop_parens(&op.op, &op.right))
+    }
+
+    fn binop(&mut self, op: &BinOp) -> io::Result<()> {
+        let real = self.is_real(&op.left) || self.is_real(&op.right);
+
+        if is_intrinsic(&op.op) {
+            let dtype = if real { "f32" } else { "i32" };
+            match op.op {
+                OpType::Max => write!(self.out, "{}::max(", dtype)?,
+                _ => write!(self.out, "{}::min(", dtype)?,
+            }
+            self.converted(&op.left, real)?;
+            write!(self.out, ", ")?;
+            self.converted(&op.right, real)?;
+            return write!(self.out, ")");
+        }
+
+        let symbol = match op.op {
+            OpType::Plus => "+",
+            OpType::Minus => "-",
+            OpType::Mul => "*",
+            OpType::Div => "/",
+            OpType::Equal => "==",
+            OpType::NotEqual => "!=",
+            OpType::Greater => ">",
+            OpType::GreaterEqual => ">=",
+            OpType::Lower => "<",
+            OpType::LowerEqual => "<=",
+            OpType::And => "&&",
+            OpType::Or => "||",
+            OpType::Not | OpType::Min | OpType::Max => {
+                return Err(io::Error::other(format!(
+                    "Invalid binary operator {:?}",
+                    op.op
+                )))
+            }
+        };
+
+        self.converted_operand(&op.left, real, binop_parens(&op.op, &op.left, false))?;
+        write!(self.out, " {} ", symbol)?;
+        self.converted_operand(&op.right, real, binop_parens(&op.op, &op.right, true))
+    }
+
+    fn is_real(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Integer(_) => false,
+            Expression::Real(_) => true,
+            Expression::Variable(v) => self.reals.contains(&v.name),
+            Expression::UnOp(op) => self.is_real(&op.right),
+            Expression::BinOp(op) => match op.op {
+                OpType::Plus | OpType::Minus | OpType::Mul | OpType::Div => {
+                    self.is_real(&op.left) || self.is_real(&op.right)
+                }
+                OpType::Min | OpType::Max => self.is_real(&op.left) || self.is_real(&op.right),
+                _ => false,
+            },
+            Expression::Expression(expr) => self.is_real(expr),
+        }
+    }
+}
+
+/// Collect the distinct contiguous reads of the loop. Fails for reads of
+/// the assigned array other than the assigned element, uses of the loop
+/// variable outside of subscripts and non-contiguous reads.
+fn slice_reads(expr: &Expression, lhs: &Variable, var: &str, reads: &mut Vec<Variable>) -> bool {
+    match expr {
+        Expression::Integer(_) | Expression::Real(_) => true,
+        Expression::Variable(v) if v.name == lhs.name => same_access(v, lhs),
+        Expression::Variable(v) if !uses_var(expr, var) => v
+            .indices
+            .iter()
+            .all(|index| slice_reads(index, lhs, var, reads)),
+        Expression::Variable(v) => {
+            if v.indices.is_empty() || !contiguous(v, var) {
+                return false;
+            }
+            if !reads.iter().any(|r| same_access(r, v)) {
+                reads.push(v.clone());
+            }
+            true
+        }
+        Expression::UnOp(op) => slice_reads(&op.right, lhs, var, reads),
+        Expression::BinOp(op) => {
+            slice_reads(&op.left, lhs, var, reads) && slice_reads(&op.right, lhs, var, reads)
+        }
+        Expression::Expression(expr) => slice_reads(expr, lhs, var, reads),
+    }
+}
+
+/// Accesses of the same element with affine subscripts
+fn same_access(a: &Variable, b: &Variable) -> bool {
+    a.name == b.name
+        && a.indices.len() == b.indices.len()
+        && a.indices.iter().zip(b.indices.iter()).all(|(x, y)| {
+            match (Affine::from_expr(x), Affine::from_expr(y)) {
+                (Some(x), Some(y)) => x == y,
+                _ => false,
+            }
+        })
+}
+
+fn substitute_access(expr: &Expression, access: &Variable, element: &str) -> Expression {
+    match expr {
+        Expression::Variable(v) if same_access(v, access) => Expression::Variable(Variable {
+            name: element.to_owned(),
+            indices: Vec::new(),
+        }),
+        Expression::Integer(_) | Expression::Real(_) | Expression::Variable(_) => expr.clone(),
+        Expression::UnOp(op) => Expression::UnOp(Box::new(UnOp {
+            op: op.op.clone(),
+            right: substitute_access(&op.right, access, element),
+        })),
+        Expression::BinOp(op) => Expression::BinOp(Box::new(BinOp {
+            op: op.op.clone(),
+            left: substitute_access(&op.left, access, element),
+            right: substitute_access(&op.right, access, element),
+        })),
+        Expression::Expression(e) => {
+            Expression::Expression(Box::new(substitute_access(e, access, element)))
+        }
+    }
+}
+
+fn rust_type(dtype: &DefinitionType) -> &'static str {
+    match dtype {
+        DefinitionType::Integer => "i32",
+        DefinitionType::Real => "f32",
+    }
+}
+
+fn size(def: &Definition) -> i32 {
+    def.dimensions.iter().map(|(lb, ub)| ub - lb + 1).product()
+}
+
+fn bounds(def: &Definition) -> String {
+    let bounds = def
+        .dimensions
+        .iter()
+        .map(|(lb, ub)| format!("{}:{}", lb, ub))
+        .collect::<Vec<_>>();
+
+    format!("{}({})", &def.name, bounds.join(","))
+}
+
+fn is_intrinsic(op: &OpType) -> bool {
+    matches!(op, OpType::Min | OpType::Max)
+}
+
+fn indentation(indent: u8) -> String {
+    " ".repeat(4 * indent as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use petgraph::Graph;
+    use transform::tests::parse;
+    use vectorization::{
+        tests::{generate, graph, NEST, SHIFTED},
+        Mode,
+    };
+
+    #[test]
+    fn traces_linearized_arrays() {
+        let mut out = Vec::new();
+        generate_rust_trace(&parse(NEST), &mut out).unwrap();
+        let trace = String::from_utf8(out).unwrap();
+
+        assert!(trace.contains("    let mut a = vec![0.0f32; 400]; // a(1:20,1:20)\n"));
+        assert!(trace.contains(
+            "            println!(\" 004 a DEF {} {}\", i, j);
+            println!(\" 004 a USE {} {}\", i, j);
+            a[(i + 20 * j - 21) as usize] = a[(i + 20 * j - 21) as usize] * 2.0; // 004
+"
+        ));
+    }
+
+    #[test]
+    fn zips_contiguous_slices() {
+        let mut graph = Graph::new();
+        graph.add_node(2);
+
+        let kernel = generate(SHIFTED, &graph, Mode::Rust);
+        assert!(kernel.contains("pub fn shifted(a: &mut [f32], b: &mut [f32]) {\n"));
+        assert!(kernel.contains(
+            "    for (x, x2) in a[0..][..18].iter_mut()
+        .zip(b[1..].iter()) { // 001
+        *x = *x2 * 2.0; // 002
+    }
+"
+        ));
+    }
+
+    #[test]
+    fn overlapping_borrows_use_plain_loops() {
+        let kernel = generate(NEST, &graph(), Mode::Rust);
+        assert!(kernel.contains(
+            "        for j in 1..=10 { // 003
+            a[(i + 20 * j - 21) as usize] = a[(i + 20 * j - 21) as usize] * 2.0; // 004
+        }
+"
+        ));
+        assert!(!kernel.contains("iter_mut"));
+    }
+}
@@ -0,0 +1,155 @@
+use super::*;
+use codegen::expression_source;
+use ir::{self, Ast, Expression, Variable};
+use transform::Affine;
+
+/// References of the assignments of a program, to attribute dependencies to
+/// the references causing them
+pub(super) struct References<'a> {
+    /// Assignments by label, with the variables of their enclosing loops
+    assignments: HashMap<Statement, (&'a ir::Assign, Vec<&'a str>)>,
+}
+
+impl<'a> References<'a> {
+    pub(super) fn new(ast: &'a Ast) -> Self {
+        let mut references = References {
+            assignments: HashMap::new(),
+        };
+
+        references.add_statements(&ast.statements, &mut Vec::new());
+        references
+    }
+
+    fn add_statements(&mut self, statements: &'a ir::StatementList, loops: &mut Vec<&'a str>) {
+        for statement in statements.0.iter() {
+            match statement {
+                ir::Statement::Assignment(a) => {
+                    self.assignments.insert(a.label, (a, loops.clone()));
+                }
+                ir::Statement::Loop(l) => {
+                    loops.push(&l.var);
+                    self.add_statements(&l.statements, loops);
+                    loops.pop();
+                }
+                ir::Statement::If(i) => {
+                    self.add_statements(&i.then_branch, loops);
+                    self.add_statements(&i.else_branch, loops);
+                }
+            }
+        }
+    }
+
+    /// Source of the references to `var` in the source and the target
+    /// statement of `edge` causing a dependency, if each statement has a
+    /// single one. References not accessing the element of the first witness
+    /// are ruled out.
+    pub(super) fn subscripts(
+        &self,
+        edge: &DependencyEdge,
+        dep: &DependencyType,
+        var: &str,
+        witnesses: &[Witness],
+    ) -> Option<(String, String)> {
+        let DependencyEdge(source, target) = *edge;
+        let (source_writes, target_writes) = match dep {
+            DependencyType::True => (true, false),
+            DependencyType::Anti => (false, true),
+            DependencyType::Output => (true, true),
+            DependencyType::Input => (false, false),
+        };
+
+        let witness = witnesses.first();
+        let source = self.reference(
+            source,
+            var,
+            source_writes,
+            witness.map(|w| (w.indices.as_slice(), w.source.as_slice())),
+        )?;
+        let target = self.reference(
+            target,
+            var,
+            target_writes,
+            witness.map(|w| (w.indices.as_slice(), w.target.as_slice())),
+        )?;
+
+        Some((source_of(source), source_of(target)))
+    }
+
+    /// The reference to `var` written or read by a statement, given the
+    /// element and the iteration of the loops around the statement
+    fn reference(
+        &self,
+        statement: Statement,
+        var: &str,
+        write: bool,
+        element: Option<(&[i32], &[i32])>,
+    ) -> Option<&'a Variable> {
+        let (assign, loops) = self.assignments.get(&statement)?;
+
+        let mut candidates = Vec::new();
+        if write {
+            if assign.lhs.name == var {
+                candidates.push(&assign.lhs);
+            }
+        } else {
+            reads(&assign.rhs, var, &mut candidates);
+            for index in assign.lhs.indices.iter() {
+                reads(index, var, &mut candidates);
+            }
+        }
+
+        if let Some((indices, iteration)) = element {
+            let values = loops
+                .iter()
+                .cloned()
+                .zip(iteration.iter().cloned())
+                .collect::<HashMap<_, _>>();
+            candidates.retain(|v| accesses(v, indices, &values));
+        }
+
+        // Equal references need not be told apart
+        let first = *candidates.first()?;
+        if candidates.iter().all(|&v| v == first) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+}
+
+/// Variables named `var` read by an expression
+fn reads<'a>(expr: &'a Expression, var: &str, references: &mut Vec<&'a Variable>) {
+    match expr {
+        Expression::Variable(v) => {
+            if v.name == var {
+                references.push(v);
+            }
+            for index in v.indices.iter() {
+                reads(index, var, references);
+            }
+        }
+        Expression::BinOp(op) => {
+            reads(&op.left, var, references);
+            reads(&op.right, var, references);
+        }
+        Expression::UnOp(op) => reads(&op.right, var, references),
+        Expression::Expression(expr) => reads(expr, var, references),
+        Expression::Integer(_) | Expression::Real(_) => (),
+    }
+}
+
+/// Whether a reference may access the element `indices`. Subscripts which
+/// can not be evaluated do not rule it out.
+fn accesses(v: &Variable, indices: &[i32], values: &HashMap<&str, i32>) -> bool {
+    v.indices.len() == indices.len()
+        && v.indices.iter().zip(indices.iter()).all(|(index, i)| {
+            match Affine::from_expr(index).and_then(|a| a.evaluate(values)) {
+                Some(value) => value == *i,
+                None => true,
+            }
+        })
+}
+
+fn source_of(v: &Variable) -> String {
+    expression_source(&Expression::Variable(v.clone()))
+}
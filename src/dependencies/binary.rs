@@ -0,0 +1,304 @@
+//! Binary trace format written by tracing programs with unformatted stream
+//! I/O. All integers have 4 bytes in the byte order of the machine running
+//! the tracing program.
+//!
+//! The trace starts with the magic string `VECTRACE`, the format version and
+//! a string table: the number of variables, followed by rank, length and
+//! name of every variable. Records follow until the end of the file, each
+//! starting with its tag:
+//!
+//! * `DEF`/`USE`: statement label, index into the string table and one
+//!   subscript per dimension of the variable
+//! * `LOOP_BEGIN`/`LOOP_END`: loop label
+//! * `LOOP_UPDATE`: loop label and value of the loop variable
+
+use super::*;
+
+pub const MAGIC: &str = "VECTRACE";
+pub const VERSION: i32 = 1;
+
+pub const DEF: i32 = 1;
+pub const USE: i32 = 2;
+pub const LOOP_BEGIN: i32 = 3;
+pub const LOOP_END: i32 = 4;
+pub const LOOP_UPDATE: i32 = 5;
+
+/// Binary trace borrowing names and records from the trace data
+pub(super) struct BinaryTrace<'a> {
+    names: Vec<(&'a str, usize)>,
+    records: &'a [u8],
+    /// Offset of the records in the trace data, used in error messages
+    start: usize,
+}
+
+pub(super) enum Record<'a> {
+    Access {
+        statement: Statement,
+        var: &'a str,
+        category: Category,
+        indices: Indices<'a>,
+    },
+    LoopBegin(LoopLabel),
+    LoopEnd(LoopLabel),
+    LoopUpdate(LoopLabel, i32),
+}
+
+/// Subscripts of an access, decoded on demand
+pub(super) struct Indices<'a>(&'a [u8]);
+
+pub(super) struct Records<'a, 'b> {
+    trace: &'b BinaryTrace<'a>,
+    offset: usize,
+}
+
+impl<'a> BinaryTrace<'a> {
+    /// Read the header and string table of a binary trace
+    pub(super) fn parse(data: &'a [u8]) -> io::Result<Self> {
+        if !data.starts_with(MAGIC.as_bytes()) {
+            return Err(invalid("Not a binary trace".to_owned()));
+        }
+
+        let mut offset = MAGIC.len();
+        if read_i32(data, offset) != Some(VERSION) {
+            return Err(invalid(
+                "Unsupported binary trace version or byte order".to_owned(),
+            ));
+        }
+        offset += 4;
+
+        let count = read_count(data, offset)?;
+        offset += 4;
+
+        let mut names = Vec::with_capacity(count);
+        for _ in 0..count {
+            let rank = read_count(data, offset)?;
+            let length = read_count(data, offset + 4)?;
+            offset += 8;
+
+            let name = data
+                .get(offset..offset + length)
+                .and_then(|name| std::str::from_utf8(name).ok())
+                .ok_or_else(|| invalid(format!("Invalid string table at byte {}", offset)))?;
+            offset += length;
+
+            names.push((name, rank));
+        }
+
+        Ok(BinaryTrace {
+            names,
+            records: &data[offset..],
+            start: offset,
+        })
+    }
+
+    pub(super) fn records<'b>(&'b self) -> Records<'a, 'b> {
+        Records {
+            trace: self,
+            offset: 0,
+        }
+    }
+}
+
+impl<'a, 'b> Records<'a, 'b> {
+    /// Offset of the next record in the trace data
+    pub(super) fn position(&self) -> usize {
+        self.trace.start + self.offset
+    }
+
+    fn next_record(&mut self) -> io::Result<Record<'a>> {
+        let records = self.trace.records;
+        let field = |i: usize| read_i32(records, self.offset + 4 * i);
+
+        let (record, length) = match field(0) {
+            Some(tag) if tag == DEF || tag == USE => {
+                let (statement, id) = match (field(1), field(2)) {
+                    (Some(statement), Some(id)) => (statement, id),
+                    _ => return Err(self.truncated()),
+                };
+                let &(var, rank) = self
+                    .trace
+                    .names
+                    .get(id as usize)
+                    .ok_or_else(|| self.error(format!("Unknown variable {}", id)))?;
+
+                let start = self.offset + 12;
+                let indices = records
+                    .get(start..start + 4 * rank)
+                    .ok_or_else(|| self.truncated())?;
+
+                let category = if tag == DEF {
+                    Category::Write
+                } else {
+                    Category::Read
+                };
+                let access = Record::Access {
+                    statement,
+                    var,
+                    category,
+                    indices: Indices(indices),
+                };
+
+                (access, 12 + 4 * rank)
+            }
+            Some(LOOP_BEGIN) => match field(1) {
+                Some(label) => (Record::LoopBegin(label), 8),
+                None => return Err(self.truncated()),
+            },
+            Some(LOOP_END) => match field(1) {
+                Some(label) => (Record::LoopEnd(label), 8),
+                None => return Err(self.truncated()),
+            },
+            Some(LOOP_UPDATE) => match (field(1), field(2)) {
+                (Some(label), Some(value)) => (Record::LoopUpdate(label, value), 12),
+                _ => return Err(self.truncated()),
+            },
+            Some(tag) => return Err(self.error(format!("Unknown record tag {}", tag))),
+            None => return Err(self.truncated()),
+        };
+
+        self.offset += length;
+        Ok(record)
+    }
+
+    fn error(&self, message: String) -> io::Error {
+        invalid(format!("{} at byte {}", message, self.position()))
+    }
+
+    fn truncated(&self) -> io::Error {
+        self.error("Truncated record".to_owned())
+    }
+}
+
+impl<'a, 'b> Iterator for Records<'a, 'b> {
+    type Item = io::Result<Record<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.trace.records.len() {
+            return None;
+        }
+
+        let record = self.next_record();
+        if record.is_err() {
+            // Stop after the first error
+            self.offset = self.trace.records.len();
+        }
+
+        Some(record)
+    }
+}
+
+impl<'a> Indices<'a> {
+    pub(super) fn iter(&self) -> impl Iterator<Item = i32> + 'a {
+        self.0
+            .chunks_exact(4)
+            .map(|c| i32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+    }
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Option<i32> {
+    data.get(offset..offset + 4)
+        .map(|c| i32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+}
+
+fn read_count(data: &[u8], offset: usize) -> io::Result<usize> {
+    match read_i32(data, offset) {
+        Some(count) if count >= 0 => Ok(count as usize),
+        _ => Err(invalid(format!("Invalid string table at byte {}", offset))),
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::other(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trace of the variables `a(:)` and `s` followed by the given records
+    fn trace(records: &[i32]) -> Vec<u8> {
+        let mut data = MAGIC.as_bytes().to_vec();
+        for v in [VERSION, 2, 1, 1].iter() {
+            data.extend_from_slice(&v.to_ne_bytes());
+        }
+        data.push(b'a');
+        for v in [0i32, 1].iter() {
+            data.extend_from_slice(&v.to_ne_bytes());
+        }
+        data.push(b's');
+        for v in records {
+            data.extend_from_slice(&v.to_ne_bytes());
+        }
+
+        data
+    }
+
+    /// Error of the first invalid record
+    fn error(data: &[u8]) -> String {
+        let trace = BinaryTrace::parse(data).unwrap();
+        match trace.records().find_map(|r| r.err()) {
+            Some(e) => e.to_string(),
+            None => panic!("All records are valid"),
+        }
+    }
+
+    #[test]
+    fn reads_records() {
+        let data = trace(&[LOOP_BEGIN, 2, LOOP_UPDATE, 2, 7, DEF, 3, 0, 7, USE, 3, 1]);
+        let trace = BinaryTrace::parse(&data).unwrap();
+        let records = trace.records().collect::<io::Result<Vec<_>>>().unwrap();
+
+        assert_eq!(records.len(), 4);
+        match &records[2] {
+            Record::Access {
+                statement: 3,
+                var: "a",
+                category: Category::Write,
+                indices,
+            } => assert_eq!(indices.iter().collect::<Vec<_>>(), vec![7]),
+            _ => panic!("Expected definition of a"),
+        }
+        match &records[3] {
+            Record::Access {
+                var: "s", indices, ..
+            } => assert_eq!(indices.iter().count(), 0),
+            _ => panic!("Expected use of s"),
+        }
+    }
+
+    #[test]
+    fn truncated_records() {
+        let data = trace(&[DEF, 3, 0, 7]);
+        assert!(error(&data[..data.len() - 1]).starts_with("Truncated record"));
+        assert!(error(&trace(&[DEF, 3, 0])).starts_with("Truncated record"));
+        assert!(error(&trace(&[LOOP_UPDATE, 2])).starts_with("Truncated record"));
+        assert!(error(&trace(&[LOOP_END])).starts_with("Truncated record"));
+    }
+
+    #[test]
+    fn unknown_tag() {
+        let message = error(&trace(&[LOOP_BEGIN, 2, 9, 2]));
+        assert_eq!(
+            message,
+            format!(
+                "Unknown record tag 9 at byte {}",
+                trace(&[LOOP_BEGIN, 2]).len()
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_variable() {
+        assert!(error(&trace(&[USE, 3, -1, 7])).starts_with("Unknown variable -1"));
+        assert!(error(&trace(&[USE, 3, 2, 7])).starts_with("Unknown variable 2"));
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(BinaryTrace::parse(b"VECTRAC").is_err());
+
+        let mut data = trace(&[]);
+        data.truncate(data.len() - 1);
+        assert!(BinaryTrace::parse(&data).is_err());
+    }
+}
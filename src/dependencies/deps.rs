@@ -1,82 +1,161 @@
 use super::*;
-use std::collections::{HashMap, HashSet};
+use std::{collections::HashMap, ops::Deref};
 
 pub(super) fn find_deps_for_var(
-    sorted_access: &[Access],
+    var: &trace_parser::VarAccesses,
     instances: &[StatementInstance],
-    deps: &mut HashMap<DependencyEdge, HashSet<LevelDependency>>,
+    options: &Options,
+    found: &mut FoundDependencies,
 ) {
     let mut tracker = InstanceTracker::new();
-    let mut iter = sorted_access.iter();
-    let mut last = match iter.next() {
-        Some(l) => l,
-        None => return,
-    };
+    let mut last = None;
+    let id = found.var_id(var.name());
+
+    for (element, position) in var.by_element() {
+        // Find dependencies once all accesses of an element are tracked
+        if let Some((last_element, last_position)) = last {
+            if last_element != element {
+                let indices = var.subscripts(last_position as usize);
+                add_dependencies(&tracker, instances, options, found, id, indices);
+                tracker = InstanceTracker::new();
+            }
+        }
 
-    while let Some(access) = iter.next() {
-        // Add access in instance to tracker
-        tracker.add_access(last);
+        let (instance, category) = var.access(position as usize);
+        tracker.add_access(instance, category);
+        last = Some((element, position));
+    }
 
-        // Check if we're still on the same element
-        let same = access.indices == last.indices;
-        last = access;
-        if same {
-            continue;
-        }
+    if let Some((_, last_position)) = last {
+        let indices = var.subscripts(last_position as usize);
+        add_dependencies(&tracker, instances, options, found, id, indices);
+    }
+}
 
-        // Find levels for found dependencies and store in global set
-        for (edge, dep) in tracker.calc_dependencies() {
-            let (si1, si2) = to_instances(edge, instances);
-            let edge = DependencyEdge(si1.statement, si2.statement);
+/// Find levels for the dependencies of an element and store them in the
+/// global set
+fn add_dependencies(
+    tracker: &InstanceTracker,
+    instances: &[StatementInstance],
+    options: &Options,
+    found: &mut FoundDependencies,
+    var: usize,
+    indices: &[i32],
+) {
+    tracker.calc_dependencies(instances, options, &mut |s1, s2, dep| {
+        found.add(s1, s2, dep, var, indices)
+    });
+}
 
-            deps.entry(edge)
-                .and_modify(|set| {
-                    let c = find_level(si1, si2);
-                    set.insert(LevelDependency(c, dep.clone()));
-                })
-                .or_insert_with(|| {
-                    let mut set = HashSet::new();
+/// Dependencies found in a trace by the variable causing them, each with up
+/// to `witnesses` witnesses. The smallest witnesses are kept, so the result
+/// does not depend on the order in which dependencies are found.
+pub(super) struct FoundDependencies {
+    pub(super) deps: HashMap<DependencyEdge, LevelWitnesses>,
+    /// Variable names by id
+    pub(super) names: Vec<String>,
+    ids: HashMap<String, usize>,
+    witnesses: usize,
+}
 
-                    let c = find_level(si1, si2);
-                    set.insert(LevelDependency(c, dep.clone()));
+/// Witnesses of the dependencies of an edge by level, type and id of the
+/// variable
+pub(super) type LevelWitnesses = HashMap<(Level, DependencyType, usize), Vec<Witness>>;
+
+impl FoundDependencies {
+    pub(super) fn new(witnesses: usize) -> Self {
+        FoundDependencies {
+            deps: HashMap::new(),
+            names: Vec::new(),
+            ids: HashMap::new(),
+            witnesses,
+        }
+    }
 
-                    set
-                });
+    /// Id of a variable, dependencies are stored with it instead of its name
+    pub(super) fn var_id(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
         }
 
-        tracker = InstanceTracker::new();
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), self.names.len() - 1);
+        self.names.len() - 1
     }
 
-    // Add access in instance to tracker
-    tracker.add_access(last);
-
-    // Find levels for found dependencies and store in global set
-    for (edge, dep) in tracker.calc_dependencies() {
-        let (si1, si2) = to_instances(edge, instances);
+    /// Store a dependency between two statement instances with the level of
+    /// the loop carrying it, caused by the element `indices` of variable `var`
+    pub(super) fn add(
+        &mut self,
+        si1: &StatementInstance,
+        si2: &StatementInstance,
+        dep: DependencyType,
+        var: usize,
+        indices: &[i32],
+    ) {
         let edge = DependencyEdge(si1.statement, si2.statement);
+        let c = find_level(si1, si2);
+
+        let witnesses = self
+            .deps
+            .entry(edge)
+            .or_default()
+            .entry((c, dep, var))
+            .or_default();
+        if self.witnesses == 0 {
+            return;
+        }
 
-        deps.entry(edge)
-            .and_modify(|set| {
-                let c = find_level(si1, si2);
-                set.insert(LevelDependency(c, dep.clone()));
-            })
-            .or_insert_with(|| {
-                let mut set = HashSet::new();
+        // Witnesses are kept in order, only build one if it is kept
+        let key = (indices, si1.iteration.as_slice(), si2.iteration.as_slice());
+        if witnesses.len() == self.witnesses && witnesses.last().is_some_and(|w| w.key() <= key) {
+            return;
+        }
 
-                let c = find_level(si1, si2);
-                set.insert(LevelDependency(c, dep.clone()));
+        if let Err(i) = witnesses.binary_search_by(|w| w.key().cmp(&key)) {
+            witnesses.insert(
+                i,
+                Witness {
+                    indices: indices.to_vec(),
+                    source: si1.iteration.clone(),
+                    target: si2.iteration.clone(),
+                },
+            );
+            witnesses.truncate(self.witnesses);
+        }
+    }
 
-                set
-            });
+    /// Store a dependency together with witnesses found elsewhere
+    pub(super) fn add_witnessed(
+        &mut self,
+        edge: DependencyEdge,
+        level: Level,
+        dep: DependencyType,
+        var: &str,
+        witnesses: Vec<Witness>,
+    ) {
+        let id = self.var_id(var);
+        let kept = self
+            .deps
+            .entry(edge)
+            .or_default()
+            .entry((level, dep, id))
+            .or_default();
+
+        kept.extend(witnesses);
+        kept.sort();
+        kept.dedup();
+        kept.truncate(self.witnesses);
     }
-}
 
-fn to_instances(
-    dep: DependencyEdge,
-    instances: &[StatementInstance],
-) -> (&StatementInstance, &StatementInstance) {
-    let DependencyEdge(i1, i2) = dep;
-    (&instances[i1 as usize], &instances[i2 as usize])
+    /// Add the dependencies found in another part of the same trace
+    pub(super) fn merge(&mut self, other: FoundDependencies) {
+        for (edge, level_deps) in other.deps {
+            for ((level, dep, var), witnesses) in level_deps {
+                self.add_witnessed(edge.clone(), level, dep, &other.names[var], witnesses);
+            }
+        }
+    }
 }
 
 fn find_level(s1: &StatementInstance, s2: &StatementInstance) -> i32 {
@@ -127,12 +206,13 @@ impl InstanceTracker {
         }
     }
 
-    fn add_access(&mut self, access: &Access) {
+    fn add_access(&mut self, instance: u32, category: &Category) {
+        let instance = instance as Statement;
         let mut was_present = false;
 
         if let Some((last, (use_flag, def_flag))) = self.use_def.last_mut() {
-            if *last == access.statement {
-                match access.category {
+            if *last == instance {
+                match category {
                     Category::Read => *use_flag = true,
                     Category::Write => *def_flag = true,
                 }
@@ -143,8 +223,8 @@ impl InstanceTracker {
 
         if !was_present {
             self.use_def.push((
-                access.statement,
-                match access.category {
+                instance,
+                match category {
                     Category::Read => (true, false),
                     Category::Write => (false, true),
                 },
@@ -152,83 +232,203 @@ impl InstanceTracker {
         }
     }
 
-    fn calc_dependencies(&self) -> Vec<(DependencyEdge, DependencyType)> {
-        let mut deps = Vec::new();
-
-        let iter = self.use_def.iter().zip(self.use_def.iter().skip(1));
-        let mut last_write = -1i32;
-        let mut uses = Vec::new();
-
-        for ((s1, (u1, d1)), (s2, (u2, d2))) in iter {
-            let ((s1, (u1, d1)), (s2, (u2, d2))) = ((*s1, (*u1, *d1)), (*s2, (*u2, *d2)));
-            let edge = DependencyEdge(s1, s2);
-
-            if !u1 && d1 && !u2 && d2 {
-                deps.push((edge, DependencyType::Output));
-                last_write = s2;
-                uses.clear();
-            } else if !u1 && d1 && u2 && !d2 {
-                deps.push((edge, DependencyType::True));
-                last_write = s1;
-                uses.clear();
-            } else if !u1 && d1 && u2 && d2 {
-                deps.push((edge.clone(), DependencyType::True));
-                deps.push((edge, DependencyType::Output));
-                last_write = s2;
-                uses.clear();
-            } else if u1 && !d1 && !u2 && d2 {
-                for last in uses.iter() {
-                    let edge = DependencyEdge(*last, s2);
-                    deps.push((edge, DependencyType::Anti));
-                }
+    fn calc_dependencies<'a, F>(
+        &self,
+        instances: &'a [StatementInstance],
+        options: &Options,
+        dependency: &mut F,
+    ) where
+        F: FnMut(&&'a StatementInstance, &&'a StatementInstance, DependencyType),
+    {
+        let mut element = ElementState::new(options);
+        let mut accesses = self
+            .use_def
+            .iter()
+            .map(|&(instance, flags)| (&instances[instance as usize], flags));
+
+        if let Some(mut first) = accesses.next() {
+            for second in accesses {
+                element.step(&first, &second, dependency);
+                first = second;
+            }
+        }
+        element.finish(dependency);
+    }
+}
 
-                if last_write >= 0 {
-                    let edge = DependencyEdge(last_write, s2);
-                    deps.push((edge, DependencyType::Output));
-                }
+/// Writer and readers of an array element, which are needed to find the
+/// dependencies of the next access to the element.
+///
+/// Value-based, true dependencies are reported from the write whose value is
+/// read, as they are anyway, and output dependencies on overwritten values
+/// only if the value was read or it is overwritten by the last write of the
+/// trace. Writes of values which are never read, e.g. of arrays initialized
+/// again, only have to precede the last write, whose value is live after the
+/// trace.
+///
+/// Input dependencies are reported between consecutive instances reading the
+/// same value.
+pub(super) struct ElementState<T> {
+    last_write: Option<T>,
+    /// Reads of the value of the last write, see `push_collapsed`
+    uses: Vec<T>,
+    /// Writes of values overwritten before being read, value-based only, see
+    /// `push_collapsed`
+    dead: Vec<T>,
+    value_based: bool,
+    input: bool,
+}
 
-                deps.push((edge, DependencyType::Anti));
-                last_write = s2;
-                uses.clear();
-            } else if u1 && !d1 && u2 && d2 {
-                for last in uses.iter() {
-                    let edge = DependencyEdge(*last, s2);
-                    deps.push((edge, DependencyType::Anti));
-                }
+impl<T> ElementState<T>
+where
+    T: Clone + Deref<Target = StatementInstance>,
+{
+    pub(super) fn new(options: &Options) -> Self {
+        ElementState {
+            last_write: None,
+            uses: Vec::new(),
+            dead: Vec::new(),
+            value_based: options.value_based,
+            input: options.input,
+        }
+    }
 
-                if last_write >= 0 {
-                    let edge = DependencyEdge(last_write, s2);
-                    deps.push((edge, DependencyType::True));
-                }
+    /// Report the dependencies between two consecutive instances accessing
+    /// the element, each with a flag for reading and one for writing it
+    pub(super) fn step<F>(
+        &mut self,
+        first: &(T, (bool, bool)),
+        second: &(T, (bool, bool)),
+        dependency: &mut F,
+    ) where
+        F: FnMut(&T, &T, DependencyType),
+    {
+        let (s1, (u1, d1)) = (&first.0, first.1);
+        let (s2, (u2, d2)) = (&second.0, second.1);
+
+        if !u1 && d1 && !u2 && d2 {
+            if self.value_based {
+                push_collapsed(&mut self.dead, s1);
+            } else {
+                dependency(s1, s2, DependencyType::Output);
+            }
+            self.last_write = Some(s2.clone());
+            self.uses.clear();
+        } else if !u1 && d1 && u2 && !d2 {
+            dependency(s1, s2, DependencyType::True);
+            self.last_write = Some(s1.clone());
+            self.uses.clear();
+        } else if !u1 && d1 && u2 && d2 {
+            dependency(s1, s2, DependencyType::True);
+            dependency(s1, s2, DependencyType::Output);
+            self.last_write = Some(s2.clone());
+            self.uses.clear();
+        } else if u1 && !d1 && !u2 && d2 {
+            for last in self.uses.iter() {
+                dependency(last, s2, DependencyType::Anti);
+            }
 
-                deps.push((edge, DependencyType::Anti));
-                last_write = s2;
-                uses.clear();
-            } else if u1 && d1 && !u2 && d2 {
-                deps.push((edge.clone(), DependencyType::Anti));
-                deps.push((edge, DependencyType::Output));
-                last_write = s2;
-                uses.clear();
-            } else if u1 && d1 && u2 && !d2 {
-                deps.push((edge, DependencyType::True));
-                last_write = s1;
-                uses.clear();
-            } else if u1 && d1 && u2 && d2 {
-                deps.push((edge.clone(), DependencyType::True));
-                deps.push((edge.clone(), DependencyType::Output));
-                deps.push((edge.clone(), DependencyType::Anti));
-                last_write = s2;
-                uses.clear();
+            if let Some(last_write) = &self.last_write {
+                dependency(last_write, s2, DependencyType::Output);
+            }
+
+            dependency(s1, s2, DependencyType::Anti);
+            self.last_write = Some(s2.clone());
+            self.uses.clear();
+        } else if u1 && !d1 && u2 && d2 {
+            for last in self.uses.iter() {
+                dependency(last, s2, DependencyType::Anti);
+            }
+
+            if let Some(last_write) = &self.last_write {
+                dependency(last_write, s2, DependencyType::True);
+            }
+
+            dependency(s1, s2, DependencyType::Anti);
+            if self.input {
+                dependency(s1, s2, DependencyType::Input);
+            }
+            self.last_write = Some(s2.clone());
+            self.uses.clear();
+        } else if u1 && d1 && !u2 && d2 {
+            dependency(s1, s2, DependencyType::Anti);
+            if self.value_based {
+                push_collapsed(&mut self.dead, s1);
             } else {
-                uses.push(s1);
+                dependency(s1, s2, DependencyType::Output);
+            }
+            self.last_write = Some(s2.clone());
+            self.uses.clear();
+        } else if u1 && d1 && u2 && !d2 {
+            dependency(s1, s2, DependencyType::True);
+            self.last_write = Some(s1.clone());
+            self.uses.clear();
+        } else if u1 && d1 && u2 && d2 {
+            dependency(s1, s2, DependencyType::True);
+            dependency(s1, s2, DependencyType::Output);
+            dependency(s1, s2, DependencyType::Anti);
+            self.last_write = Some(s2.clone());
+            self.uses.clear();
+        } else {
+            push_collapsed(&mut self.uses, s1);
+
+            if let Some(last_write) = &self.last_write {
+                dependency(last_write, s2, DependencyType::True);
+            }
+            if self.input {
+                dependency(s1, s2, DependencyType::Input);
+            }
+        }
+    }
 
-                if last_write >= 0 {
-                    let edge = DependencyEdge(last_write, s2);
-                    deps.push((edge, DependencyType::True));
-                }
+    /// Report the dependencies left after the last access to the element
+    pub(super) fn finish<F>(&self, dependency: &mut F)
+    where
+        F: FnMut(&T, &T, DependencyType),
+    {
+        // The value of the last write is live after the trace
+        if let Some(last_write) = &self.last_write {
+            for dead in self.dead.iter() {
+                dependency(dead, last_write, DependencyType::Output);
             }
         }
+    }
+}
+
+/// Add an instance to earlier instances accessing an element. Of the earlier
+/// instances of the same statement, only the first is kept for every loop
+/// depth at which they differ from the new instance. Later instances find the
+/// same dependency levels to all of them, so the instances kept are bounded
+/// by the number of statements and their nesting depth instead of by the
+/// length of the trace.
+fn push_collapsed<T>(instances: &mut Vec<T>, instance: &T)
+where
+    T: Clone + Deref<Target = StatementInstance>,
+{
+    let depth = |i: &StatementInstance| {
+        i.iteration
+            .iter()
+            .zip(instance.iteration.iter())
+            .position(|(a, b)| a != b)
+    };
 
-        deps
+    let mut k = 0;
+    while k < instances.len() {
+        let i = &instances[k];
+        let duplicate = i.statement == instance.statement && {
+            let d = depth(i);
+            d.is_none()
+                || instances[..k]
+                    .iter()
+                    .any(|j| j.statement == instance.statement && depth(j) == d)
+        };
+
+        if duplicate {
+            instances.remove(k);
+        } else {
+            k += 1;
+        }
     }
+
+    instances.push(instance.clone());
 }
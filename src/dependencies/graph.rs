@@ -1,15 +1,16 @@
 use super::*;
-use petgraph::{algo::tarjan_scc, prelude::*, Graph};
-use std::{fs::File, io};
+use petgraph::{algo::tarjan_scc, Graph};
+use std::{collections::HashMap, fs::File, io};
 
 pub(super) fn build_graph(
     statements: Vec<Statement>,
     deps: Vec<Dependency>,
 ) -> Graph<Statement, Vec<LevelDependency>> {
     let mut graph = Graph::new();
+    let mut nodes = HashMap::new();
 
     statements.into_iter().for_each(|s| {
-        graph.add_node(s);
+        nodes.insert(s, graph.add_node(s));
     });
 
     for Dependency {
@@ -17,8 +18,10 @@ pub(super) fn build_graph(
         level_deps,
     } in deps
     {
-        let n1 = NodeIndex::new(s1 as usize - 1);
-        let n2 = NodeIndex::new(s2 as usize - 1);
+        // Statement labels share their numbering with loops, so they
+        // cannot be used as node indices directly
+        let n1 = nodes[&s1];
+        let n2 = nodes[&s2];
         graph.update_edge(n1, n2, level_deps);
     }
 
@@ -28,9 +31,36 @@ pub(super) fn build_graph(
 pub fn print_graph(
     graph: &Graph<Statement, Vec<LevelDependency>>,
     file_name: &str,
+) -> Result<(), io::Error> {
+    write_graph(graph, None, file_name)
+}
+
+/// Print a graph merged from `total` runs, every edge labeled with the
+/// number of runs showing it
+pub fn print_merged_graph(
+    graph: &Graph<Statement, Vec<LevelDependency>>,
+    runs: &[EdgeRuns],
+    total: usize,
+    file_name: &str,
+) -> Result<(), io::Error> {
+    write_graph(graph, Some((runs, total)), file_name)
+}
+
+fn write_graph(
+    graph: &Graph<Statement, Vec<LevelDependency>>,
+    runs: Option<(&[EdgeRuns], usize)>,
+    file_name: &str,
 ) -> Result<(), io::Error> {
     use std::io::Write;
 
+    let edge_runs = runs
+        .map(|(runs, _)| {
+            runs.iter()
+                .map(|r| ((r.source, r.target), r.runs))
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+
     let f = File::create(file_name)?;
     let mut writer = io::BufWriter::new(f);
 
@@ -44,23 +74,129 @@ pub fn print_graph(
         }
         writeln!(writer, "    graph[style=dotted];")?;
         writeln!(writer, "  }}")?;
-        writeln!(writer, "")?;
+        writeln!(writer)?;
     }
 
     for edge in graph.raw_edges().iter() {
-        write!(writer, "  s{} -> ", graph[edge.source()])?;
-        write!(writer, "s{} [label=\"", graph[edge.target()])?;
-        for LevelDependency(level, dep) in edge.weight.iter() {
-            match dep {
-                DependencyType::Anti => write!(writer, " A{}", level)?,
-                DependencyType::Output => write!(writer, " O{}", level)?,
-                DependencyType::True => write!(writer, " T{}", level)?,
+        let source = graph[edge.source()];
+        let target = graph[edge.target()];
+        write!(writer, "  s{} -> ", source)?;
+        write!(writer, "s{} [label=\"", target)?;
+
+        // Variables causing a level dependency follow it, e.g. `T1(a, b)`
+        let mut level_deps = edge.weight.iter().peekable();
+        while let Some(LevelDependency(level, dep, attribution)) = level_deps.next() {
+            write!(writer, " {}{}", letter(dep), level)?;
+
+            let mut vars = vec![attribution.var.as_str()];
+            while let Some(LevelDependency(_, _, a)) =
+                level_deps.next_if(|LevelDependency(l, d, _)| l == level && d == dep)
+            {
+                vars.push(&a.var);
+            }
+            vars.retain(|v| !v.is_empty());
+            if !vars.is_empty() {
+                write!(writer, "({})", vars.join(", "))?;
+            }
+        }
+        if let Some((_, total)) = runs {
+            let count = edge_runs.get(&(source, target)).cloned().unwrap_or(0);
+            write!(writer, " ({} of {} runs)", count, total)?;
+        }
+        write!(writer, "\"")?;
+
+        // Subscripts and witnesses are shown as tooltip, one per line
+        let mut lines = Vec::new();
+        for LevelDependency(level, dep, attribution) in edge.weight.iter() {
+            if let Some((s1, s2)) = &attribution.subscripts {
+                lines.push(format!(
+                    "{}{}({}): {} -> {}",
+                    letter(dep),
+                    level,
+                    attribution.var,
+                    s1,
+                    s2
+                ));
+            }
+            for witness in attribution.witnesses.iter() {
+                lines.push(describe_witness(
+                    source,
+                    target,
+                    *level,
+                    dep,
+                    &attribution.var,
+                    witness,
+                ));
             }
         }
-        writeln!(writer, "\"];")?;
+        if !lines.is_empty() {
+            write!(writer, ", tooltip=\"{}\"", lines.join("\\n"))?;
+        }
+        writeln!(writer, "];")?;
     }
 
     writeln!(writer, "}}")?;
 
     Ok(())
 }
+
+/// Describe a witness, e.g. `T1: S3 writes a(5) at (4), S5 reads it at (5)`
+/// with the iterations of the loops around both statements
+fn describe_witness(
+    source: Statement,
+    target: Statement,
+    level: Level,
+    dep: &DependencyType,
+    var: &str,
+    witness: &Witness,
+) -> String {
+    let (first, second) = match dep {
+        DependencyType::True => ("writes", "reads"),
+        DependencyType::Anti => ("reads", "writes"),
+        DependencyType::Output => ("writes", "writes"),
+        DependencyType::Input => ("reads", "reads"),
+    };
+
+    let element = if witness.indices.is_empty() {
+        var.to_owned()
+    } else {
+        format!("{}({})", var, list(&witness.indices))
+    };
+    let at = |iteration: &[i32]| {
+        if iteration.is_empty() {
+            String::new()
+        } else {
+            format!(" at ({})", list(iteration))
+        }
+    };
+
+    format!(
+        "{}{}: S{} {} {}{}, S{} {} it{}",
+        letter(dep),
+        level,
+        source,
+        first,
+        element,
+        at(&witness.source),
+        target,
+        second,
+        at(&witness.target)
+    )
+}
+
+pub(super) fn letter(dep: &DependencyType) -> &'static str {
+    match dep {
+        DependencyType::True => "T",
+        DependencyType::Anti => "A",
+        DependencyType::Output => "O",
+        DependencyType::Input => "I",
+    }
+}
+
+fn list(values: &[i32]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,164 @@
+use super::deps::FoundDependencies;
+use super::*;
+
+/// Number of runs, i.e. of merged graphs, in which an edge occurred
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
+pub struct EdgeRuns {
+    pub source: Statement,
+    pub target: Statement,
+    pub runs: usize,
+}
+
+/// Merge the dependence graphs of several runs of the same program, e.g.
+/// with different inputs. The merged graph has every statement and every
+/// dependency of any run, so transformations based on it are valid for all
+/// of them. The edges are returned with the number of runs showing them.
+pub fn merge_graphs(
+    graphs: &[Graph<Statement, Vec<LevelDependency>>],
+) -> (Graph<Statement, Vec<LevelDependency>>, Vec<EdgeRuns>) {
+    let mut statements = HashSet::new();
+    let mut dependencies = FoundDependencies::new(usize::MAX);
+    let mut runs: HashMap<DependencyEdge, usize> = HashMap::new();
+    let mut subscripts = HashMap::new();
+
+    for graph in graphs {
+        statements.extend(graph.raw_nodes().iter().map(|n| n.weight));
+
+        for edge in graph.raw_edges() {
+            let edge_key = DependencyEdge(graph[edge.source()], graph[edge.target()]);
+            for LevelDependency(level, dep, attribution) in edge.weight.iter() {
+                dependencies.add_witnessed(
+                    edge_key.clone(),
+                    *level,
+                    dep.clone(),
+                    &attribution.var,
+                    attribution.witnesses.clone(),
+                );
+
+                // Runs attributing a dependency to different references leave
+                // it without subscripts
+                let key = (
+                    edge_key.clone(),
+                    *level,
+                    dep.clone(),
+                    attribution.var.clone(),
+                );
+                subscripts
+                    .entry(key)
+                    .and_modify(|s: &mut Option<(String, String)>| {
+                        if *s != attribution.subscripts {
+                            *s = None
+                        }
+                    })
+                    .or_insert_with(|| attribution.subscripts.clone());
+            }
+            *runs.entry(edge_key).or_default() += 1;
+        }
+    }
+
+    let mut statements = statements.into_iter().collect::<Vec<_>>();
+    statements.sort();
+
+    let mut runs = runs
+        .into_iter()
+        .map(|(DependencyEdge(source, target), runs)| EdgeRuns {
+            source,
+            target,
+            runs,
+        })
+        .collect::<Vec<_>>();
+    runs.sort_by_key(|r| (r.source, r.target));
+
+    let mut graph = build(statements, dependencies, None, usize::MAX);
+    for index in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(index).expect("Edge of the graph");
+        let edge = DependencyEdge(graph[source], graph[target]);
+
+        for LevelDependency(level, dep, attribution) in graph[index].iter_mut() {
+            let key = (edge.clone(), *level, dep.clone(), attribution.var.clone());
+            attribution.subscripts = subscripts.remove(&key).unwrap_or_default();
+        }
+    }
+
+    (graph, runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(
+        level: Level,
+        dep: DependencyType,
+        var: &str,
+        subscripts: (&str, &str),
+    ) -> LevelDependency {
+        let attribution = Attribution {
+            var: var.to_owned(),
+            subscripts: Some((subscripts.0.to_owned(), subscripts.1.to_owned())),
+            witnesses: Vec::new(),
+        };
+        LevelDependency(level, dep, attribution)
+    }
+
+    fn graph(
+        edges: Vec<(Statement, Statement, LevelDependency)>,
+    ) -> Graph<Statement, Vec<LevelDependency>> {
+        let mut graph = Graph::new();
+        let mut nodes = HashMap::new();
+        for (source, target, dependency) in edges {
+            let source = *nodes
+                .entry(source)
+                .or_insert_with(|| graph.add_node(source));
+            let target = *nodes
+                .entry(target)
+                .or_insert_with(|| graph.add_node(target));
+            graph.add_edge(source, target, vec![dependency]);
+        }
+        graph
+    }
+
+    #[test]
+    fn counts_runs_and_merges_subscripts() {
+        let true_dep = |distance| dependency(1, DependencyType::True, "a", ("a(i)", distance));
+        let anti = dependency(0, DependencyType::Anti, "b", ("b(i)", "b(i+1)"));
+        let graphs = vec![
+            graph(vec![(2, 3, true_dep("a(i-1)"))]),
+            graph(vec![(2, 3, true_dep("a(i-1)")), (3, 4, anti.clone())]),
+            graph(vec![(2, 3, true_dep("a(i-2)"))]),
+        ];
+
+        let (merged, runs) = merge_graphs(&graphs);
+        assert_eq!(
+            merged.node_weights().cloned().collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            runs,
+            vec![
+                EdgeRuns {
+                    source: 2,
+                    target: 3,
+                    runs: 3,
+                },
+                EdgeRuns {
+                    source: 3,
+                    target: 4,
+                    runs: 1,
+                },
+            ]
+        );
+
+        let mut edges = merged
+            .raw_edges()
+            .iter()
+            .map(|e| (merged[e.source()], merged[e.target()], e.weight.clone()))
+            .collect::<Vec<_>>();
+        edges.sort_by_key(|(source, target, _)| (*source, *target));
+
+        // Differing references of the runs are not shown
+        let mut unknown = true_dep("a(i-1)");
+        unknown.2.subscripts = None;
+        assert_eq!(edges, vec![(2, 3, vec![unknown]), (3, 4, vec![anti])]);
+    }
+}
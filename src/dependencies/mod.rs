@@ -1,30 +1,150 @@
+mod attribute;
+pub mod binary;
 mod deps;
 mod graph;
+mod merge;
+mod reuse;
+mod shadow;
 mod trace_parser;
+mod validate;
 
+use ir::Ast;
 use petgraph::Graph;
 use std::{
     collections::{HashMap, HashSet},
     io,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
-pub use self::graph::print_graph;
+pub use self::graph::{print_graph, print_merged_graph};
+pub use self::merge::{merge_graphs, EdgeRuns};
+pub use self::reuse::print_reuse_report;
 
-pub fn find_dependencies<T>(inp: T) -> io::Result<Graph<Statement, Vec<LevelDependency>>>
+use self::deps::FoundDependencies;
+
+/// Options of the dependence analysis
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Number of threads of the batch analysis
+    pub jobs: usize,
+    /// Number of witnesses kept for every level dependency
+    pub witnesses: usize,
+    /// Value-based analysis, leaving out output dependencies between writes
+    /// of values which are never read, except for the last write
+    pub value_based: bool,
+    /// Report input dependencies between statements reading the same value
+    pub input: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            jobs: 1,
+            witnesses: 0,
+            value_based: false,
+            input: false,
+        }
+    }
+}
+
+impl Options {
+    /// Witnesses kept during the analysis, dependencies are attributed to
+    /// references in the program by their first witness
+    fn kept_witnesses(&self, ast: Option<&Ast>) -> usize {
+        match ast {
+            Some(_) => self.witnesses.max(1),
+            None => self.witnesses,
+        }
+    }
+}
+
+/// Dependencies in a text trace. Variables are analyzed on `options.jobs`
+/// threads, the graph does not depend on their number.
+///
+/// Traces with improperly nested loops are rejected. Given the `ast` of the
+/// traced program, statement labels, loop nesting and variables of the trace
+/// are checked against it, and dependencies are attributed to the references
+/// of the program causing them.
+pub fn find_dependencies<T>(
+    inp: T,
+    ast: Option<&Ast>,
+    options: &Options,
+) -> io::Result<Graph<Statement, Vec<LevelDependency>>>
+where
+    T: io::BufRead,
+{
+    let trace = trace_parser::read_trace(inp, ast)?;
+    analyze(trace, ast, options)
+}
+
+/// Dependencies in a trace in the binary format of `binary`
+pub fn find_dependencies_binary(
+    data: &[u8],
+    ast: Option<&Ast>,
+    options: &Options,
+) -> io::Result<Graph<Statement, Vec<LevelDependency>>> {
+    let trace = trace_parser::read_binary_trace(data, ast)?;
+    analyze(trace, ast, options)
+}
+
+/// Dependencies in a text trace, found in a single pass over the trace with
+/// memory bounded by the number of accessed array elements, times the number
+/// of statements accessing an element and their loop nesting depth
+pub fn find_dependencies_streaming<T>(
+    inp: T,
+    ast: Option<&Ast>,
+    options: &Options,
+) -> io::Result<Graph<Statement, Vec<LevelDependency>>>
 where
     T: io::BufRead,
 {
-    let (instances, access) = trace_parser::read_trace(inp)?;
-    let access = trace_parser::split_and_sort_trace(access);
+    let mut shadow = shadow::ShadowMemory::new(options.kept_witnesses(ast), options);
+    trace_parser::for_each_output(inp, ast, |t| {
+        shadow.push(t);
+        Ok(())
+    })?;
 
+    let (statements, dependencies) = shadow.finish();
+    Ok(build(statements, dependencies, ast, options.witnesses))
+}
+
+/// Streaming analysis of a trace in the binary format of `binary`
+pub fn find_dependencies_binary_streaming(
+    data: &[u8],
+    ast: Option<&Ast>,
+    options: &Options,
+) -> io::Result<Graph<Statement, Vec<LevelDependency>>> {
+    let mut shadow = shadow::ShadowMemory::new(options.kept_witnesses(ast), options);
+    trace_parser::for_each_binary_output(data, ast, |t| {
+        shadow.push(t);
+        Ok(())
+    })?;
+
+    let (statements, dependencies) = shadow.finish();
+    Ok(build(statements, dependencies, ast, options.witnesses))
+}
+
+fn analyze(
+    trace: trace_parser::PackedTrace,
+    ast: Option<&Ast>,
+    options: &Options,
+) -> io::Result<Graph<Statement, Vec<LevelDependency>>> {
     // Find dependencies
-    let mut dependencies = HashMap::new();
-    for var in access.values() {
-        deps::find_deps_for_var(var, &instances, &mut dependencies);
-    }
+    let witnesses = options.kept_witnesses(ast);
+    let dependencies = if options.jobs > 1 && trace.vars.len() > 1 {
+        analyze_parallel(&trace, options, witnesses)
+    } else {
+        let mut dependencies = FoundDependencies::new(witnesses);
+        for var in trace.vars.iter() {
+            deps::find_deps_for_var(var, &trace.instances, options, &mut dependencies);
+        }
+        dependencies
+    };
 
     // Find statements in instances
-    let mut statements = instances
+    let mut statements = trace
+        .instances
         .iter()
         .map(|i| i.statement)
         .collect::<HashSet<_>>()
@@ -32,18 +152,95 @@ where
         .collect::<Vec<_>>();
     statements.sort();
 
-    // Collect dependencies into array
-    let dependencies = dependencies
+    Ok(build(statements, dependencies, ast, options.witnesses))
+}
+
+/// Analyze the variables of a trace on several threads. Threads take the
+/// next variable left, largest first, and collect their dependencies on
+/// their own. The union of the collected dependencies does not depend on
+/// which thread analyzed which variable.
+fn analyze_parallel(
+    trace: &trace_parser::PackedTrace,
+    options: &Options,
+    witnesses: usize,
+) -> FoundDependencies {
+    let mut vars = trace.vars.iter().collect::<Vec<_>>();
+    vars.sort_by_key(|v| std::cmp::Reverse(v.len()));
+
+    let next = AtomicUsize::new(0);
+    let found = thread::scope(|scope| {
+        let threads = (0..options.jobs.min(vars.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut dependencies = FoundDependencies::new(witnesses);
+                    while let Some(var) = vars.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        deps::find_deps_for_var(var, &trace.instances, options, &mut dependencies);
+                    }
+                    dependencies
+                })
+            })
+            .collect::<Vec<_>>();
+
+        threads
+            .into_iter()
+            .map(|t| t.join().expect("Dependence analysis thread panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    let mut dependencies = FoundDependencies::new(witnesses);
+    for thread_dependencies in found {
+        dependencies.merge(thread_dependencies);
+    }
+
+    dependencies
+}
+
+/// Build the dependence graph with up to `witnesses` witnesses for every
+/// level dependency. Given the `ast`, dependencies are attributed to its
+/// references.
+fn build(
+    statements: Vec<Statement>,
+    dependencies: FoundDependencies,
+    ast: Option<&Ast>,
+    witnesses: usize,
+) -> Graph<Statement, Vec<LevelDependency>> {
+    let references = ast.map(attribute::References::new);
+    let names = dependencies.names;
+
+    // Collect dependencies into array, ordered to get the same graph for
+    // every run
+    let mut dependencies = dependencies
+        .deps
         .into_iter()
         .map(|(edge, level_deps)| {
-            let mut level_deps: Vec<_> = level_deps.into_iter().collect();
-            level_deps.sort_by_key(|d| d.0);
+            let mut level_deps: Vec<_> = level_deps
+                .into_iter()
+                .map(|((level, dep, var), mut kept)| {
+                    let var = names[var].to_owned();
+                    let subscripts = references
+                        .as_ref()
+                        .and_then(|r| r.subscripts(&edge, &dep, &var, &kept));
+                    kept.truncate(witnesses);
+
+                    LevelDependency(
+                        level,
+                        dep,
+                        Attribution {
+                            var,
+                            subscripts,
+                            witnesses: kept,
+                        },
+                    )
+                })
+                .collect();
+            level_deps.sort();
             Dependency { edge, level_deps }
         })
         .collect::<Vec<_>>();
+    dependencies.sort_by(|d1, d2| d1.edge.cmp(&d2.edge));
 
     // Build layered dependence graph
-    Ok(graph::build_graph(statements, dependencies))
+    graph::build_graph(statements, dependencies)
 }
 
 #[derive(Debug)]
@@ -55,7 +252,7 @@ pub type Statement = i32;
 pub type LoopLabel = i32;
 pub type Level = i32;
 
-#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone)]
 struct DependencyEdge(Statement, Statement);
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -64,7 +261,7 @@ enum Category {
     Write,
 }
 
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug)]
 struct Access {
     statement: Statement,
     var: String,
@@ -72,15 +269,81 @@ struct Access {
     indices: Vec<i32>,
 }
 
-#[derive(Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Serialize, Deserialize)]
 pub enum DependencyType {
     True,
     Anti,
     Output,
+    /// Both statements read the element, which constrains no order but shows
+    /// reuse
+    Input,
+}
+
+/// Dependency carried by the loop at a level, 0 if it is loop independent,
+/// attributed to the variable causing it
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Serialize, Deserialize)]
+#[serde(from = "LevelDependencyRepr")]
+pub struct LevelDependency(pub Level, pub DependencyType, pub Attribution);
+
+/// Graphs without attributions omit them, graphs with witnesses only have
+/// these
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LevelDependencyRepr {
+    Plain(Level, DependencyType),
+    Attributed(Level, DependencyType, Attribution),
+    Witnessed(Level, DependencyType, Vec<Witness>),
 }
 
-#[derive(Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
-pub struct LevelDependency(pub Level, pub DependencyType);
+impl From<LevelDependencyRepr> for LevelDependency {
+    fn from(repr: LevelDependencyRepr) -> Self {
+        match repr {
+            LevelDependencyRepr::Plain(level, dep) => {
+                LevelDependency(level, dep, Attribution::default())
+            }
+            LevelDependencyRepr::Attributed(level, dep, attribution) => {
+                LevelDependency(level, dep, attribution)
+            }
+            LevelDependencyRepr::Witnessed(level, dep, witnesses) => LevelDependency(
+                level,
+                dep,
+                Attribution {
+                    witnesses,
+                    ..Attribution::default()
+                },
+            ),
+        }
+    }
+}
+
+/// Variable causing a level dependency. Given the program, the source of the
+/// references in the source and the target statement, e.g. `a(i)` and
+/// `a(i - 1)`, if they can be told apart.
+#[derive(Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Serialize, Deserialize)]
+pub struct Attribution {
+    #[serde(default)]
+    pub var: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscripts: Option<(String, String)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub witnesses: Vec<Witness>,
+}
+
+/// Element of the variable accessed by two statement instances causing a
+/// dependency, with the iterations of the loops around the source and the
+/// target instance
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Clone, Serialize, Deserialize)]
+pub struct Witness {
+    pub indices: Vec<i32>,
+    pub source: Vec<i32>,
+    pub target: Vec<i32>,
+}
+
+impl Witness {
+    fn key(&self) -> (&[i32], &[i32], &[i32]) {
+        (&self.indices, &self.source, &self.target)
+    }
+}
 
 #[derive(Debug)]
 struct Dependency {
@@ -99,6 +362,329 @@ struct StatementInstance {
 enum TraceOutput {
     Access(Access),
     LoopBegin(LoopLabel),
-    LoopEnd,
-    LoopUpdate(i32),
+    LoopEnd(LoopLabel),
+    LoopUpdate(LoopLabel, i32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use transform::tests::parse;
+
+    enum Event {
+        Begin(LoopLabel, &'static str),
+        Update(LoopLabel, &'static str, i32),
+        End(LoopLabel, &'static str),
+        Access(Statement, usize, Category, Vec<i32>),
+    }
+
+    /// Variables with their rank
+    const VARS: [(&str, usize); 3] = [("a", 2), ("b", 2), ("s", 0)];
+
+    /// Events of the loop nest
+    ///
+    ///     do i = 2, 7
+    ///       do j = 1, 6
+    ///         a(i, j) = a(i - 1, j + 1) + b(i, j)
+    ///         b(i, j) = s
+    ///         s = s + a(i, j)
+    ///       b(i, 1) = 0
+    fn events() -> Vec<Event> {
+        use self::Category::*;
+        use self::Event::*;
+
+        let mut events = vec![Begin(2, "i")];
+        for i in 2..=7 {
+            events.push(Update(2, "i", i));
+            events.push(Begin(3, "j"));
+            for j in 1..=6 {
+                events.push(Update(3, "j", j));
+                events.push(Access(4, 0, Write, vec![i, j]));
+                events.push(Access(4, 0, Read, vec![i - 1, j + 1]));
+                events.push(Access(4, 1, Read, vec![i, j]));
+                events.push(Access(5, 1, Write, vec![i, j]));
+                events.push(Access(5, 2, Read, vec![]));
+                events.push(Access(6, 2, Write, vec![]));
+                events.push(Access(6, 2, Read, vec![]));
+                events.push(Access(6, 0, Read, vec![i, j]));
+            }
+            events.push(End(3, "j"));
+            events.push(Access(7, 1, Write, vec![i, 1]));
+        }
+        events.push(End(2, "i"));
+
+        events
+    }
+
+    fn text_trace(events: &[Event]) -> Vec<u8> {
+        let mut trace = String::new();
+        for event in events {
+            let line = match event {
+                Event::Begin(label, var) => format!(" {} {} loop begin", label, var),
+                Event::Update(label, var, value) => format!(" {} {} {}", label, var, value),
+                Event::End(label, var) => format!(" {} {} loop end", label, var),
+                Event::Access(statement, var, category, indices) => {
+                    let category = match category {
+                        Category::Write => "DEF",
+                        Category::Read => "USE",
+                    };
+                    let mut line = format!(" {:03} {} {}", statement, VARS[*var].0, category);
+                    for index in indices {
+                        line.push_str(&format!(" {}", index));
+                    }
+                    line
+                }
+            };
+            trace.push_str(&line);
+            trace.push('\n');
+        }
+
+        trace.into_bytes()
+    }
+
+    fn binary_trace(events: &[Event]) -> Vec<u8> {
+        let mut trace = binary::MAGIC.as_bytes().to_vec();
+        let write = |trace: &mut Vec<u8>, values: &[i32]| {
+            for v in values {
+                trace.extend_from_slice(&v.to_ne_bytes());
+            }
+        };
+
+        write(&mut trace, &[binary::VERSION, VARS.len() as i32]);
+        for (name, rank) in VARS.iter() {
+            write(&mut trace, &[*rank as i32, name.len() as i32]);
+            trace.extend_from_slice(name.as_bytes());
+        }
+
+        for event in events {
+            match event {
+                Event::Begin(label, _) => write(&mut trace, &[binary::LOOP_BEGIN, *label]),
+                Event::Update(label, _, value) => {
+                    write(&mut trace, &[binary::LOOP_UPDATE, *label, *value])
+                }
+                Event::End(label, _) => write(&mut trace, &[binary::LOOP_END, *label]),
+                Event::Access(statement, var, category, indices) => {
+                    let tag = match category {
+                        Category::Write => binary::DEF,
+                        Category::Read => binary::USE,
+                    };
+                    write(&mut trace, &[tag, *statement, *var as i32]);
+                    write(&mut trace, indices);
+                }
+            }
+        }
+
+        trace
+    }
+
+    type Edges = Vec<(Statement, Statement, Vec<LevelDependency>)>;
+
+    fn edges(graph: Graph<Statement, Vec<LevelDependency>>) -> (Vec<Statement>, Edges) {
+        let edges = graph
+            .raw_edges()
+            .iter()
+            .map(|e| (graph[e.source()], graph[e.target()], e.weight.clone()))
+            .collect();
+        (graph.node_weights().cloned().collect(), edges)
+    }
+
+    #[test]
+    fn analyses_find_equal_graphs() {
+        let events = events();
+        let (text, binary) = (text_trace(&events), binary_trace(&events));
+
+        let variants = vec![
+            Options::default(),
+            Options {
+                witnesses: 3,
+                ..Options::default()
+            },
+            Options {
+                value_based: true,
+                ..Options::default()
+            },
+            Options {
+                input: true,
+                witnesses: 2,
+                ..Options::default()
+            },
+        ];
+
+        for options in variants {
+            let parallel = Options {
+                jobs: 4,
+                ..options.clone()
+            };
+            let batch = edges(find_dependencies(Cursor::new(&text), None, &options).unwrap());
+            assert!(!batch.1.is_empty());
+
+            let graphs = vec![
+                find_dependencies(Cursor::new(&text), None, &parallel).unwrap(),
+                find_dependencies_streaming(Cursor::new(&text), None, &options).unwrap(),
+                find_dependencies_binary(&binary, None, &options).unwrap(),
+                find_dependencies_binary(&binary, None, &parallel).unwrap(),
+                find_dependencies_binary_streaming(&binary, None, &options).unwrap(),
+            ];
+            for graph in graphs {
+                assert_eq!(edges(graph), batch, "{:?}", options);
+            }
+        }
+    }
+
+    #[test]
+    fn witnesses_show_iterations_of_both_instances() {
+        let options = Options {
+            witnesses: 3,
+            ..Options::default()
+        };
+        let graph = find_dependencies(Cursor::new(text_trace(&events())), None, &options).unwrap();
+        let (_, edges) = edges(graph);
+
+        // a(i, j) is read as a(i - 1, j + 1) in the next iteration of i
+        let (_, _, dependencies) = edges.iter().find(|(s, t, _)| *s == 4 && *t == 4).unwrap();
+        let LevelDependency(_, _, attribution) = dependencies
+            .iter()
+            .find(|LevelDependency(l, dep, _)| *l == 1 && *dep == DependencyType::True)
+            .unwrap();
+
+        assert!(!attribution.witnesses.is_empty() && attribution.witnesses.len() <= 3);
+        for witness in attribution.witnesses.iter() {
+            assert_eq!(witness.indices, witness.source);
+            assert_eq!(
+                witness.target,
+                vec![witness.source[0] + 1, witness.source[1] - 1]
+            );
+        }
+    }
+    #[test]
+    fn dependencies_are_attributed_to_references() {
+        let ast = parse(
+            "nest
+a FLOAT 2 1 10 1 10
+b FLOAT 2 1 10 1 10
+s FLOAT
+i INT
+j INT
+STMTLIST
+FOR @ 2 ENTRY i EXPR INT 2 EXPR INT 7 STMTLIST
+  FOR @ 3 ENTRY j EXPR INT 1 EXPR INT 6 STMTLIST
+    ASSIGN @ 4 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST EXPR BINOP + EXPR VAR ENTRY a EXPRLIST EXPR BINOP - EXPR VAR ENTRY i EXPR INT 1 EXPR BINOP + EXPR VAR ENTRY j EXPR INT 1 /EXPRLIST EXPR VAR ENTRY b EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST
+    ASSIGN @ 5 VAR ENTRY b EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST EXPR VAR ENTRY s
+    ASSIGN @ 6 VAR ENTRY s EXPR BINOP + EXPR VAR ENTRY s EXPR VAR ENTRY a EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST
+  /STMTLIST
+  ASSIGN @ 7 VAR ENTRY b EXPRLIST EXPR VAR ENTRY i EXPR INT 1 /EXPRLIST EXPR INT 0
+/STMTLIST
+/STMTLIST
+",
+        );
+        let trace = text_trace(&events());
+        let graph =
+            find_dependencies(Cursor::new(&trace), Some(&ast), &Options::default()).unwrap();
+        let (_, edges) = edges(graph);
+
+        let attribution = |source, target, level, dep| {
+            let (_, _, dependencies) = edges
+                .iter()
+                .find(|(s, t, _)| *s == source && *t == target)
+                .unwrap();
+            dependencies
+                .iter()
+                .find(|LevelDependency(l, d, _)| *l == level && *d == dep)
+                .map(|LevelDependency(_, _, attribution)| {
+                    let (s1, s2) = attribution.subscripts.clone().unwrap();
+                    (attribution.var.to_owned(), s1, s2)
+                })
+                .unwrap()
+        };
+        let expected =
+            |var: &str, s1: &str, s2: &str| (var.to_owned(), s1.to_owned(), s2.to_owned());
+
+        assert_eq!(
+            attribution(4, 4, 1, DependencyType::True),
+            expected("a", "a(i,j)", "a(i-1,j+1)")
+        );
+        assert_eq!(
+            attribution(4, 5, 0, DependencyType::Anti),
+            expected("b", "b(i,j)", "b(i,j)")
+        );
+        assert_eq!(
+            attribution(5, 7, 0, DependencyType::Output),
+            expected("b", "b(i,j)", "b(i,1)")
+        );
+        assert_eq!(
+            attribution(6, 5, 1, DependencyType::True),
+            expected("s", "s", "s")
+        );
+    }
+
+    #[test]
+    fn value_based_output_dependencies_end_in_live_write() {
+        use self::Category::Write;
+        use self::Event::*;
+
+        // Element overwritten by two statements in every iteration
+        let mut events = vec![Begin(2, "i")];
+        for i in 1..=3 {
+            events.push(Update(2, "i", i));
+            events.push(Access(8, 0, Write, vec![1, 1]));
+            events.push(Access(9, 0, Write, vec![1, 1]));
+        }
+        events.push(End(2, "i"));
+
+        let trace = text_trace(&events);
+        let analyze = |value_based| {
+            let options = Options {
+                value_based,
+                ..Options::default()
+            };
+            let (_, edges) = edges(find_dependencies(Cursor::new(&trace), None, &options).unwrap());
+            edges
+                .into_iter()
+                .map(|(s, t, deps)| (s, t, deps.into_iter().map(|d| (d.0, d.1)).collect()))
+                .collect::<Vec<(Statement, Statement, Vec<_>)>>()
+        };
+        let output = |levels: &[Level]| {
+            levels
+                .iter()
+                .map(|l| (*l, DependencyType::Output))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            analyze(false),
+            vec![(8, 9, output(&[0])), (9, 8, output(&[1]))]
+        );
+
+        // Only the last write of the element is live after the trace
+        assert_eq!(
+            analyze(true),
+            vec![(8, 9, output(&[0, 1])), (9, 9, output(&[1]))]
+        );
+    }
+    #[test]
+    fn input_dependencies_are_opt_in() {
+        let trace = text_trace(&events());
+        let inputs = |input| {
+            let options = Options {
+                input,
+                ..Options::default()
+            };
+            let (_, edges) = edges(find_dependencies(Cursor::new(&trace), None, &options).unwrap());
+            edges
+                .into_iter()
+                .flat_map(|(s, t, deps)| deps.into_iter().map(move |d| (s, t, d)))
+                .filter(|(_, _, LevelDependency(_, dep, _))| *dep == DependencyType::Input)
+                .map(|(s, t, LevelDependency(level, _, attribution))| {
+                    (s, t, level, attribution.var)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert!(inputs(false).is_empty());
+
+        // a(i, j) is read by statement 6 and as a(i - 1, j + 1) by statement
+        // 4 in the next iteration of i
+        assert!(inputs(true).contains(&(6, 4, 1, "a".to_owned())));
+    }
 }
@@ -0,0 +1,103 @@
+use super::graph::letter;
+use super::*;
+use std::{collections::BTreeMap, fs::File};
+
+/// Print the reuse of every variable, i.e. the true and input dependencies
+/// caused by it. Reuse at level 0 is within the same iterations of the common
+/// loops, e.g. of loops to fuse, other reuse is carried by the loop at its
+/// level, e.g. of a loop to tile.
+pub fn print_reuse_report(
+    graph: &Graph<Statement, Vec<LevelDependency>>,
+    file_name: &str,
+) -> Result<(), io::Error> {
+    let f = File::create(file_name)?;
+    write_reuse_report(graph, io::BufWriter::new(f))
+}
+
+fn write_reuse_report<W: io::Write>(
+    graph: &Graph<Statement, Vec<LevelDependency>>,
+    mut writer: W,
+) -> Result<(), io::Error> {
+    let mut reuse: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for edge in graph.raw_edges().iter() {
+        let source = graph[edge.source()];
+        let target = graph[edge.target()];
+
+        for LevelDependency(level, dep, attribution) in edge.weight.iter() {
+            if *dep != DependencyType::True && *dep != DependencyType::Input {
+                continue;
+            }
+
+            let mut line = format!("  S{} -> S{} {}{}", source, target, letter(dep), level);
+            if let Some((s1, s2)) = &attribution.subscripts {
+                line.push_str(&format!(": {} -> {}", s1, s2));
+            }
+            reuse.entry(&attribution.var).or_default().push(line);
+        }
+    }
+
+    for (var, lines) in reuse {
+        writeln!(writer, "Reuse of {}", var)?;
+        for line in lines {
+            writeln!(writer, "{}", line)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dependency(
+        level: Level,
+        dep: DependencyType,
+        var: &str,
+        subscripts: Option<(&str, &str)>,
+    ) -> LevelDependency {
+        let attribution = Attribution {
+            var: var.to_owned(),
+            subscripts: subscripts.map(|(s1, s2)| (s1.to_owned(), s2.to_owned())),
+            witnesses: Vec::new(),
+        };
+        LevelDependency(level, dep, attribution)
+    }
+
+    #[test]
+    fn reports_true_and_input_dependencies_by_variable() {
+        let mut graph = Graph::new();
+        let s2 = graph.add_node(2);
+        let s3 = graph.add_node(3);
+        graph.add_edge(
+            s2,
+            s3,
+            vec![
+                dependency(0, DependencyType::True, "b", Some(("b(i)", "b(i)"))),
+                dependency(1, DependencyType::Anti, "b", Some(("b(i+1)", "b(i)"))),
+            ],
+        );
+        graph.add_edge(
+            s3,
+            s3,
+            vec![
+                dependency(1, DependencyType::Input, "a", Some(("a(i,j)", "a(i-1,j)"))),
+                dependency(2, DependencyType::Output, "s", None),
+                dependency(2, DependencyType::True, "s", None),
+            ],
+        );
+
+        let mut report = Vec::new();
+        write_reuse_report(&graph, &mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "Reuse of a
+  S3 -> S3 I1: a(i,j) -> a(i-1,j)
+Reuse of b
+  S2 -> S3 T0: b(i) -> b(i)
+Reuse of s
+  S3 -> S3 T2
+"
+        );
+    }
+}
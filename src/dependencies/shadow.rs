@@ -0,0 +1,154 @@
+use super::deps::{ElementState, FoundDependencies};
+use super::*;
+use std::rc::Rc;
+
+/// Streaming dependence analysis. Trace outputs are processed one at a time,
+/// for every array element only the accesses needed to find further
+/// dependencies are kept, at most one per statement and loop depth. Statement instances are shared by the elements
+/// referring to them and dropped with the last reference.
+pub(super) struct ShadowMemory {
+    /// Accessed elements of every variable, by id of the variable in `found`
+    elements: Vec<HashMap<Vec<i32>, Element>>,
+    instance: Option<Rc<StatementInstance>>,
+    loops: Vec<LoopLabel>,
+    iteration: Vec<i32>,
+    loop_updated: bool,
+    statements: HashSet<Statement>,
+    found: FoundDependencies,
+    options: Options,
+}
+
+type Entry = (Rc<StatementInstance>, (bool, bool));
+
+struct Element {
+    /// Completed instance accessing the element before `current`
+    previous: Option<Entry>,
+    /// Instance which accessed the element last, it may still access it
+    current: Entry,
+    state: ElementState<Rc<StatementInstance>>,
+}
+
+impl ShadowMemory {
+    pub(super) fn new(witnesses: usize, options: &Options) -> Self {
+        ShadowMemory {
+            elements: Vec::new(),
+            instance: None,
+            loops: Vec::new(),
+            iteration: Vec::new(),
+            loop_updated: false,
+            statements: HashSet::new(),
+            found: FoundDependencies::new(witnesses),
+            options: options.clone(),
+        }
+    }
+
+    pub(super) fn push(&mut self, t: TraceOutput) {
+        match t {
+            TraceOutput::Access(a) => self.access(a),
+            TraceOutput::LoopBegin(label) => {
+                self.loops.push(label);
+                self.iteration.push(0);
+            }
+            TraceOutput::LoopEnd(_) => {
+                self.loops.pop();
+                self.iteration.pop();
+            }
+            TraceOutput::LoopUpdate(_, index) => {
+                if let Some(i) = self.iteration.last_mut() {
+                    *i = index
+                }
+
+                self.loop_updated = true;
+            }
+        }
+    }
+
+    fn access(&mut self, a: Access) {
+        // Begin of new statement instance detected
+        let instance = match self.instance {
+            Some(ref i) if i.statement == a.statement && !self.loop_updated => i.clone(),
+            _ => {
+                let i = Rc::new(StatementInstance {
+                    statement: a.statement,
+                    loops: self.loops.clone(),
+                    iteration: self.iteration.clone(),
+                });
+
+                self.statements.insert(a.statement);
+                self.instance = Some(i.clone());
+                self.loop_updated = false;
+                i
+            }
+        };
+
+        let flags = match a.category {
+            Category::Read => (true, false),
+            Category::Write => (false, true),
+        };
+
+        let var = self.found.var_id(&a.var);
+        if var == self.elements.len() {
+            self.elements.push(HashMap::new());
+        }
+
+        let element = match self.elements[var].get_mut(&a.indices) {
+            Some(e) => e,
+            None => {
+                self.elements[var].insert(
+                    a.indices,
+                    Element {
+                        previous: None,
+                        current: (instance, flags),
+                        state: ElementState::new(&self.options),
+                    },
+                );
+                return;
+            }
+        };
+
+        if Rc::ptr_eq(&element.current.0, &instance) {
+            let (ref mut use_flag, ref mut def_flag) = element.current.1;
+            *use_flag |= flags.0;
+            *def_flag |= flags.1;
+            return;
+        }
+
+        // The current instance is complete once another one accesses the
+        // element
+        let completed = std::mem::replace(&mut element.current, (instance, flags));
+        if let Some(previous) = element.previous.take() {
+            let found = &mut self.found;
+            let indices = &a.indices;
+            element
+                .state
+                .step(&previous, &completed, &mut |s1, s2, dep| {
+                    found.add(s1, s2, dep, var, indices)
+                });
+        }
+        element.previous = Some(completed);
+    }
+
+    /// Dependencies of the whole trace, after its last output
+    pub(super) fn finish(mut self) -> (Vec<Statement>, FoundDependencies) {
+        let found = &mut self.found;
+        for (var, elements) in self.elements.iter_mut().enumerate() {
+            for (indices, element) in elements.iter_mut() {
+                let mut dependency =
+                    |s1: &Rc<StatementInstance>, s2: &Rc<StatementInstance>, dep| {
+                        found.add(s1, s2, dep, var, indices)
+                    };
+                if let Some(previous) = element.previous.take() {
+                    element
+                        .state
+                        .step(&previous, &element.current, &mut dependency);
+                }
+                element.state.finish(&mut dependency);
+            }
+        }
+
+        let mut statements = self.statements.into_iter().collect::<Vec<_>>();
+        statements.sort();
+
+        (statements, self.found)
+    }
+}
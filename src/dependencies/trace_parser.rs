@@ -1,108 +1,317 @@
+use super::validate::Validator;
 use super::*;
+use ir::Ast;
 use nom::{digit, types::CompleteStr};
-use std::{cmp::Ordering, collections::HashMap, io, str::FromStr};
+use std::{collections::HashMap, io, str::FromStr};
 
-pub(super) fn read_trace<T>(input: T) -> io::Result<(Vec<StatementInstance>, Vec<Access>)>
+pub(super) fn read_trace<T>(input: T, ast: Option<&Ast>) -> io::Result<PackedTrace>
 where
     T: io::BufRead,
 {
-    let mut access = Vec::new();
-    let mut instances = Vec::new();
-    let mut last_statement = 0;
-    let mut loops = Vec::new();
-    let mut iteration = Vec::new();
-    let mut loop_updated = false;
-
-    for line in input.lines() {
-        let t = line?
+    let mut builder = TraceBuilder::new();
+    for_each_output(input, ast, |t| builder.push(t))?;
+
+    Ok(builder.trace)
+}
+
+pub(super) fn read_binary_trace(data: &[u8], ast: Option<&Ast>) -> io::Result<PackedTrace> {
+    let mut builder = TraceBuilder::new();
+    for_each_binary_output(data, ast, |t| builder.push(t))?;
+
+    Ok(builder.trace)
+}
+
+/// Parse a text trace line by line. Every output is validated, against the
+/// program if given, before passing it on.
+pub(super) fn for_each_output<T, F>(input: T, ast: Option<&Ast>, mut f: F) -> io::Result<()>
+where
+    T: io::BufRead,
+    F: FnMut(TraceOutput) -> io::Result<()>,
+{
+    let mut validator = Validator::new(ast);
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        let invalid =
+            |message: &str| invalid_trace(format!("Line {} '{}': {}", number + 1, &line, message));
+
+        let t = line
             .parse()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Could not parse trace line"))?;
+            .map_err(|_| invalid("Could not parse trace line"))?;
+        validator.check(&t).map_err(|e| invalid(&e))?;
+
+        f(t)?;
+    }
+
+    validator
+        .finish()
+        .map_err(|e| invalid_trace(format!("{} at the end of the trace", e)))
+}
+
+pub(super) fn for_each_binary_output<F>(data: &[u8], ast: Option<&Ast>, mut f: F) -> io::Result<()>
+where
+    F: FnMut(TraceOutput) -> io::Result<()>,
+{
+    let trace = binary::BinaryTrace::parse(data)?;
+    let mut validator = Validator::new(ast);
+
+    let mut records = trace.records();
+    loop {
+        let position = records.position();
+        let record = match records.next() {
+            Some(record) => record?,
+            None => break,
+        };
+
+        let t = match record {
+            binary::Record::Access {
+                statement,
+                var,
+                category,
+                indices,
+            } => TraceOutput::Access(Access {
+                statement,
+                var: var.to_owned(),
+                category,
+                indices: indices.iter().collect(),
+            }),
+            binary::Record::LoopBegin(label) => TraceOutput::LoopBegin(label),
+            binary::Record::LoopEnd(label) => TraceOutput::LoopEnd(label),
+            binary::Record::LoopUpdate(label, value) => TraceOutput::LoopUpdate(label, value),
+        };
+        validator
+            .check(&t)
+            .map_err(|e| invalid_trace(format!("{} at byte {}", e, position)))?;
+
+        f(t)?;
+    }
+
+    validator
+        .finish()
+        .map_err(|e| invalid_trace(format!("{} at the end of the trace", e)))
+}
+
+fn invalid_trace(message: String) -> io::Error {
+    io::Error::other(message)
+}
+
+/// Statement instances and accesses of a trace. Accesses are grouped by
+/// variable, variable names are interned and stored once.
+pub(super) struct PackedTrace {
+    pub(super) instances: Vec<StatementInstance>,
+    pub(super) vars: Vec<VarAccesses>,
+}
+
+/// Accesses of a single variable in trace order. The subscripts of all
+/// accesses are packed into one vector, `rank` subscripts per access.
+pub(super) struct VarAccesses {
+    name: String,
+    rank: usize,
+    /// Statement instance and category of every access
+    accesses: Vec<(u32, Category)>,
+    indices: Vec<i32>,
+}
+
+impl VarAccesses {
+    fn new(name: String, rank: usize) -> Self {
+        VarAccesses {
+            name,
+            rank,
+            accesses: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    /// Number of accesses
+    pub(super) fn len(&self) -> usize {
+        self.accesses.len()
+    }
+
+    pub(super) fn access(&self, position: usize) -> (u32, &Category) {
+        let (instance, ref category) = self.accesses[position];
+        (instance, category)
+    }
+
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(super) fn subscripts(&self, position: usize) -> &[i32] {
+        &self.indices[position * self.rank..(position + 1) * self.rank]
+    }
+
+    /// Positions of the accesses together with the element they access,
+    /// ordered by element. Accesses of the same element keep their order in
+    /// the trace.
+    pub(super) fn by_element(&self) -> Vec<(u64, u32)> {
+        let mut sorted = match self.offsets() {
+            Some(sorted) => sorted,
+            None => self.ranks(),
+        };
+
+        sorted.sort_unstable();
+        sorted
+    }
 
+    /// Offset of the element of every access if the elements are linearized
+    /// in the bounding box of all subscripts, `None` if it is too large
+    fn offsets(&self) -> Option<Vec<(u64, u32)>> {
+        let mut lower = vec![i32::MAX; self.rank];
+        let mut upper = vec![i32::MIN; self.rank];
+        for subscripts in self.indices.chunks(self.rank.max(1)) {
+            for (d, &i) in subscripts.iter().enumerate() {
+                lower[d] = lower[d].min(i);
+                upper[d] = upper[d].max(i);
+            }
+        }
+
+        let mut strides = Vec::with_capacity(self.rank);
+        let mut size = 1u64;
+        for (l, u) in lower.iter().zip(upper.iter()) {
+            strides.push(size);
+            size = size.checked_mul((i64::from(*u) - i64::from(*l) + 1) as u64)?;
+        }
+
+        let offsets = (0..self.accesses.len())
+            .map(|position| {
+                let offset = self
+                    .subscripts(position)
+                    .iter()
+                    .zip(lower.iter().zip(strides.iter()))
+                    .map(|(&i, (&l, &stride))| (i64::from(i) - i64::from(l)) as u64 * stride)
+                    .sum();
+
+                (offset, position as u32)
+            })
+            .collect();
+
+        Some(offsets)
+    }
+
+    /// Rank of the element of every access among all accessed elements
+    fn ranks(&self) -> Vec<(u64, u32)> {
+        let mut positions = (0..self.accesses.len() as u32).collect::<Vec<_>>();
+        positions.sort_by(|&p1, &p2| {
+            self.subscripts(p1 as usize)
+                .cmp(self.subscripts(p2 as usize))
+        });
+
+        let mut element = 0;
+        let mut ranks = Vec::with_capacity(positions.len());
+        for (i, &position) in positions.iter().enumerate() {
+            if i > 0
+                && self.subscripts(positions[i - 1] as usize) != self.subscripts(position as usize)
+            {
+                element += 1;
+            }
+            ranks.push((element, position));
+        }
+
+        ranks
+    }
+}
+
+/// Statement instances and accesses of a trace, built from its outputs in
+/// order
+struct TraceBuilder {
+    trace: PackedTrace,
+    ids: HashMap<String, usize>,
+    last_statement: Statement,
+    loops: Vec<LoopLabel>,
+    iteration: Vec<i32>,
+    loop_updated: bool,
+}
+
+impl TraceBuilder {
+    fn new() -> Self {
+        TraceBuilder {
+            trace: PackedTrace {
+                instances: Vec::new(),
+                vars: Vec::new(),
+            },
+            ids: HashMap::new(),
+            last_statement: 0,
+            loops: Vec::new(),
+            iteration: Vec::new(),
+            loop_updated: false,
+        }
+    }
+
+    fn push(&mut self, t: TraceOutput) -> io::Result<()> {
         // Deal with different trace outputs
         match t {
-            TraceOutput::Access(mut a) => {
+            TraceOutput::Access(a) => {
+                let instances = &mut self.trace.instances;
+
                 // Begin of new statement instance detected
-                if a.statement != last_statement || loop_updated {
+                if a.statement != self.last_statement || self.loop_updated {
                     instances.push(StatementInstance {
                         statement: a.statement,
-                        loops: loops.clone(),
-                        iteration: iteration.clone(),
+                        loops: self.loops.clone(),
+                        iteration: self.iteration.clone(),
                     });
 
-                    last_statement = a.statement;
-                    loop_updated = false;
+                    self.last_statement = a.statement;
+                    self.loop_updated = false;
                 }
 
-                // Update reference of statement to instance
-                a.statement = instances.len() as i32 - 1;
-                access.push(a);
+                let vars = &mut self.trace.vars;
+                let id = match self.ids.get(&a.var) {
+                    Some(&id) => id,
+                    None => {
+                        vars.push(VarAccesses::new(a.var.to_owned(), a.indices.len()));
+                        self.ids.insert(a.var, vars.len() - 1);
+                        vars.len() - 1
+                    }
+                };
+
+                let var = &mut vars[id];
+                if var.rank != a.indices.len() {
+                    return Err(io::Error::other(format!(
+                        "Variable {} accessed with {} and {} subscripts",
+                        var.name,
+                        var.rank,
+                        a.indices.len()
+                    )));
+                }
+
+                // Refer to the instance instead of the statement
+                var.accesses.push((instances.len() as u32 - 1, a.category));
+                var.indices.extend_from_slice(&a.indices);
             }
             TraceOutput::LoopBegin(label) => {
-                loops.push(label);
-                iteration.push(0);
+                self.loops.push(label);
+                self.iteration.push(0);
             }
-            TraceOutput::LoopEnd => {
-                loops.pop();
-                iteration.pop();
+            TraceOutput::LoopEnd(_) => {
+                self.loops.pop();
+                self.iteration.pop();
             }
-            TraceOutput::LoopUpdate(index) => {
-                if let Some(i) = iteration.last_mut() {
+            TraceOutput::LoopUpdate(_, index) => {
+                if let Some(i) = self.iteration.last_mut() {
                     *i = index
                 }
 
-                loop_updated = true;
+                self.loop_updated = true;
             }
         }
-    }
-
-    Ok((instances, access))
-}
 
-pub(super) fn split_and_sort_trace(trace: Vec<Access>) -> HashMap<String, Vec<Access>> {
-    let mut map: HashMap<String, Vec<Access>> = HashMap::new();
-
-    for a in trace {
-        map.entry(a.var.to_owned())
-            .and_modify(|v| v.push(a.clone()))
-            .or_insert(vec![a]);
-    }
-
-    for vec in map.values_mut() {
-        vec.sort();
+        Ok(())
     }
-
-    map
 }
 
 impl FromStr for TraceOutput {
     type Err = TraceError;
 
     fn from_str(trace_line: &str) -> Result<Self, Self::Err> {
+        // Nothing may follow the output on the line
         match parse_trace(CompleteStr(trace_line)) {
-            Ok((_, t)) => Ok(t),
-            Err(_) => Err(TraceError::ParseAccessError),
+            Ok((rest, t)) if rest.trim().is_empty() => Ok(t),
+            _ => Err(TraceError::ParseAccessError),
         }
     }
 }
 
-impl Ord for Access {
-    fn cmp(&self, other: &Access) -> Ordering {
-        self.indices.cmp(&other.indices)
-    }
-}
-
-impl PartialOrd for Access {
-    fn partial_cmp(&self, other: &Access) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl PartialEq for Access {
-    fn eq(&self, other: &Access) -> bool {
-        self.indices == other.indices
-    }
-}
-
 named!(i32_digit<CompleteStr, i32>, map_res!(
     digit,
     |c: CompleteStr| FromStr::from_str(*c)
@@ -137,18 +346,18 @@ named!(loop_begin<CompleteStr, TraceOutput>, ws!(do_parse!(
 )));
 
 named!(loop_end<CompleteStr, TraceOutput>, ws!(do_parse!(
-    _label: i32_digit        >>
+    label: i32_digit         >>
     _var: take_until!(" ")   >>
     tag!("loop")             >>
     tag!("end")              >>
-    (TraceOutput::LoopEnd)
+    (TraceOutput::LoopEnd(label))
 )));
 
 named!(loop_iteration<CompleteStr, TraceOutput>, ws!(do_parse!(
-    _label: i32_digit        >>
+    label: i32_digit         >>
     _index: take_until!(" ") >>
     value: i32_digit         >>
-    (TraceOutput::LoopUpdate(value))
+    (TraceOutput::LoopUpdate(label, value))
 )));
 
 named!(parse_trace<CompleteStr, TraceOutput>, alt!(
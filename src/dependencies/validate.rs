@@ -0,0 +1,252 @@
+use super::*;
+use ir::{self, Ast};
+
+/// Consistency checks of trace outputs in trace order. Loops have to be
+/// begun, updated and ended in proper nesting. Given the program, accesses
+/// have to belong to its assignments and variables, and statements have to
+/// be executed inside the loops enclosing them in the program.
+pub(super) struct Validator<'a> {
+    loops: Vec<LoopLabel>,
+    program: Option<Program<'a>>,
+}
+
+struct Program<'a> {
+    /// Assignments by label, with their enclosing loops
+    assignments: HashMap<Statement, Vec<LoopLabel>>,
+    /// Loops by label, with their enclosing loops
+    loops: HashMap<LoopLabel, Vec<LoopLabel>>,
+    /// Declared variables with their rank
+    variables: HashMap<&'a str, usize>,
+}
+
+impl<'a> Validator<'a> {
+    pub(super) fn new(ast: Option<&'a Ast>) -> Self {
+        Validator {
+            loops: Vec::new(),
+            program: ast.map(Program::new),
+        }
+    }
+
+    pub(super) fn check(&mut self, t: &TraceOutput) -> Result<(), String> {
+        match t {
+            TraceOutput::Access(a) => {
+                if let Some(program) = &self.program {
+                    program.check_access(a, &self.loops)?;
+                }
+            }
+            TraceOutput::LoopBegin(label) => {
+                if let Some(program) = &self.program {
+                    program.check_loop(*label, &self.loops)?;
+                }
+
+                self.loops.push(*label);
+            }
+            TraceOutput::LoopEnd(label) => {
+                self.check_innermost(*label, "End")?;
+                self.loops.pop();
+            }
+            TraceOutput::LoopUpdate(label, _) => self.check_innermost(*label, "Update")?,
+        }
+
+        Ok(())
+    }
+
+    /// Check that all loops have ended at the end of the trace
+    pub(super) fn finish(&self) -> Result<(), String> {
+        match self.loops.last() {
+            Some(label) => Err(format!("Loop {} does not end", label)),
+            None => Ok(()),
+        }
+    }
+
+    fn check_innermost(&self, label: LoopLabel, event: &str) -> Result<(), String> {
+        match self.loops.last() {
+            Some(&innermost) if innermost == label => Ok(()),
+            Some(innermost) => Err(format!(
+                "{} of loop {} inside of loop {}",
+                event, label, innermost
+            )),
+            None => Err(format!("{} of loop {} outside of any loop", event, label)),
+        }
+    }
+}
+
+impl<'a> Program<'a> {
+    fn new(ast: &'a Ast) -> Self {
+        let mut program = Program {
+            assignments: HashMap::new(),
+            loops: HashMap::new(),
+            variables: ast
+                .vardef
+                .iter()
+                .map(|d| (d.name.as_str(), d.dimensions.len()))
+                .collect(),
+        };
+
+        program.add_statements(&ast.statements, &mut Vec::new());
+        program
+    }
+
+    fn add_statements(&mut self, statements: &ir::StatementList, loops: &mut Vec<LoopLabel>) {
+        for statement in statements.0.iter() {
+            match statement {
+                ir::Statement::Assignment(a) => {
+                    self.assignments.insert(a.label, loops.clone());
+                }
+                ir::Statement::Loop(l) => {
+                    self.loops.insert(l.label, loops.clone());
+                    loops.push(l.label);
+                    self.add_statements(&l.statements, loops);
+                    loops.pop();
+                }
+                ir::Statement::If(i) => {
+                    self.add_statements(&i.then_branch, loops);
+                    self.add_statements(&i.else_branch, loops);
+                }
+            }
+        }
+    }
+
+    fn check_access(&self, a: &Access, loops: &[LoopLabel]) -> Result<(), String> {
+        match self.assignments.get(&a.statement) {
+            Some(enclosing) if enclosing.as_slice() == loops => (),
+            Some(enclosing) => {
+                return Err(format!(
+                    "Statement {:03} executed in loops {:?} instead of {:?}",
+                    a.statement, loops, enclosing
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "Statement {:03} is no assignment of the program",
+                    a.statement
+                ))
+            }
+        }
+
+        match self.variables.get(a.var.as_str()) {
+            Some(&rank) if rank == a.indices.len() => Ok(()),
+            Some(rank) => Err(format!(
+                "Variable {} of rank {} accessed with {} subscripts",
+                a.var,
+                rank,
+                a.indices.len()
+            )),
+            None => Err(format!("Variable {} is not declared", a.var)),
+        }
+    }
+
+    fn check_loop(&self, label: LoopLabel, loops: &[LoopLabel]) -> Result<(), String> {
+        match self.loops.get(&label) {
+            Some(enclosing) if enclosing.as_slice() == loops => Ok(()),
+            Some(enclosing) => Err(format!(
+                "Loop {} begins in loops {:?} instead of {:?}",
+                label, loops, enclosing
+            )),
+            None => Err(format!("Loop {} is no loop of the program", label)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(statement: Statement, var: &str, indices: Vec<i32>) -> TraceOutput {
+        TraceOutput::Access(Access {
+            statement,
+            var: var.to_owned(),
+            category: Category::Read,
+            indices,
+        })
+    }
+
+    /// First error when checking the outputs in order, including the end of
+    /// the trace
+    fn error(ast: Option<&Ast>, outputs: &[TraceOutput]) -> Option<String> {
+        let mut validator = Validator::new(ast);
+        outputs
+            .iter()
+            .map(|t| validator.check(t))
+            .find_map(Result::err)
+            .or_else(|| validator.finish().err())
+    }
+
+    #[test]
+    fn improperly_nested_loops() {
+        use super::TraceOutput::*;
+
+        assert_eq!(
+            error(None, &[LoopEnd(2)]),
+            Some("End of loop 2 outside of any loop".to_owned())
+        );
+        assert_eq!(
+            error(None, &[LoopUpdate(2, 1)]),
+            Some("Update of loop 2 outside of any loop".to_owned())
+        );
+        assert_eq!(
+            error(None, &[LoopBegin(2), LoopBegin(3), LoopUpdate(2, 1)]),
+            Some("Update of loop 2 inside of loop 3".to_owned())
+        );
+        assert_eq!(
+            error(None, &[LoopBegin(2), LoopBegin(3), LoopEnd(2)]),
+            Some("End of loop 2 inside of loop 3".to_owned())
+        );
+        assert_eq!(
+            error(None, &[LoopBegin(2), LoopUpdate(2, 1)]),
+            Some("Loop 2 does not end".to_owned())
+        );
+        assert_eq!(
+            error(None, &[LoopBegin(2), LoopUpdate(2, 1), LoopEnd(2)]),
+            None
+        );
+    }
+
+    #[test]
+    fn outputs_not_matching_the_program() {
+        use super::TraceOutput::*;
+
+        let text = "p a FLOAT 1 1 10 i INT STMTLIST \
+                    FOR @ 1 ENTRY i EXPR INT 1 EXPR INT 10 STMTLIST \
+                    ASSIGN @ 2 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR INT 0 \
+                    /STMTLIST /STMTLIST $";
+        let ast = match ir::parse_ast(text) {
+            Ok((_, ast)) => ast,
+            Err(e) => panic!("Could not parse program: {}", e),
+        };
+        let ast = Some(&ast);
+
+        let valid = [
+            LoopBegin(1),
+            LoopUpdate(1, 1),
+            access(2, "a", vec![1]),
+            LoopEnd(1),
+        ];
+        assert_eq!(error(ast, &valid), None);
+
+        assert_eq!(
+            error(ast, &[access(2, "a", vec![1])]),
+            Some("Statement 002 executed in loops [] instead of [1]".to_owned())
+        );
+        assert_eq!(
+            error(ast, &[LoopBegin(1), access(3, "a", vec![1])]),
+            Some("Statement 003 is no assignment of the program".to_owned())
+        );
+        assert_eq!(
+            error(ast, &[LoopBegin(1), access(2, "a", vec![1, 2])]),
+            Some("Variable a of rank 1 accessed with 2 subscripts".to_owned())
+        );
+        assert_eq!(
+            error(ast, &[LoopBegin(1), access(2, "b", vec![1])]),
+            Some("Variable b is not declared".to_owned())
+        );
+        assert_eq!(
+            error(ast, &[LoopBegin(1), LoopBegin(1)]),
+            Some("Loop 1 begins in loops [1] instead of []".to_owned())
+        );
+        assert_eq!(
+            error(ast, &[LoopBegin(4)]),
+            Some("Loop 4 is no loop of the program".to_owned())
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use super::*;
+use std::io;
+
+/// Fold operations on integer and real literals into a single literal.
+///
+/// Operations which overflow, divide by zero or have no finite result are
+/// left unfolded with a warning naming the statement `label`. Operators which
+/// are invalid in their position, e.g. a unary `*`, are an error.
+pub fn fold_expr(expr: &Expression, label: i32) -> io::Result<Expression> {
+    match expr {
+        Expression::Variable(var) => {
+            let mut indices = Vec::new();
+            for index in var.indices.iter() {
+                indices.push(fold_expr(index, label)?);
+            }
+
+            Ok(Expression::Variable(Variable {
+                name: var.name.to_owned(),
+                indices,
+            }))
+        }
+        Expression::Integer(i) => Ok(Expression::Integer(*i)),
+        Expression::Real(r) => Ok(Expression::Real(*r)),
+        Expression::BinOp(op) => {
+            if op.op == OpType::Not {
+                return Err(invalid_operator("binary", &op.op, label));
+            }
+
+            let left = fold_expr(&op.left, label)?;
+            let right = fold_expr(&op.right, label)?;
+
+            let folded = match (&left, &right) {
+                (Expression::Integer(l), Expression::Integer(r)) => fold_integers(&op.op, *l, *r),
+                (Expression::Integer(l), Expression::Real(r)) => {
+                    fold_reals(&op.op, f64::from(*l), *r)
+                }
+                (Expression::Real(l), Expression::Integer(r)) => {
+                    fold_reals(&op.op, *l, f64::from(*r))
+                }
+                (Expression::Real(l), Expression::Real(r)) => fold_reals(&op.op, *l, *r),
+                _ => return Ok(binop(&op.op, left, right)),
+            };
+
+            match folded {
+                Some(expr) => Ok(expr),
+                None => {
+                    eprintln!("{}", unfoldable(&op.op, &[&left, &right], label));
+                    Ok(binop(&op.op, left, right))
+                }
+            }
+        }
+        Expression::UnOp(op) => {
+            let right = fold_expr(&op.right, label)?;
+            let unfolded = |right| {
+                Ok(Expression::UnOp(Box::new(UnOp {
+                    op: op.op.clone(),
+                    right,
+                })))
+            };
+
+            match (&op.op, &right) {
+                (OpType::Minus, Expression::Integer(i)) => match i.checked_neg() {
+                    Some(i) => Ok(Expression::Integer(i)),
+                    None => {
+                        eprintln!("{}", unfoldable(&op.op, &[&right], label));
+                        unfolded(right)
+                    }
+                },
+                (OpType::Minus, Expression::Real(r)) => Ok(Expression::Real(-r)),
+                (OpType::Not, Expression::Integer(i)) => Ok(Expression::Integer((*i == 0) as i32)),
+                (OpType::Not, Expression::Real(r)) => Ok(Expression::Integer((*r == 0.0) as i32)),
+                (OpType::Plus, Expression::Integer(_)) | (OpType::Plus, Expression::Real(_)) => {
+                    Ok(right)
+                }
+                (OpType::Plus, _) | (OpType::Minus, _) | (OpType::Not, _) => unfolded(right),
+                _ => Err(invalid_operator("unary", &op.op, label)),
+            }
+        }
+        Expression::Expression(expr) => {
+            let expr = fold_expr(expr, label)?;
+            match expr {
+                Expression::Integer(_) | Expression::Real(_) => Ok(expr),
+                _ => Ok(Expression::Expression(Box::new(expr))),
+            }
+        }
+    }
+}
+
+fn fold_integers(op: &OpType, left: i32, right: i32) -> Option<Expression> {
+    let value = match op {
+        OpType::Plus => left.checked_add(right)?,
+        OpType::Minus => left.checked_sub(right)?,
+        OpType::Mul => left.checked_mul(right)?,
+        OpType::Div => left.checked_div(right)?,
+        OpType::Equal => (left == right) as i32,
+        OpType::NotEqual => (left != right) as i32,
+        OpType::Greater => (left > right) as i32,
+        OpType::GreaterEqual => (left >= right) as i32,
+        OpType::Lower => (left < right) as i32,
+        OpType::LowerEqual => (left <= right) as i32,
+        OpType::And => (left != 0 && right != 0) as i32,
+        OpType::Or => (left != 0 || right != 0) as i32,
+        OpType::Min => left.min(right),
+        OpType::Max => left.max(right),
+        OpType::Not => return None,
+    };
+
+    Some(Expression::Integer(value))
+}
+
+fn fold_reals(op: &OpType, left: f64, right: f64) -> Option<Expression> {
+    let value = match op {
+        OpType::Plus => left + right,
+        OpType::Minus => left - right,
+        OpType::Mul => left * right,
+        OpType::Div => left / right,
+        OpType::Min => left.min(right),
+        OpType::Max => left.max(right),
+        OpType::Equal => return Some(Expression::Integer((left == right) as i32)),
+        OpType::NotEqual => return Some(Expression::Integer((left != right) as i32)),
+        OpType::Greater => return Some(Expression::Integer((left > right) as i32)),
+        OpType::GreaterEqual => return Some(Expression::Integer((left >= right) as i32)),
+        OpType::Lower => return Some(Expression::Integer((left < right) as i32)),
+        OpType::LowerEqual => return Some(Expression::Integer((left <= right) as i32)),
+        OpType::And => return Some(Expression::Integer((left != 0.0 && right != 0.0) as i32)),
+        OpType::Or => return Some(Expression::Integer((left != 0.0 || right != 0.0) as i32)),
+        OpType::Not => return None,
+    };
+
+    if value.is_finite() {
+        Some(Expression::Real(value))
+    } else {
+        None
+    }
+}
+
+/// Warning about an operation left unfolded
+fn unfoldable(op: &OpType, operands: &[&Expression], label: i32) -> String {
+    let operands = operands
+        .iter()
+        .map(|e| literal(e))
+        .collect::<Vec<_>>()
+        .join(" and ");
+
+    format!(
+        "Warning: {:?} of {} can not be folded in statement {:03}, leaving it unfolded",
+        op, operands, label
+    )
+}
+
+fn literal(expr: &Expression) -> String {
+    match expr {
+        Expression::Integer(i) => i.to_string(),
+        Expression::Real(r) => r.to_string(),
+        _ => format!("{:?}", expr),
+    }
+}
+
+fn binop(op: &OpType, left: Expression, right: Expression) -> Expression {
+    Expression::BinOp(Box::new(BinOp {
+        op: op.clone(),
+        left,
+        right,
+    }))
+}
+
+fn invalid_operator(position: &str, op: &OpType, label: i32) -> io::Error {
+    io::Error::other(format!(
+        "Invalid {} operator {:?} in statement {:03}",
+        position, op, label
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(i: i32) -> Expression {
+        Expression::Integer(i)
+    }
+
+    fn unop(op: OpType, right: Expression) -> Expression {
+        Expression::UnOp(Box::new(UnOp { op, right }))
+    }
+
+    #[test]
+    fn folds_unless_checked_arithmetic_fails() {
+        let folded = |expr: &Expression| fold_expr(expr, 7).unwrap();
+
+        assert_eq!(folded(&binop(&OpType::Mul, int(6), int(7))), int(42));
+        assert_eq!(folded(&binop(&OpType::Div, int(-7), int(2))), int(-3));
+        assert_eq!(folded(&binop(&OpType::Lower, int(1), int(2))), int(1));
+        assert_eq!(folded(&unop(OpType::Minus, int(5))), int(-5));
+
+        let unfolded = vec![
+            binop(&OpType::Plus, int(i32::MAX), int(1)),
+            binop(&OpType::Mul, int(i32::MIN), int(-1)),
+            binop(&OpType::Div, int(1), int(0)),
+            binop(&OpType::Div, int(i32::MIN), int(-1)),
+            binop(&OpType::Div, Expression::Real(1.0), int(0)),
+            unop(OpType::Minus, int(i32::MIN)),
+        ];
+        for expr in unfolded {
+            assert_eq!(folded(&expr), expr);
+        }
+    }
+
+    #[test]
+    fn diagnostics_name_operands_and_statement() {
+        assert_eq!(
+            unfoldable(&OpType::Div, &[&int(1), &int(0)], 7),
+            "Warning: Div of 1 and 0 can not be folded in statement 007, leaving it unfolded"
+        );
+        assert_eq!(
+            unfoldable(&OpType::Minus, &[&int(i32::MIN)], 12),
+            "Warning: Minus of -2147483648 can not be folded in statement 012, leaving it unfolded"
+        );
+    }
+
+    #[test]
+    fn invalid_operators_are_errors() {
+        for expr in [
+            unop(OpType::Min, int(1)),
+            unop(OpType::Max, int(1)),
+            unop(OpType::Mul, int(1)),
+            binop(&OpType::Not, int(1), int(2)),
+        ]
+        .iter()
+        {
+            let e = fold_expr(expr, 3).unwrap_err();
+            assert!(e.to_string().ends_with("in statement 003"), "{}", e);
+        }
+    }
+}
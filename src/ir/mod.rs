@@ -1,54 +1,59 @@
+mod fold;
 mod parse;
+mod print;
 
+pub use self::fold::fold_expr;
 pub use self::parse::parse_ast;
+pub use self::print::print_ast;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ast {
     pub name: String,
     pub vardef: Vec<Definition>,
     pub statements: StatementList,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Definition {
     pub name: String,
     pub dimensions: Vec<(i32, i32)>,
     pub dtype: DefinitionType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DefinitionType {
     Real,
     Integer,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StatementList(pub Vec<Statement>);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     Assignment(Assign),
     Loop(Loop),
     If(If),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Assign {
     pub label: i32,
     pub lhs: Variable,
     pub rhs: Expression,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Loop {
     pub label: i32,
     pub var: String,
     pub lower: Expression,
     pub upper: Expression,
+    pub step: Option<Expression>,
     pub statements: StatementList,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct If {
     pub label: i32,
     pub expr: Expression,
@@ -56,13 +61,13 @@ pub struct If {
     pub else_branch: StatementList,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Variable {
     pub name: String,
     pub indices: Vec<Expression>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Integer(i32),
     Real(f64),
@@ -72,7 +77,7 @@ pub enum Expression {
     Expression(Box<Expression>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum OpType {
     Plus,
     Minus,
@@ -87,15 +92,17 @@ pub enum OpType {
     And,
     Or,
     Not,
+    Min,
+    Max,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct UnOp {
     pub op: OpType,
     pub right: Expression,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BinOp {
     pub op: OpType,
     pub left: Expression,
@@ -74,13 +74,15 @@ named!(parse_binoptype<&str,OpType>, ws!(alt!(
     map!(tag!("/"), |_| OpType::Div) |
     map!(tag!("=="), |_| OpType::Equal) |
     map!(tag!("<>"), |_| OpType::NotEqual) |
-    map!(tag!(">"), |_| OpType::Greater) |
     map!(tag!(">="), |_| OpType::GreaterEqual) |
-    map!(tag!("<"), |_| OpType::Lower) |
+    map!(tag!(">"), |_| OpType::Greater) |
     map!(tag!("<="), |_| OpType::LowerEqual) |
+    map!(tag!("<"), |_| OpType::Lower) |
     map!(tag!(".and."), |_| OpType::And) |
     map!(tag!(".or."), |_| OpType::Or) |
-    map!(tag!(".not."), |_| OpType::Not)
+    map!(tag!(".not."), |_| OpType::Not) |
+    map!(tag!("min"), |_| OpType::Min) |
+    map!(tag!("max"), |_| OpType::Max)
 )));
 
 named!(parse_unop<&str,Expression>, ws!(do_parse!(
@@ -126,13 +128,20 @@ named!(parse_loop<&str,Statement>, ws!(do_parse!(
     var: parse_entry                  >>
     lower: parse_expr                 >>
     upper: parse_expr                 >>
+    step: opt!(parse_step)            >>
     statements: parse_stmtlist        >>
     (Statement::Loop(Loop {
-        label, var, lower, upper,
+        label, var, lower, upper, step,
         statements
     }))
 )));
 
+named!(parse_step<&str,Expression>, ws!(do_parse!(
+    tag!("STEP")      >>
+    step: parse_expr  >>
+    (step)
+)));
+
 named!(parse_stmtlist<&str,StatementList>, ws!(do_parse!(
     tag!("STMTLIST")                                         >>
     list: many0!(alt!(parse_assign | parse_loop | parse_if)) >>
@@ -206,3 +215,27 @@ named!(pub parse_ast<&str,Ast>, ws!(do_parse!(
     statements: parse_stmtlist >>
     (Ast { name: name.to_owned(), vardef, statements })
 )));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Operator of a binary expression in the IR
+    fn binop(symbol: &str) -> String {
+        let text = format!("EXPR BINOP {} EXPR VAR ENTRY i EXPR INT 1 $", symbol);
+        match parse_expr(&text) {
+            Ok((_, Expression::BinOp(op))) => format!("{:?}", op.op),
+            e => panic!("Could not parse {}: {:?}", symbol, e),
+        }
+    }
+
+    #[test]
+    fn comparison_operators() {
+        assert_eq!(binop("=="), "Equal");
+        assert_eq!(binop("<>"), "NotEqual");
+        assert_eq!(binop(">="), "GreaterEqual");
+        assert_eq!(binop(">"), "Greater");
+        assert_eq!(binop("<="), "LowerEqual");
+        assert_eq!(binop("<"), "Lower");
+    }
+}
@@ -0,0 +1,145 @@
+use super::*;
+use std::io;
+
+/// Write the AST in the textual IR format accepted by `parse_ast`
+pub fn print_ast<W>(ast: &Ast, out: &mut W) -> io::Result<()>
+where
+    W: io::Write,
+{
+    writeln!(out, "{}", &ast.name)?;
+
+    for def in ast.vardef.iter() {
+        print_definition(def, out)?;
+    }
+
+    print_stmtlist(&ast.statements.0, out, 0)?;
+    writeln!(out)
+}
+
+fn print_definition<W: io::Write>(def: &Definition, out: &mut W) -> io::Result<()> {
+    write!(out, "{} ", &def.name)?;
+
+    match def.dtype {
+        DefinitionType::Integer => write!(out, "INT")?,
+        DefinitionType::Real => write!(out, "FLOAT")?,
+    }
+
+    if !def.dimensions.is_empty() {
+        write!(out, " {}", def.dimensions.len())?;
+        for (lb, ub) in def.dimensions.iter() {
+            write!(out, " {} {}", lb, ub)?;
+        }
+    }
+
+    writeln!(out)
+}
+
+fn print_stmtlist<W: io::Write>(
+    statements: &[Statement],
+    out: &mut W,
+    indent: usize,
+) -> io::Result<()> {
+    write!(out, "STMTLIST")?;
+
+    for statement in statements {
+        write!(out, "\n{}", "  ".repeat(indent + 1))?;
+        print_statement(statement, out, indent + 1)?;
+    }
+
+    write!(out, "\n{}/STMTLIST", "  ".repeat(indent))
+}
+
+fn print_statement<W: io::Write>(
+    statement: &Statement,
+    out: &mut W,
+    indent: usize,
+) -> io::Result<()> {
+    match statement {
+        Statement::Assignment(a) => {
+            write!(out, "ASSIGN @ {} ", a.label)?;
+            print_variable(&a.lhs, out)?;
+            write!(out, " ")?;
+            print_expression(&a.rhs, out)
+        }
+        Statement::Loop(l) => {
+            write!(out, "FOR @ {} ENTRY {} ", l.label, &l.var)?;
+            print_expression(&l.lower, out)?;
+            write!(out, " ")?;
+            print_expression(&l.upper, out)?;
+            if let Some(step) = &l.step {
+                write!(out, " STEP ")?;
+                print_expression(step, out)?;
+            }
+            write!(out, " ")?;
+            print_stmtlist(&l.statements.0, out, indent)
+        }
+        Statement::If(i) => {
+            write!(out, "FOR @ {} ", i.label)?;
+            print_expression(&i.expr, out)?;
+            write!(out, " ")?;
+            print_stmtlist(&i.then_branch.0, out, indent)?;
+            write!(out, " ")?;
+            print_stmtlist(&i.else_branch.0, out, indent)
+        }
+    }
+}
+
+fn print_variable<W: io::Write>(var: &Variable, out: &mut W) -> io::Result<()> {
+    // Entries are terminated by the following whitespace
+    write!(out, "VAR ENTRY {}", &var.name)?;
+
+    if !var.indices.is_empty() {
+        write!(out, " EXPRLIST")?;
+        for index in var.indices.iter() {
+            write!(out, " ")?;
+            print_expression(index, out)?;
+        }
+        write!(out, " /EXPRLIST")?;
+    }
+
+    Ok(())
+}
+
+fn print_expression<W: io::Write>(expr: &Expression, out: &mut W) -> io::Result<()> {
+    write!(out, "EXPR ")?;
+
+    match expr {
+        // The parser only accepts unsigned literals
+        Expression::Integer(i) if *i < 0 => write!(out, "UNOP - EXPR INT {}", -(*i as i64)),
+        Expression::Integer(i) => write!(out, "INT {}", i),
+        Expression::Real(r) if *r < 0.0 => write!(out, "UNOP - EXPR FLOAT {:?}", -r),
+        Expression::Real(r) => write!(out, "FLOAT {:?}", r),
+        Expression::Variable(var) => print_variable(var, out),
+        Expression::UnOp(op) => {
+            write!(out, "UNOP {} ", op_token(&op.op))?;
+            print_expression(&op.right, out)
+        }
+        Expression::BinOp(op) => {
+            write!(out, "BINOP {} ", op_token(&op.op))?;
+            print_expression(&op.left, out)?;
+            write!(out, " ")?;
+            print_expression(&op.right, out)
+        }
+        Expression::Expression(expr) => print_expression(expr, out),
+    }
+}
+
+fn op_token(op: &OpType) -> &'static str {
+    match op {
+        OpType::Plus => "+",
+        OpType::Minus => "-",
+        OpType::Mul => "*",
+        OpType::Div => "/",
+        OpType::Equal => "==",
+        OpType::NotEqual => "<>",
+        OpType::Greater => ">",
+        OpType::GreaterEqual => ">=",
+        OpType::Lower => "<",
+        OpType::LowerEqual => "<=",
+        OpType::And => ".and.",
+        OpType::Or => ".or.",
+        OpType::Not => ".not.",
+        OpType::Min => "min",
+        OpType::Max => "max",
+    }
+}
@@ -7,4 +7,5 @@ extern crate petgraph;
 pub mod codegen;
 pub mod dependencies;
 pub mod ir;
+pub mod transform;
 pub mod vectorization;
@@ -0,0 +1,152 @@
+use super::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// Integer expression of the form `c + a1*v1 + ... + an*vn`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Affine {
+    pub terms: BTreeMap<String, i32>,
+    pub constant: i32,
+}
+
+impl Affine {
+    pub fn constant(constant: i32) -> Self {
+        Affine {
+            terms: BTreeMap::new(),
+            constant,
+        }
+    }
+
+    pub fn var(name: &str) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(name.to_owned(), 1);
+
+        Affine { terms, constant: 0 }
+    }
+
+    /// Convert an expression, if it only consists of integers, scalar
+    /// variables, additions, subtractions and multiplications by constants
+    pub fn from_expr(expr: &Expression) -> Option<Self> {
+        match expr {
+            Expression::Integer(i) => Some(Affine::constant(*i)),
+            Expression::Variable(var) if var.indices.is_empty() => Some(Affine::var(&var.name)),
+            Expression::Expression(expr) => Affine::from_expr(expr),
+            Expression::UnOp(op) => {
+                let right = Affine::from_expr(&op.right)?;
+                match op.op {
+                    OpType::Plus => Some(right),
+                    OpType::Minus => right.scale(-1),
+                    _ => None,
+                }
+            }
+            Expression::BinOp(op) => {
+                let left = Affine::from_expr(&op.left)?;
+                let right = Affine::from_expr(&op.right)?;
+                match op.op {
+                    OpType::Plus => left.add(&right),
+                    OpType::Minus => left.add(&right.scale(-1)?),
+                    OpType::Mul if left.is_constant() => right.scale(left.constant),
+                    OpType::Mul if right.is_constant() => left.scale(right.constant),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn coefficient(&self, var: &str) -> i32 {
+        *self.terms.get(var).unwrap_or(&0)
+    }
+
+    /// The expression without the term of `var`
+    pub fn without(&self, var: &str) -> Self {
+        let mut result = self.clone();
+        result.terms.remove(var);
+        result
+    }
+
+    pub fn uses(&self, var: &str) -> bool {
+        self.terms.contains_key(var)
+    }
+
+    /// Value of the expression, if all of its variables have a value
+    pub fn evaluate(&self, values: &HashMap<&str, i32>) -> Option<i32> {
+        let mut value = self.constant;
+        for (var, c) in self.terms.iter() {
+            value = value.checked_add(c.checked_mul(*values.get(var.as_str())?)?)?;
+        }
+
+        Some(value)
+    }
+
+    pub fn add(&self, other: &Affine) -> Option<Self> {
+        let mut result = self.clone();
+        result.constant = result.constant.checked_add(other.constant)?;
+
+        for (var, c) in other.terms.iter() {
+            let sum = result.coefficient(var).checked_add(*c)?;
+            if sum == 0 {
+                result.terms.remove(var);
+            } else {
+                result.terms.insert(var.to_owned(), sum);
+            }
+        }
+
+        Some(result)
+    }
+
+    pub fn scale(&self, factor: i32) -> Option<Self> {
+        if factor == 0 {
+            return Some(Affine::constant(0));
+        }
+
+        let mut terms = BTreeMap::new();
+        for (var, c) in self.terms.iter() {
+            terms.insert(var.to_owned(), c.checked_mul(factor)?);
+        }
+
+        Some(Affine {
+            terms,
+            constant: self.constant.checked_mul(factor)?,
+        })
+    }
+
+    /// Build an expression, terms are ordered by variable name and followed
+    /// by the constant
+    pub fn to_expr(&self) -> Expression {
+        let mut expr: Option<Expression> = None;
+
+        for (var, c) in self.terms.iter() {
+            let v = Expression::Variable(Variable {
+                name: var.to_owned(),
+                indices: Vec::new(),
+            });
+            let term = match c.abs() {
+                1 => v,
+                a => binop(OpType::Mul, Expression::Integer(a), v),
+            };
+
+            expr = Some(match expr {
+                None if *c < 0 => Expression::UnOp(Box::new(UnOp {
+                    op: OpType::Minus,
+                    right: term,
+                })),
+                None => term,
+                Some(e) if *c < 0 => binop(OpType::Minus, e, term),
+                Some(e) => binop(OpType::Plus, e, term),
+            });
+        }
+
+        match expr {
+            None => Expression::Integer(self.constant),
+            Some(e) if self.constant == 0 => e,
+            Some(e) => match self.constant.checked_neg() {
+                Some(c) if c > 0 => binop(OpType::Minus, e, Expression::Integer(c)),
+                _ => binop(OpType::Plus, e, Expression::Integer(self.constant)),
+            },
+        }
+    }
+}
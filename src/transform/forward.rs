@@ -0,0 +1,272 @@
+use super::*;
+use std::collections::HashMap;
+
+/// Forward substitute scalar temporaries assigned in loop bodies.
+///
+/// For an assignment `t = e` directly in a loop body, uses of `t` in the
+/// following statements of the body are replaced by `e` as long as none of the
+/// variables of `e` are assigned in between. The assignment is removed, if all
+/// uses in the loop were replaced and `t` is not referenced anywhere else in
+/// the program. Uses of `t` before its assignment read the value of the
+/// previous iteration and prevent the substitution, as does `e` having a
+/// different type than `t`. Also returns the labels of the removed
+/// assignments.
+pub fn forward_substitute(ast: &Ast) -> io::Result<(Ast, Vec<Label>)> {
+    let mut forward = Forward {
+        references: HashMap::new(),
+        reals: ast
+            .vardef
+            .iter()
+            .filter(|d| match d.dtype {
+                DefinitionType::Real => true,
+                DefinitionType::Integer => false,
+            })
+            .map(|d| d.name.to_owned())
+            .collect(),
+        removed: Vec::new(),
+    };
+    count_references(&ast.statements.0, &mut forward.references);
+
+    let ast = Ast {
+        name: ast.name.to_owned(),
+        vardef: ast.vardef.clone(),
+        statements: forward.stmtlist(&ast.statements.0),
+    };
+    Ok((ast, forward.removed))
+}
+
+struct Forward {
+    /// Number of statements referencing each name in the whole program
+    references: HashMap<String, usize>,
+    reals: HashSet<String>,
+    /// Labels of the removed assignments
+    removed: Vec<Label>,
+}
+
+impl Forward {
+    fn stmtlist(&mut self, statements: &[Statement]) -> StatementList {
+        StatementList(
+            statements
+                .iter()
+                .map(|s| match s {
+                    Statement::Loop(l) => {
+                        let statements = self.stmtlist(&l.statements.0);
+                        Statement::Loop(self.substitute_loop(Loop {
+                            statements,
+                            ..l.clone()
+                        }))
+                    }
+                    Statement::If(i) => Statement::If(If {
+                        label: i.label,
+                        expr: i.expr.clone(),
+                        then_branch: self.stmtlist(&i.then_branch.0),
+                        else_branch: self.stmtlist(&i.else_branch.0),
+                    }),
+                    Statement::Assignment(_) => s.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    fn substitute_loop(&mut self, mut l: Loop) -> Loop {
+        let mut index = 0;
+
+        while index < l.statements.0.len() {
+            let (t, e) = match &l.statements.0[index] {
+                Statement::Assignment(a)
+                    if a.lhs.indices.is_empty() && !uses_var(&a.rhs, &a.lhs.name) =>
+                {
+                    (a.lhs.name.to_owned(), a.rhs.clone())
+                }
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+
+            let body = &l.statements.0;
+            let earlier = body[..index]
+                .iter()
+                .any(|s| statement_references(s, &t) > 0);
+            let mut assigned = HashSet::new();
+            assigned_names(&body[index + 1..], &mut assigned);
+            let same_type = self.reals.contains(&t) == self.is_real(&e);
+            if earlier || !same_type || assigned.contains(&t) || l.var == t {
+                index += 1;
+                continue;
+            }
+
+            let mut operands = HashSet::new();
+            expression_names(&e, &mut operands);
+            let references = body
+                .iter()
+                .map(|s| statement_references(s, &t))
+                .sum::<usize>();
+
+            // Replace uses until an operand of the expression changes
+            let mut statements = body[..=index].to_vec();
+            let mut remaining = 0;
+            let mut blocked = false;
+            for s in body[index + 1..].iter() {
+                let mut written = HashSet::new();
+                assigned_names(::std::slice::from_ref(s), &mut written);
+                let changes = written.iter().any(|w| operands.contains(w));
+
+                let simple = matches!(s, Statement::Assignment(_));
+                if blocked || (changes && !simple) {
+                    remaining += statement_references(s, &t);
+                    statements.push(s.clone());
+                } else {
+                    statements.push(substitute_statement(s, &t, &e));
+                }
+                blocked = blocked || changes;
+            }
+
+            let uses = statements[index + 1..]
+                .iter()
+                .map(|s| statement_references(s, &t))
+                .sum::<usize>();
+            if uses + remaining == 0 && self.references.get(&t) == Some(&references) {
+                self.removed.push(label(&statements[index]));
+                statements.remove(index);
+            } else {
+                index += 1;
+            }
+
+            l.statements = simplify_subscripts(&statements);
+        }
+
+        l
+    }
+
+    fn is_real(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Integer(_) => false,
+            Expression::Real(_) => true,
+            Expression::Variable(var) => self.reals.contains(&var.name),
+            Expression::UnOp(op) => self.is_real(&op.right),
+            Expression::BinOp(op) => self.is_real(&op.left) || self.is_real(&op.right),
+            Expression::Expression(expr) => self.is_real(expr),
+        }
+    }
+}
+
+fn label(statement: &Statement) -> i32 {
+    match statement {
+        Statement::Assignment(a) => a.label,
+        Statement::Loop(l) => l.label,
+        Statement::If(i) => i.label,
+    }
+}
+
+/// Names of all variables and arrays assigned in the statements
+fn assigned_names(statements: &[Statement], names: &mut HashSet<String>) {
+    for s in statements {
+        match s {
+            Statement::Assignment(a) => {
+                names.insert(a.lhs.name.to_owned());
+            }
+            Statement::Loop(l) => {
+                names.insert(l.var.to_owned());
+                assigned_names(&l.statements.0, names);
+            }
+            Statement::If(i) => {
+                assigned_names(&i.then_branch.0, names);
+                assigned_names(&i.else_branch.0, names);
+            }
+        }
+    }
+}
+
+fn expression_names(expr: &Expression, names: &mut HashSet<String>) {
+    match expr {
+        Expression::Integer(_) | Expression::Real(_) => (),
+        Expression::Variable(var) => {
+            names.insert(var.name.to_owned());
+            for index in var.indices.iter() {
+                expression_names(index, names);
+            }
+        }
+        Expression::UnOp(op) => expression_names(&op.right, names),
+        Expression::BinOp(op) => {
+            expression_names(&op.left, names);
+            expression_names(&op.right, names);
+        }
+        Expression::Expression(expr) => expression_names(expr, names),
+    }
+}
+
+/// Number of references to `name` in the statements, including assignments
+fn count_references(statements: &[Statement], references: &mut HashMap<String, usize>) {
+    for s in statements {
+        let mut names = HashSet::new();
+        match s {
+            Statement::Assignment(a) => {
+                names.insert(a.lhs.name.to_owned());
+                for index in a.lhs.indices.iter() {
+                    expression_names(index, &mut names);
+                }
+                expression_names(&a.rhs, &mut names);
+            }
+            Statement::Loop(l) => {
+                names.insert(l.var.to_owned());
+                expression_names(&l.lower, &mut names);
+                expression_names(&l.upper, &mut names);
+                if let Some(step) = &l.step {
+                    expression_names(step, &mut names);
+                }
+                count_references(&l.statements.0, references);
+            }
+            Statement::If(i) => {
+                expression_names(&i.expr, &mut names);
+                count_references(&i.then_branch.0, references);
+                count_references(&i.else_branch.0, references);
+            }
+        }
+
+        for name in names {
+            *references.entry(name).or_insert(0) += 1;
+        }
+    }
+}
+
+fn statement_references(statement: &Statement, name: &str) -> usize {
+    let mut references = HashMap::new();
+    count_references(::std::slice::from_ref(statement), &mut references);
+
+    *references.get(name).unwrap_or(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::tests::{parse, writes};
+
+    #[test]
+    fn removes_substituted_temporary() {
+        let ast = parse(
+            "forward
+a FLOAT 1 1 20
+b FLOAT 1 1 20
+t FLOAT
+i INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 1 EXPR INT 10 STMTLIST
+  ASSIGN @ 2 VAR ENTRY t EXPR BINOP * EXPR VAR ENTRY b EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR FLOAT 2.0
+  ASSIGN @ 3 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR VAR ENTRY t
+/STMTLIST
+/STMTLIST
+",
+        );
+        let (substituted, removed) = forward_substitute(&ast).unwrap();
+        assert_eq!(removed, vec![2]);
+
+        let arrays = |ast: &Ast| {
+            writes(ast, &[])
+                .into_iter()
+                .filter(|(name, _)| name == "a")
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(arrays(&substituted), arrays(&ast));
+    }
+}
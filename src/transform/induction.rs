@@ -0,0 +1,306 @@
+use super::*;
+
+/// Replace auxiliary induction variables by closed forms of the loop variable.
+///
+/// An integer scalar `k` qualifies, if its only assignment in a loop body is
+/// an update `k = k + c` directly in the body with `c` invariant in the loop.
+/// Uses of `k` in the body become `k + (var - lower) * c`, counting the
+/// update once more after it, the update is removed and the final value is
+/// assigned after the loop. Inner loops are handled first, so their final
+/// value assignments can be substituted in the outer loops. Loops with a step
+/// have to be normalized first. Also returns the substituted variables with
+/// the labels of their loops.
+pub fn substitute_induction(ast: &Ast) -> io::Result<(Ast, Vec<(String, LoopLabel)>)> {
+    let integers = ast
+        .vardef
+        .iter()
+        .filter(|d| d.dimensions.is_empty())
+        .filter(|d| match d.dtype {
+            DefinitionType::Integer => true,
+            DefinitionType::Real => false,
+        })
+        .map(|d| d.name.to_owned())
+        .collect::<HashSet<_>>();
+
+    let mut substitution = Substitution {
+        integers,
+        label: max_label(&ast.statements.0),
+        substituted: Vec::new(),
+    };
+
+    let ast = Ast {
+        name: ast.name.to_owned(),
+        vardef: ast.vardef.clone(),
+        statements: substitution.stmtlist(&ast.statements.0),
+    };
+    Ok((ast, substitution.substituted))
+}
+
+struct Substitution {
+    integers: HashSet<String>,
+    label: i32,
+    substituted: Vec<(String, LoopLabel)>,
+}
+
+/// Update `k = k + increment` at position `index` of a loop body
+struct Induction {
+    var: String,
+    increment: Expression,
+    index: usize,
+}
+
+impl Substitution {
+    fn stmtlist(&mut self, statements: &[Statement]) -> StatementList {
+        let mut result = Vec::new();
+
+        for s in statements {
+            match s {
+                Statement::Loop(l) => {
+                    let l = Loop {
+                        statements: self.stmtlist(&l.statements.0),
+                        ..l.clone()
+                    };
+                    result.extend(self.substitute_loop(l));
+                }
+                Statement::If(i) => result.push(Statement::If(If {
+                    label: i.label,
+                    expr: i.expr.clone(),
+                    then_branch: self.stmtlist(&i.then_branch.0),
+                    else_branch: self.stmtlist(&i.else_branch.0),
+                })),
+                Statement::Assignment(_) => result.push(s.clone()),
+            }
+        }
+
+        StatementList(result)
+    }
+
+    fn substitute_loop(&mut self, mut l: Loop) -> Vec<Statement> {
+        let mut result = Vec::new();
+        if l.step.is_some() {
+            result.push(Statement::Loop(l));
+            return result;
+        }
+
+        let mut final_values = Vec::new();
+        while let Some(induction) = self.find_induction(&l) {
+            self.substituted.push((induction.var.to_owned(), l.label));
+
+            let before = closed_form(&l, &induction, 0);
+            let after = closed_form(&l, &induction, 1);
+
+            let mut statements = Vec::new();
+            for (index, s) in l.statements.0.iter().enumerate() {
+                if index < induction.index {
+                    statements.push(substitute_statement(s, &induction.var, &before));
+                } else if index > induction.index {
+                    statements.push(substitute_statement(s, &induction.var, &after));
+                }
+            }
+            l.statements = simplify_subscripts(&statements);
+
+            // k = k + max(upper - lower + 1, 0) * c
+            let trips = Affine::from_expr(&l.upper)
+                .and_then(|ub| Some((ub, Affine::from_expr(&l.lower)?)))
+                .and_then(|(ub, lb)| ub.add(&lb.scale(-1)?)?.add(&Affine::constant(1)));
+            let trips = match trips {
+                Some(ref t) if t.is_constant() => Expression::Integer(t.constant.max(0)),
+                t => binop(
+                    OpType::Max,
+                    t.map(|t| t.to_expr()).unwrap_or_else(|| {
+                        let span = binop(
+                            OpType::Minus,
+                            l.upper.clone(),
+                            Expression::Expression(Box::new(l.lower.clone())),
+                        );
+                        binop(OpType::Plus, span, Expression::Integer(1))
+                    }),
+                    Expression::Integer(0),
+                ),
+            };
+            let total = match (&trips, &induction.increment) {
+                (Expression::Integer(t), Expression::Integer(c)) => {
+                    t.checked_mul(*c).map(Expression::Integer)
+                }
+                _ => None,
+            };
+            let total =
+                total.unwrap_or_else(|| binop(OpType::Mul, trips, paren(&induction.increment)));
+
+            self.label += 1;
+            final_values.push(Statement::Assignment(Assign {
+                label: self.label,
+                lhs: Variable {
+                    name: induction.var.to_owned(),
+                    indices: Vec::new(),
+                },
+                rhs: simplify(binop(OpType::Plus, var_expr(&induction.var), total)),
+            }));
+        }
+
+        result.push(Statement::Loop(l));
+        result.extend(final_values);
+        result
+    }
+
+    fn find_induction(&self, l: &Loop) -> Option<Induction> {
+        let mut variant = HashSet::new();
+        variant.insert(l.var.to_owned());
+        variant_names(&l.statements.0, &mut variant);
+
+        for (index, s) in l.statements.0.iter().enumerate() {
+            let a = match s {
+                Statement::Assignment(a) => a,
+                _ => continue,
+            };
+
+            let k = &a.lhs.name;
+            if !a.lhs.indices.is_empty()
+                || !self.integers.contains(k)
+                || assignments(&l.statements.0, k) != 1
+                || uses_var(&l.lower, k)
+                || uses_var(&l.upper, k)
+            {
+                continue;
+            }
+
+            let rhs = match Affine::from_expr(&a.rhs) {
+                Some(rhs) => rhs,
+                None => continue,
+            };
+            let increment = rhs.without(k);
+            if rhs.coefficient(k) != 1 || variant.iter().any(|v| increment.uses(v)) {
+                continue;
+            }
+
+            return Some(Induction {
+                var: k.to_owned(),
+                increment: increment.to_expr(),
+                index,
+            });
+        }
+
+        None
+    }
+}
+
+/// Value of the induction variable in terms of its value before the loop,
+/// `k + (var - lower + updates) * c`
+fn closed_form(l: &Loop, induction: &Induction, updates: i32) -> Expression {
+    let affine = Affine::from_expr(&l.lower)
+        .and_then(|lb| Affine::var(&l.var).add(&lb.scale(-1)?))
+        .and_then(|i| i.add(&Affine::constant(updates)))
+        .and_then(|i| match induction.increment {
+            Expression::Integer(c) => i.scale(c),
+            _ => None,
+        })
+        .and_then(|i| i.add(&Affine::var(&induction.var)));
+
+    match affine {
+        Some(a) => a.to_expr(),
+        None => {
+            let iterations = binop(OpType::Minus, var_expr(&l.var), paren(&l.lower));
+            let iterations = match updates {
+                0 => iterations,
+                u => binop(OpType::Plus, iterations, Expression::Integer(u)),
+            };
+            let offset = binop(
+                OpType::Mul,
+                Expression::Expression(Box::new(iterations)),
+                paren(&induction.increment),
+            );
+            binop(OpType::Plus, var_expr(&induction.var), offset)
+        }
+    }
+}
+
+/// Number of assignments to `name` in the statements
+fn assignments(statements: &[Statement], name: &str) -> usize {
+    statements
+        .iter()
+        .map(|s| match s {
+            Statement::Assignment(a) => (a.lhs.name == name) as usize,
+            Statement::Loop(l) => (l.var == name) as usize + assignments(&l.statements.0, name),
+            Statement::If(i) => {
+                assignments(&i.then_branch.0, name) + assignments(&i.else_branch.0, name)
+            }
+        })
+        .sum()
+}
+
+fn simplify(expr: Expression) -> Expression {
+    Affine::from_expr(&expr)
+        .map(|a| a.to_expr())
+        .unwrap_or(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::tests::{parse, writes};
+
+    /// Loop updating `k` before writing `a(k)`, followed by `after`
+    fn program(after: &str) -> Ast {
+        parse(&format!(
+            "induction
+a FLOAT 1 1 40
+b FLOAT 1 1 20
+i INT
+k INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 1 EXPR VAR ENTRY n STMTLIST
+  ASSIGN @ 2 VAR ENTRY k EXPR BINOP + EXPR VAR ENTRY k EXPR INT 2
+  ASSIGN @ 3 VAR ENTRY a EXPRLIST EXPR VAR ENTRY k /EXPRLIST EXPR VAR ENTRY b EXPRLIST EXPR VAR ENTRY i /EXPRLIST
+/STMTLIST
+{}
+/STMTLIST
+",
+            after
+        ))
+    }
+
+    /// Elements of `a` written for `n` iterations
+    fn array_writes(ast: &Ast, n: i32) -> Vec<(String, Vec<i32>)> {
+        writes(ast, &[("n", n), ("k", 1)])
+            .into_iter()
+            .filter(|(name, _)| name == "a")
+            .collect()
+    }
+
+    #[test]
+    fn removes_update() {
+        let ast = program("");
+        let (substituted, inductions) = substitute_induction(&ast).unwrap();
+        assert_eq!(inductions, vec![("k".to_owned(), 1)]);
+
+        match &substituted.statements.0[0] {
+            Statement::Loop(l) => assert_eq!(l.statements.0.len(), 1),
+            s => panic!("Expected a loop, found {:?}", s),
+        }
+        for n in 0..=10 {
+            assert_eq!(
+                array_writes(&substituted, n),
+                array_writes(&ast, n),
+                "n = {}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn assigns_final_value_read_after_loop() {
+        let ast = program("ASSIGN @ 4 VAR ENTRY a EXPRLIST EXPR VAR ENTRY k /EXPRLIST EXPR INT 0");
+        let (substituted, _) = substitute_induction(&ast).unwrap();
+        assert_eq!(substituted.statements.0.len(), 3);
+
+        for n in 0..=10 {
+            assert_eq!(
+                array_writes(&substituted, n),
+                array_writes(&ast, n),
+                "n = {}",
+                n
+            );
+        }
+    }
+}
@@ -0,0 +1,596 @@
+pub mod affine;
+pub mod forward;
+pub mod induction;
+pub mod normalize;
+pub mod peel;
+pub mod reverse;
+pub mod simplify;
+pub mod split;
+pub mod tiling;
+pub mod unroll_jam;
+
+use dependencies::{DependencyType, Level, LevelDependency, LoopLabel, Statement as Label};
+use ir::*;
+use petgraph::Graph;
+use std::{collections::HashSet, io};
+
+pub use self::affine::Affine;
+pub use self::forward::forward_substitute;
+pub use self::induction::substitute_induction;
+pub use self::normalize::normalize;
+pub use self::peel::peel;
+pub use self::reverse::reverse;
+pub use self::simplify::simplify;
+pub use self::split::split;
+pub use self::tiling::tile;
+pub use self::unroll_jam::unroll_and_jam;
+
+/// Largest label used by any statement or loop in the list
+pub fn max_label(statements: &[Statement]) -> i32 {
+    let mut max = 0;
+
+    for s in statements {
+        let label = match s {
+            Statement::Assignment(a) => a.label,
+            Statement::Loop(l) => l.label.max(max_label(&l.statements.0)),
+            Statement::If(i) => i
+                .label
+                .max(max_label(&i.then_branch.0))
+                .max(max_label(&i.else_branch.0)),
+        };
+        max = max.max(label);
+    }
+
+    max
+}
+
+/// Collect all variable names declared or used as loop variable
+pub fn used_names(ast: &Ast) -> HashSet<String> {
+    let mut names = ast
+        .vardef
+        .iter()
+        .map(|d| d.name.to_owned())
+        .collect::<HashSet<_>>();
+    loop_vars(&ast.statements.0, &mut names);
+
+    names
+}
+
+/// Collect the names of all loop variables
+pub fn loop_vars(statements: &[Statement], names: &mut HashSet<String>) {
+    for s in statements {
+        match s {
+            Statement::Loop(l) => {
+                names.insert(l.var.to_owned());
+                loop_vars(&l.statements.0, names);
+            }
+            Statement::If(i) => {
+                loop_vars(&i.then_branch.0, names);
+                loop_vars(&i.else_branch.0, names);
+            }
+            Statement::Assignment(_) => (),
+        }
+    }
+}
+
+/// Collect names referenced outside of the loops binding them
+pub fn unbound_names(
+    statements: &[Statement],
+    bound: &mut Vec<String>,
+    names: &mut HashSet<String>,
+) {
+    fn expression(expr: &Expression, bound: &[String], names: &mut HashSet<String>) {
+        match expr {
+            Expression::Integer(_) | Expression::Real(_) => (),
+            Expression::Variable(v) => {
+                if !bound.contains(&v.name) {
+                    names.insert(v.name.to_owned());
+                }
+                for index in v.indices.iter() {
+                    expression(index, bound, names);
+                }
+            }
+            Expression::UnOp(op) => expression(&op.right, bound, names),
+            Expression::BinOp(op) => {
+                expression(&op.left, bound, names);
+                expression(&op.right, bound, names);
+            }
+            Expression::Expression(expr) => expression(expr, bound, names),
+        }
+    }
+
+    for s in statements {
+        match s {
+            Statement::Assignment(a) => {
+                expression(&Expression::Variable(a.lhs.clone()), bound, names);
+                expression(&a.rhs, bound, names);
+            }
+            Statement::Loop(l) => {
+                expression(&l.lower, bound, names);
+                expression(&l.upper, bound, names);
+                if let Some(step) = &l.step {
+                    expression(step, bound, names);
+                }
+                bound.push(l.var.to_owned());
+                unbound_names(&l.statements.0, bound, names);
+                bound.pop();
+            }
+            Statement::If(i) => {
+                expression(&i.expr, bound, names);
+                unbound_names(&i.then_branch.0, bound, names);
+                unbound_names(&i.else_branch.0, bound, names);
+            }
+        }
+    }
+}
+
+/// Derive a variable name from `base` which is not used in the program yet
+pub fn fresh_name(base: &str, names: &mut HashSet<String>) -> String {
+    let mut name = base.to_owned();
+    let mut counter = 1;
+    while names.contains(&name) {
+        counter += 1;
+        name = format!("{}{}", base, counter);
+    }
+    names.insert(name.to_owned());
+
+    name
+}
+
+/// Check whether an expression references the variable `name`
+pub fn uses_var(expr: &Expression, name: &str) -> bool {
+    match expr {
+        Expression::Integer(_) | Expression::Real(_) => false,
+        Expression::Variable(var) => {
+            var.name == name || var.indices.iter().any(|e| uses_var(e, name))
+        }
+        Expression::UnOp(op) => uses_var(&op.right, name),
+        Expression::BinOp(op) => uses_var(&op.left, name) || uses_var(&op.right, name),
+        Expression::Expression(expr) => uses_var(expr, name),
+    }
+}
+
+/// Consecutive iterations of the loop over `var` access consecutive elements
+/// of the first dimension
+pub fn contiguous(access: &Variable, var: &str) -> bool {
+    access
+        .indices
+        .iter()
+        .enumerate()
+        .all(|(i, index)| match (i, Affine::from_expr(index)) {
+            (0, Some(a)) => a.coefficient(var) == 1,
+            (0, None) => false,
+            (_, Some(a)) => !a.uses(var),
+            (_, None) => !uses_var(index, var),
+        })
+}
+
+/// Labels of all assignments nested in the statement list
+pub fn statement_labels(statements: &[Statement], labels: &mut HashSet<Label>) {
+    for s in statements {
+        match s {
+            Statement::Assignment(a) => {
+                labels.insert(a.label);
+            }
+            Statement::Loop(l) => statement_labels(&l.statements.0, labels),
+            Statement::If(i) => {
+                statement_labels(&i.then_branch.0, labels);
+                statement_labels(&i.else_branch.0, labels);
+            }
+        }
+    }
+}
+
+/// Levels of all dependencies between the given statements, except for input
+/// dependencies which constrain no order
+pub fn dependency_levels(
+    graph: &Graph<Label, Vec<LevelDependency>>,
+    statements: &HashSet<Label>,
+) -> HashSet<Level> {
+    let mut levels = HashSet::new();
+
+    for edge in graph.raw_edges() {
+        let (s1, s2) = (graph[edge.source()], graph[edge.target()]);
+        if statements.contains(&s1) && statements.contains(&s2) {
+            for LevelDependency(level, dep, _) in edge.weight.iter() {
+                if *dep != DependencyType::Input {
+                    levels.insert(*level);
+                }
+            }
+        }
+    }
+
+    levels
+}
+
+/// Replace the loop with the given label by the statements built by `rewrite`,
+/// which receives the loop and its nesting depth
+pub fn rewrite_loop<F>(ast: &Ast, label: LoopLabel, mut rewrite: F) -> io::Result<Ast>
+where
+    F: FnMut(&Loop, Level) -> io::Result<Vec<Statement>>,
+{
+    let mut found = false;
+    let statements = rewrite_stmtlist(&ast.statements.0, label, 0, &mut rewrite, &mut found)?;
+
+    if !found {
+        return Err(io::Error::other(format!(
+            "Could not find loop with label {}",
+            label
+        )));
+    }
+
+    Ok(Ast {
+        name: ast.name.to_owned(),
+        vardef: ast.vardef.clone(),
+        statements,
+    })
+}
+
+fn rewrite_stmtlist<F>(
+    statements: &[Statement],
+    label: LoopLabel,
+    depth: Level,
+    rewrite: &mut F,
+    found: &mut bool,
+) -> io::Result<StatementList>
+where
+    F: FnMut(&Loop, Level) -> io::Result<Vec<Statement>>,
+{
+    let mut result = Vec::new();
+
+    for s in statements {
+        match s {
+            Statement::Loop(l) if l.label == label => {
+                *found = true;
+                result.extend(rewrite(l, depth)?);
+            }
+            Statement::Loop(l) => result.push(Statement::Loop(Loop {
+                statements: rewrite_stmtlist(&l.statements.0, label, depth + 1, rewrite, found)?,
+                ..l.clone()
+            })),
+            Statement::If(i) => result.push(Statement::If(If {
+                label: i.label,
+                expr: i.expr.clone(),
+                then_branch: rewrite_stmtlist(&i.then_branch.0, label, depth, rewrite, found)?,
+                else_branch: rewrite_stmtlist(&i.else_branch.0, label, depth, rewrite, found)?,
+            })),
+            Statement::Assignment(_) => result.push(s.clone()),
+        }
+    }
+
+    Ok(StatementList(result))
+}
+
+/// Copy the statements, giving every statement and loop a fresh label
+pub fn relabel(statements: &[Statement], label: &mut i32) -> StatementList {
+    StatementList(
+        statements
+            .iter()
+            .map(|s| {
+                *label += 1;
+                match s {
+                    Statement::Assignment(a) => Statement::Assignment(Assign {
+                        label: *label,
+                        ..a.clone()
+                    }),
+                    Statement::Loop(l) => Statement::Loop(Loop {
+                        label: *label,
+                        statements: relabel(&l.statements.0, label),
+                        ..l.clone()
+                    }),
+                    Statement::If(i) => Statement::If(If {
+                        label: *label,
+                        expr: i.expr.clone(),
+                        then_branch: relabel(&i.then_branch.0, label),
+                        else_branch: relabel(&i.else_branch.0, label),
+                    }),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Replace every use of the scalar `var` in the statements by `replacement`
+pub fn substitute_stmtlist(
+    statements: &[Statement],
+    var: &str,
+    replacement: &Expression,
+) -> StatementList {
+    StatementList(
+        statements
+            .iter()
+            .map(|s| substitute_statement(s, var, replacement))
+            .collect(),
+    )
+}
+
+pub fn substitute_statement(
+    statement: &Statement,
+    var: &str,
+    replacement: &Expression,
+) -> Statement {
+    match statement {
+        Statement::Assignment(a) => Statement::Assignment(Assign {
+            label: a.label,
+            lhs: substitute_variable(&a.lhs, var, replacement),
+            rhs: substitute(&a.rhs, var, replacement),
+        }),
+        Statement::Loop(l) => Statement::Loop(Loop {
+            label: l.label,
+            var: l.var.to_owned(),
+            lower: substitute(&l.lower, var, replacement),
+            upper: substitute(&l.upper, var, replacement),
+            step: l.step.as_ref().map(|s| substitute(s, var, replacement)),
+            statements: substitute_stmtlist(&l.statements.0, var, replacement),
+        }),
+        Statement::If(i) => Statement::If(If {
+            label: i.label,
+            expr: substitute(&i.expr, var, replacement),
+            then_branch: substitute_stmtlist(&i.then_branch.0, var, replacement),
+            else_branch: substitute_stmtlist(&i.else_branch.0, var, replacement),
+        }),
+    }
+}
+
+fn substitute_variable(var: &Variable, name: &str, replacement: &Expression) -> Variable {
+    Variable {
+        name: var.name.to_owned(),
+        indices: var
+            .indices
+            .iter()
+            .map(|e| substitute(e, name, replacement))
+            .collect(),
+    }
+}
+
+/// Replace every use of the scalar `var` in the expression by `replacement`
+pub fn substitute(expr: &Expression, var: &str, replacement: &Expression) -> Expression {
+    match expr {
+        Expression::Variable(v) if v.name == var && v.indices.is_empty() => match replacement {
+            // Keep the replaced operation together when printed
+            Expression::BinOp(_) | Expression::UnOp(_) => {
+                Expression::Expression(Box::new(replacement.clone()))
+            }
+            _ => replacement.clone(),
+        },
+        Expression::Variable(v) => Expression::Variable(substitute_variable(v, var, replacement)),
+        Expression::Integer(_) | Expression::Real(_) => expr.clone(),
+        Expression::UnOp(op) => Expression::UnOp(Box::new(UnOp {
+            op: op.op.clone(),
+            right: substitute(&op.right, var, replacement),
+        })),
+        Expression::BinOp(op) => Expression::BinOp(Box::new(BinOp {
+            op: op.op.clone(),
+            left: substitute(&op.left, var, replacement),
+            right: substitute(&op.right, var, replacement),
+        })),
+        Expression::Expression(e) => {
+            Expression::Expression(Box::new(substitute(e, var, replacement)))
+        }
+    }
+}
+
+pub fn var_expr(name: &str) -> Expression {
+    Expression::Variable(Variable {
+        name: name.to_owned(),
+        indices: Vec::new(),
+    })
+}
+
+pub fn binop(op: OpType, left: Expression, right: Expression) -> Expression {
+    Expression::BinOp(Box::new(BinOp { op, left, right }))
+}
+
+/// Keep operations together when used as an operand
+pub fn paren(expr: &Expression) -> Expression {
+    match expr {
+        Expression::BinOp(_) | Expression::UnOp(_) => {
+            Expression::Expression(Box::new(expr.clone()))
+        }
+        _ => expr.clone(),
+    }
+}
+
+/// Read or write of a variable
+pub struct Access<'a> {
+    pub var: &'a Variable,
+    pub write: bool,
+}
+
+/// Collect all variable accesses of the statements in program order
+pub fn collect_accesses<'a>(statements: &'a [Statement], accesses: &mut Vec<Access<'a>>) {
+    for s in statements {
+        match s {
+            Statement::Assignment(a) => {
+                accesses.push(Access {
+                    var: &a.lhs,
+                    write: true,
+                });
+                for index in a.lhs.indices.iter() {
+                    collect_reads(index, accesses);
+                }
+                collect_reads(&a.rhs, accesses);
+            }
+            Statement::Loop(l) => collect_accesses(&l.statements.0, accesses),
+            Statement::If(i) => {
+                collect_reads(&i.expr, accesses);
+                collect_accesses(&i.then_branch.0, accesses);
+                collect_accesses(&i.else_branch.0, accesses);
+            }
+        }
+    }
+}
+
+pub fn collect_reads<'a>(expr: &'a Expression, accesses: &mut Vec<Access<'a>>) {
+    match expr {
+        Expression::Integer(_) | Expression::Real(_) => (),
+        Expression::Variable(var) => {
+            accesses.push(Access { var, write: false });
+            for index in var.indices.iter() {
+                collect_reads(index, accesses);
+            }
+        }
+        Expression::UnOp(op) => collect_reads(&op.right, accesses),
+        Expression::BinOp(op) => {
+            collect_reads(&op.left, accesses);
+            collect_reads(&op.right, accesses);
+        }
+        Expression::Expression(expr) => collect_reads(expr, accesses),
+    }
+}
+
+/// Inner loop variables and scalars assigned in the statements
+pub fn variant_names(statements: &[Statement], names: &mut HashSet<String>) {
+    for s in statements {
+        match s {
+            Statement::Assignment(a) => {
+                if a.lhs.indices.is_empty() {
+                    names.insert(a.lhs.name.to_owned());
+                }
+            }
+            Statement::Loop(l) => {
+                names.insert(l.var.to_owned());
+                variant_names(&l.statements.0, names);
+            }
+            Statement::If(i) => {
+                variant_names(&i.then_branch.0, names);
+                variant_names(&i.else_branch.0, names);
+            }
+        }
+    }
+}
+
+/// Rewrite affine subscripts and loop bounds into their canonical form, e.g.
+/// `(i + 1) - 1` becomes `i`
+pub fn simplify_subscripts(statements: &[Statement]) -> StatementList {
+    StatementList(
+        statements
+            .iter()
+            .map(|s| match s {
+                Statement::Assignment(a) => Statement::Assignment(Assign {
+                    label: a.label,
+                    lhs: simplify_variable(&a.lhs),
+                    rhs: simplify_indices(&a.rhs),
+                }),
+                Statement::Loop(l) => Statement::Loop(Loop {
+                    label: l.label,
+                    var: l.var.to_owned(),
+                    lower: simplify(&l.lower),
+                    upper: simplify(&l.upper),
+                    step: l.step.as_ref().map(simplify),
+                    statements: simplify_subscripts(&l.statements.0),
+                }),
+                Statement::If(i) => Statement::If(If {
+                    label: i.label,
+                    expr: simplify_indices(&i.expr),
+                    then_branch: simplify_subscripts(&i.then_branch.0),
+                    else_branch: simplify_subscripts(&i.else_branch.0),
+                }),
+            })
+            .collect(),
+    )
+}
+
+fn simplify_variable(var: &Variable) -> Variable {
+    Variable {
+        name: var.name.to_owned(),
+        indices: var.indices.iter().map(simplify).collect(),
+    }
+}
+
+fn simplify_indices(expr: &Expression) -> Expression {
+    match expr {
+        Expression::Variable(var) => Expression::Variable(simplify_variable(var)),
+        Expression::Integer(_) | Expression::Real(_) => expr.clone(),
+        Expression::UnOp(op) => Expression::UnOp(Box::new(UnOp {
+            op: op.op.clone(),
+            right: simplify_indices(&op.right),
+        })),
+        Expression::BinOp(op) => Expression::BinOp(Box::new(BinOp {
+            op: op.op.clone(),
+            left: simplify_indices(&op.left),
+            right: simplify_indices(&op.right),
+        })),
+        Expression::Expression(expr) => Expression::Expression(Box::new(simplify_indices(expr))),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Program in the format of `.ast` files, fixed up for the parser like
+    /// in the binaries
+    pub fn parse(text: &str) -> Ast {
+        let text = format!("{} $", text).replace('\n', " ");
+        match parse_ast(&text) {
+            Ok((_, ast)) => ast,
+            Err(e) => panic!("Could not parse program: {}", e),
+        }
+    }
+
+    /// Value of an integer expression
+    pub fn eval(expr: &Expression, values: &HashMap<String, i32>) -> Option<i32> {
+        let expr = values.iter().fold(expr.clone(), |expr, (name, value)| {
+            substitute(&expr, name, &Expression::Integer(*value))
+        });
+
+        match fold_expr(&expr, 0) {
+            Ok(Expression::Integer(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Elements written by running the program with the given scalars, in
+    /// the order of the writes
+    pub fn writes(ast: &Ast, scalars: &[(&str, i32)]) -> Vec<(String, Vec<i32>)> {
+        let mut values = scalars
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        let mut writes = Vec::new();
+        run(&ast.statements.0, &mut values, &mut writes);
+
+        writes
+    }
+
+    fn run(
+        statements: &[Statement],
+        values: &mut HashMap<String, i32>,
+        writes: &mut Vec<(String, Vec<i32>)>,
+    ) {
+        let value = |expr: &Expression, values: &HashMap<String, i32>| {
+            eval(expr, values).unwrap_or_else(|| panic!("Unknown value of {:?}", expr))
+        };
+
+        for s in statements {
+            match s {
+                Statement::Loop(l) => {
+                    let (lower, upper) = (value(&l.lower, values), value(&l.upper, values));
+                    let step = l.step.as_ref().map_or(1, |s| value(s, values));
+
+                    let mut i = lower;
+                    while (step > 0 && i <= upper) || (step < 0 && i >= upper) {
+                        values.insert(l.var.to_owned(), i);
+                        run(&l.statements.0, values, writes);
+                        i += step;
+                    }
+                }
+                Statement::If(i) => match value(&i.expr, values) {
+                    0 => run(&i.else_branch.0, values, writes),
+                    _ => run(&i.then_branch.0, values, writes),
+                },
+                Statement::Assignment(a) => {
+                    let indices = a.lhs.indices.iter().map(|e| value(e, values)).collect();
+                    writes.push((a.lhs.name.to_owned(), indices));
+                    if a.lhs.indices.is_empty() {
+                        if let Some(v) = eval(&a.rhs, values) {
+                            values.insert(a.lhs.name.to_owned(), v);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use super::*;
+
+/// Rewrite every loop to run from `base` (usually 1 or 0) with step 1.
+///
+/// The loop variable keeps its name, uses in the body are replaced by
+/// `lower + (var - base) * step` and the upper bound becomes the iteration
+/// count shifted by `base`. Its value after the loop therefore differs from
+/// the original program. Loops whose bounds are modified in their body are
+/// left untouched.
+pub fn normalize(ast: &Ast, base: i32) -> io::Result<Ast> {
+    Ok(Ast {
+        name: ast.name.to_owned(),
+        vardef: ast.vardef.clone(),
+        statements: normalize_stmtlist(&ast.statements.0, base)?,
+    })
+}
+
+fn normalize_stmtlist(statements: &[Statement], base: i32) -> io::Result<StatementList> {
+    let mut result = Vec::new();
+
+    for s in statements {
+        result.push(match s {
+            Statement::Loop(l) => Statement::Loop(normalize_loop(l, base)?),
+            Statement::If(i) => Statement::If(If {
+                label: i.label,
+                expr: i.expr.clone(),
+                then_branch: normalize_stmtlist(&i.then_branch.0, base)?,
+                else_branch: normalize_stmtlist(&i.else_branch.0, base)?,
+            }),
+            Statement::Assignment(_) => s.clone(),
+        });
+    }
+
+    Ok(StatementList(result))
+}
+
+fn normalize_loop(l: &Loop, base: i32) -> io::Result<Loop> {
+    let step = l
+        .step
+        .as_ref()
+        .map(|step| fold_expr(step, l.label))
+        .transpose()?;
+    if let Some(Expression::Integer(0)) = step {
+        return Err(io::Error::other(format!(
+            "Loop {} has a step of zero",
+            l.label
+        )));
+    }
+
+    let normalized = match (fold_expr(&l.lower, l.label)?, &step) {
+        (Expression::Integer(lb), None)
+        | (Expression::Integer(lb), Some(Expression::Integer(1))) => lb == base,
+        _ => false,
+    };
+
+    let mut assigned = HashSet::new();
+    variant_names(&l.statements.0, &mut assigned);
+    let bound_names = assigned.iter().any(|v| {
+        uses_var(&l.lower, v)
+            || uses_var(&l.upper, v)
+            || step.as_ref().is_some_and(|s| uses_var(s, v))
+    });
+
+    if normalized || bound_names {
+        if bound_names {
+            eprintln!(
+                "Bounds of loop {} are modified in its body, not normalizing",
+                l.label
+            );
+        }
+
+        return Ok(Loop {
+            statements: normalize_stmtlist(&l.statements.0, base)?,
+            ..l.clone()
+        });
+    }
+
+    let step = step.unwrap_or(Expression::Integer(1));
+    let replacement = iteration_value(&l.lower, &step, &l.var, base);
+    let statements = substitute_stmtlist(&l.statements.0, &l.var, &replacement);
+    let statements = simplify_subscripts(&statements.0);
+
+    Ok(Loop {
+        label: l.label,
+        var: l.var.to_owned(),
+        lower: Expression::Integer(base),
+        upper: last_iteration(&l.lower, &l.upper, &step, base),
+        step: None,
+        statements: normalize_stmtlist(&statements.0, base)?,
+    })
+}
+
+/// Value of the original loop variable, `lower + (var - base) * step`
+fn iteration_value(lower: &Expression, step: &Expression, var: &str, base: i32) -> Expression {
+    let affine = match (Affine::from_expr(lower), step) {
+        (Some(lb), Expression::Integer(s)) => Affine::var(var)
+            .add(&Affine::constant(-base))
+            .and_then(|i| i.scale(*s))
+            .and_then(|i| i.add(&lb)),
+        _ => None,
+    };
+
+    affine.map(|a| a.to_expr()).unwrap_or_else(|| {
+        let offset = binop(OpType::Minus, var_expr(var), Expression::Integer(base));
+        let offset = binop(
+            OpType::Mul,
+            Expression::Expression(Box::new(offset)),
+            paren(step),
+        );
+        binop(OpType::Plus, lower.clone(), offset)
+    })
+}
+
+/// Upper bound of the normalized loop, `base - 1 + (upper - lower + step) / step`
+fn last_iteration(
+    lower: &Expression,
+    upper: &Expression,
+    step: &Expression,
+    base: i32,
+) -> Expression {
+    // With a unit step the bound simplifies to `upper - lower + base`
+    let span = match (Affine::from_expr(lower), Affine::from_expr(upper), step) {
+        (Some(lb), Some(ub), Expression::Integer(s)) => lb
+            .scale(-1)
+            .and_then(|lb| ub.add(&lb))
+            .and_then(|span| span.add(&Affine::constant(if *s == 1 { base } else { *s }))),
+        _ => None,
+    };
+
+    match (span, step) {
+        (Some(span), Expression::Integer(1)) => span.to_expr(),
+        (Some(ref span), Expression::Integer(s)) if span.is_constant() => {
+            Expression::Integer(base - 1 + span.constant / s)
+        }
+        (span, _) => {
+            let span = match span {
+                Some(span) => span.to_expr(),
+                None => {
+                    let span = binop(OpType::Minus, upper.clone(), paren(lower));
+                    binop(OpType::Plus, span, step.clone())
+                }
+            };
+            let count = binop(
+                OpType::Div,
+                Expression::Expression(Box::new(span)),
+                paren(step),
+            );
+
+            match base - 1 {
+                0 => count,
+                b if b < 0 => binop(OpType::Minus, count, Expression::Integer(-b)),
+                b => binop(OpType::Plus, count, Expression::Integer(b)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::tests::{parse, writes};
+
+    const LOOPS: &str = "loops
+a FLOAT 1 1 40
+i INT
+j INT
+k INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 3 EXPR VAR ENTRY n STEP EXPR INT 2 STMTLIST
+  ASSIGN @ 2 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR INT 0
+/STMTLIST
+FOR @ 3 ENTRY j EXPR VAR ENTRY n EXPR UNOP - EXPR INT 2 STEP EXPR UNOP - EXPR INT 3 STMTLIST
+  ASSIGN @ 4 VAR ENTRY a EXPRLIST EXPR VAR ENTRY j /EXPRLIST EXPR INT 1
+/STMTLIST
+FOR @ 5 ENTRY k EXPR INT 2 EXPR INT 11 STEP EXPR INT 4 STMTLIST
+  ASSIGN @ 6 VAR ENTRY a EXPRLIST EXPR VAR ENTRY k /EXPRLIST EXPR INT 2
+/STMTLIST
+/STMTLIST
+";
+
+    #[test]
+    fn normalized_loops_keep_trip_count() {
+        let ast = parse(LOOPS);
+
+        for base in -1..=1 {
+            let normalized = normalize(&ast, base).unwrap();
+            for n in -4..=12 {
+                assert_eq!(
+                    writes(&normalized, &[("n", n)]),
+                    writes(&ast, &[("n", n)]),
+                    "base {}, n = {}",
+                    base,
+                    n
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn zero_step_is_an_error() {
+        let ast = parse(&LOOPS.replace("STEP EXPR INT 2", "STEP EXPR INT 0"));
+        assert!(normalize(&ast, 1).is_err());
+    }
+}
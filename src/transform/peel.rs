@@ -0,0 +1,179 @@
+use super::*;
+
+/// Peel `front` iterations off the start and `back` iterations off the end of
+/// the loop with the given label.
+///
+/// The peeled iterations are emitted as copies of the loop body with the loop
+/// variable replaced and their subscripts simplified, the remaining iterations
+/// stay in the loop. Loops with constant bounds must execute at least
+/// `front + back` iterations. Otherwise the peeled program is placed under an
+/// if that checks the number of iterations, with the original loop as the
+/// fallback. Peeled copies receive fresh labels.
+pub fn peel(ast: &Ast, label: LoopLabel, front: i32, back: i32) -> io::Result<Ast> {
+    if front < 0 || back < 0 {
+        return Err(io::Error::other(
+            "Number of peeled iterations must not be negative",
+        ));
+    }
+
+    let mut next_label = max_label(&ast.statements.0);
+
+    rewrite_loop(ast, label, |l, _| {
+        if l.step.is_some() {
+            return Err(io::Error::other(format!(
+                "Loop {} has a step and cannot be peeled",
+                l.label
+            )));
+        }
+
+        let peeled = match front.checked_add(back) {
+            Some(peeled) => peeled,
+            None => return Err(too_few_iterations(l, front, back)),
+        };
+
+        let constant = match (fold_expr(&l.lower, l.label)?, fold_expr(&l.upper, l.label)?) {
+            (Expression::Integer(lb), Expression::Integer(ub)) => {
+                match ub.checked_sub(lb).and_then(|d| d.checked_add(1)) {
+                    Some(iterations) if iterations >= peeled => true,
+                    _ => return Err(too_few_iterations(l, front, back)),
+                }
+            }
+            _ => false,
+        };
+
+        let mut result = Vec::new();
+        for k in 0..front {
+            let iteration = offset(&l.lower, k);
+            result.extend(peeled_iteration(l, &iteration, &mut next_label));
+        }
+
+        result.push(Statement::Loop(Loop {
+            lower: offset(&l.lower, front),
+            upper: offset(&l.upper, -back),
+            ..l.clone()
+        }));
+
+        for k in (0..back).rev() {
+            let iteration = offset(&l.upper, -k);
+            result.extend(peeled_iteration(l, &iteration, &mut next_label));
+        }
+
+        if constant {
+            return Ok(result);
+        }
+
+        // Number of iterations ub - lb + 1 >= front + back
+        let iterations = binop(
+            OpType::Plus,
+            binop(OpType::Minus, l.upper.clone(), paren(&l.lower)),
+            Expression::Integer(1),
+        );
+        let expr = binop(
+            OpType::GreaterEqual,
+            simplify(&iterations),
+            Expression::Integer(peeled),
+        );
+
+        next_label += 1;
+        let label = next_label;
+        let fallback = relabel(&[Statement::Loop(l.clone())], &mut next_label);
+
+        Ok(vec![Statement::If(If {
+            label,
+            expr,
+            then_branch: StatementList(result),
+            else_branch: fallback,
+        })])
+    })
+}
+
+fn too_few_iterations(l: &Loop, front: i32, back: i32) -> io::Error {
+    io::Error::other(format!(
+        "Loop {} has less than {} + {} iterations",
+        l.label, front, back
+    ))
+}
+
+fn offset(expr: &Expression, k: i32) -> Expression {
+    let expr = match k {
+        0 => return expr.clone(),
+        k if k < 0 => binop(OpType::Minus, expr.clone(), Expression::Integer(-k)),
+        k => binop(OpType::Plus, expr.clone(), Expression::Integer(k)),
+    };
+
+    simplify(&expr)
+}
+
+fn peeled_iteration(l: &Loop, iteration: &Expression, label: &mut i32) -> Vec<Statement> {
+    let body = substitute_stmtlist(&l.statements.0, &l.var, iteration);
+    let body = relabel(&body.0, label);
+
+    simplify_subscripts(&body.0).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::tests::{parse, writes};
+
+    fn program(lower: &str, upper: &str) -> Ast {
+        parse(&format!(
+            "loop
+a FLOAT 1 1 20
+i INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY i {} {} STMTLIST
+  ASSIGN @ 2 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR INT 0
+/STMTLIST
+/STMTLIST
+",
+            lower, upper
+        ))
+    }
+
+    #[test]
+    fn peels_up_to_the_trip_count() {
+        let ast = program("EXPR INT 3", "EXPR INT 10");
+
+        for (front, back) in [(0, 0), (2, 1), (0, 3), (5, 3), (8, 0)].iter() {
+            let peeled = peel(&ast, 1, *front, *back).unwrap();
+            assert_eq!(
+                writes(&peeled, &[]),
+                writes(&ast, &[]),
+                "{} + {}",
+                front,
+                back
+            );
+        }
+
+        assert!(peel(&ast, 1, 5, 4).is_err());
+        assert!(peel(&ast, 1, 9, 0).is_err());
+        assert!(peel(&ast, 1, i32::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn symbolic_bounds_are_checked_at_run_time() {
+        let ast = program("EXPR INT 2", "EXPR VAR ENTRY n");
+
+        for (front, back) in [(1, 0), (2, 1), (0, 3), (4, 4)].iter() {
+            let peeled = peel(&ast, 1, *front, *back).unwrap();
+            for n in 0..=12 {
+                assert_eq!(
+                    writes(&peeled, &[("n", n)]),
+                    writes(&ast, &[("n", n)]),
+                    "{} + {} with n = {}",
+                    front,
+                    back,
+                    n
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_bounds_do_not_overflow() {
+        let ast = program("EXPR UNOP - EXPR INT 2147483647", "EXPR INT 2147483647");
+        assert!(peel(&ast, 1, 1, 1).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+use super::*;
+
+/// Reverse loops in which all dependence distances between the accesses of
+/// the body point backwards, turning their `>` directions into `<`.
+///
+/// Directions are derived statically from affine subscripts with constant
+/// distances. Reversal runs the iterations of the loop in the opposite
+/// order, so every dependency it carries according to the dependency graph
+/// has to be reversible: input dependencies, and the dependencies of a
+/// reduction `s = s op e` on its scalar, whose order does not matter. Loops
+/// with a step have to be normalized first.
+pub fn reverse(ast: &Ast, graph: &Graph<Label, Vec<LevelDependency>>) -> io::Result<Ast> {
+    Ok(Ast {
+        name: ast.name.to_owned(),
+        vardef: ast.vardef.clone(),
+        statements: reverse_stmtlist(&ast.statements.0, graph, 0),
+    })
+}
+
+#[derive(PartialEq)]
+enum Direction {
+    Forward,
+    Backward,
+    Unknown,
+}
+
+fn reverse_stmtlist(
+    statements: &[Statement],
+    graph: &Graph<Label, Vec<LevelDependency>>,
+    depth: Level,
+) -> StatementList {
+    let mut result = Vec::new();
+
+    for s in statements {
+        result.push(match s {
+            Statement::Loop(l) => {
+                let l = reverse_loop(l, graph, depth);
+                Statement::Loop(Loop {
+                    statements: reverse_stmtlist(&l.statements.0, graph, depth + 1),
+                    ..l
+                })
+            }
+            Statement::If(i) => Statement::If(If {
+                label: i.label,
+                expr: i.expr.clone(),
+                then_branch: reverse_stmtlist(&i.then_branch.0, graph, depth),
+                else_branch: reverse_stmtlist(&i.else_branch.0, graph, depth),
+            }),
+            Statement::Assignment(_) => s.clone(),
+        });
+    }
+
+    StatementList(result)
+}
+
+fn reverse_loop(l: &Loop, graph: &Graph<Label, Vec<LevelDependency>>, depth: Level) -> Loop {
+    let mut labels = HashSet::new();
+    statement_labels(&l.statements.0, &mut labels);
+    if l.step.is_some() || !carried_reversible(l, graph, &labels, depth + 1) {
+        return l.clone();
+    }
+
+    let mut variant = HashSet::new();
+    variant.insert(l.var.to_owned());
+    variant_names(&l.statements.0, &mut variant);
+    if variant
+        .iter()
+        .any(|v| uses_var(&l.lower, v) || uses_var(&l.upper, v))
+    {
+        return l.clone();
+    }
+
+    let directions = directions(l, &variant);
+    if directions.is_empty() || directions.iter().any(|d| *d != Direction::Backward) {
+        return l.clone();
+    }
+
+    // Iterate from the upper bound down, i.e. var becomes lower + upper - var
+    let affine = match (Affine::from_expr(&l.lower), Affine::from_expr(&l.upper)) {
+        (Some(lb), Some(ub)) => lb
+            .add(&ub)
+            .and_then(|b| b.add(&Affine::var(&l.var).scale(-1)?)),
+        _ => None,
+    };
+    let replacement = affine.map(|a| a.to_expr()).unwrap_or_else(|| {
+        let bounds = binop(
+            OpType::Plus,
+            l.lower.clone(),
+            Expression::Expression(Box::new(l.upper.clone())),
+        );
+        binop(OpType::Minus, bounds, var_expr(&l.var))
+    });
+
+    let statements = substitute_stmtlist(&l.statements.0, &l.var, &replacement);
+
+    Loop {
+        statements: simplify_subscripts(&statements.0),
+        ..l.clone()
+    }
+}
+
+/// Whether all dependencies carried at `level` between the statements of the
+/// loop stay valid when its iterations run in the opposite order
+fn carried_reversible(
+    l: &Loop,
+    graph: &Graph<Label, Vec<LevelDependency>>,
+    labels: &HashSet<Label>,
+    level: Level,
+) -> bool {
+    let mut reductions = HashSet::new();
+    find_reductions(&l.statements.0, &mut reductions);
+
+    graph.raw_edges().iter().all(|edge| {
+        let (s1, s2) = (graph[edge.source()], graph[edge.target()]);
+        if !labels.contains(&s1) || !labels.contains(&s2) {
+            return true;
+        }
+
+        edge.weight.iter().all(|LevelDependency(l, dep, _)| {
+            // A reduction only writes its scalar, so its dependencies on
+            // itself are on the scalar as well
+            *l != level || *dep == DependencyType::Input || (s1 == s2 && reductions.contains(&s1))
+        })
+    })
+}
+
+/// Scalars reduced by statements `s = s op e` with `op` one of `+`, `*`,
+/// `min` and `max`, or `s = s - e`, where `e` does not use `s`
+fn find_reductions(statements: &[Statement], reductions: &mut HashSet<Label>) {
+    for s in statements {
+        match s {
+            Statement::Loop(l) => find_reductions(&l.statements.0, reductions),
+            Statement::If(i) => {
+                find_reductions(&i.then_branch.0, reductions);
+                find_reductions(&i.else_branch.0, reductions);
+            }
+            Statement::Assignment(a) => {
+                let s = &a.lhs.name;
+                let is_s = |expr: &Expression| match expr {
+                    Expression::Variable(v) => &v.name == s && v.indices.is_empty(),
+                    _ => false,
+                };
+                let op = match &a.rhs {
+                    Expression::BinOp(op) if a.lhs.indices.is_empty() => op,
+                    _ => continue,
+                };
+
+                let expr = match op.op {
+                    OpType::Plus | OpType::Mul | OpType::Min | OpType::Max if is_s(&op.right) => {
+                        &op.left
+                    }
+                    OpType::Plus | OpType::Mul | OpType::Min | OpType::Max | OpType::Minus
+                        if is_s(&op.left) =>
+                    {
+                        &op.right
+                    }
+                    _ => continue,
+                };
+                if !uses_var(expr, s) {
+                    reductions.insert(a.label);
+                }
+            }
+        }
+    }
+}
+
+/// Direction of every pair of a write and another access to the same array,
+/// `Backward` if the other access happens in an earlier iteration of the loop
+fn directions(l: &Loop, variant: &HashSet<String>) -> Vec<Direction> {
+    let mut accesses = Vec::new();
+    collect_accesses(&l.statements.0, &mut accesses);
+
+    let mut directions = Vec::new();
+    for w in accesses.iter().filter(|a| a.write) {
+        for a in accesses.iter() {
+            if a.var.name != w.var.name
+                || a.var.indices.is_empty()
+                || a.var.indices.len() != w.var.indices.len()
+                || ::std::ptr::eq(a.var, w.var)
+            {
+                continue;
+            }
+
+            match distance(l, variant, w.var, a.var) {
+                Some(Some(d)) if d < 0 => directions.push(Direction::Backward),
+                Some(Some(d)) if d > 0 => directions.push(Direction::Forward),
+                Some(_) => (),
+                None => directions.push(Direction::Unknown),
+            }
+        }
+    }
+
+    directions
+}
+
+/// Iterations between the write and the other access of the same element.
+///
+/// `None` if unknown, `Some(None)` if the loop variable does not determine
+/// the distance or the accesses never touch the same element in the same
+/// iteration of the outer loops.
+fn distance(
+    l: &Loop,
+    variant: &HashSet<String>,
+    write: &Variable,
+    access: &Variable,
+) -> Option<Option<i32>> {
+    let mut distance = None;
+
+    for (e1, e2) in write.indices.iter().zip(access.indices.iter()) {
+        let (f, g) = (Affine::from_expr(e1)?, Affine::from_expr(e2)?);
+        let (a1, a2) = (f.coefficient(&l.var), g.coefficient(&l.var));
+        let (r1, r2) = (f.without(&l.var), g.without(&l.var));
+
+        let inner = variant.iter().filter(|v| **v != l.var);
+        if inner.clone().any(|v| r1.uses(v) || r2.uses(v)) {
+            // Subscripts varying with inner loops only matter if the loop
+            // variable appears as well
+            if a1 == 0 && a2 == 0 {
+                continue;
+            }
+            return None;
+        }
+
+        // a*i1 + r1 = a*i2 + r2 gives i2 - i1 = (r1 - r2) / a
+        let diff = r1.add(&r2.scale(-1)?)?;
+        match (a1, a2) {
+            // Outer loops may be in different iterations for both accesses
+            (0, 0) => (),
+            (a1, a2) if a1 == a2 && diff.is_constant() => {
+                if diff.constant % a1 != 0 {
+                    return Some(None);
+                }
+
+                let d = diff.constant / a1;
+                match distance {
+                    Some(other) if other != d => return Some(None),
+                    _ => distance = Some(d),
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    Some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dependencies::Attribution;
+    use transform::tests::{parse, writes};
+
+    const NEST: &str = "nest
+a FLOAT 2 1 20 1 20
+s FLOAT
+i INT
+j INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY j EXPR INT 2 EXPR VAR ENTRY n STMTLIST
+  FOR @ 2 ENTRY i EXPR INT 1 EXPR INT 9 STMTLIST
+    ASSIGN @ 3 VAR ENTRY s EXPR BINOP + EXPR VAR ENTRY s EXPR VAR ENTRY a EXPRLIST EXPR VAR ENTRY j EXPR VAR ENTRY i /EXPRLIST
+    ASSIGN @ 4 VAR ENTRY a EXPRLIST EXPR VAR ENTRY j EXPR VAR ENTRY i /EXPRLIST EXPR VAR ENTRY a EXPRLIST EXPR BINOP - EXPR VAR ENTRY j EXPR INT 1 EXPR BINOP + EXPR VAR ENTRY i EXPR INT 1 /EXPRLIST
+  /STMTLIST
+/STMTLIST
+/STMTLIST
+";
+
+    /// Dependencies of the reduction on itself at both levels and of the
+    /// array assignment on itself carried by the outer loop
+    fn graph() -> Graph<Label, Vec<LevelDependency>> {
+        let mut graph = Graph::new();
+        let reduction = graph.add_node(3);
+        let assignment = graph.add_node(4);
+        let dependency =
+            |level| LevelDependency(level, DependencyType::True, Attribution::default());
+        graph.add_edge(reduction, reduction, vec![dependency(1), dependency(2)]);
+        graph.add_edge(assignment, assignment, vec![dependency(1)]);
+        graph
+    }
+
+    #[test]
+    fn reverses_inner_loop() {
+        let ast = parse(NEST);
+        let reversed = reverse(&ast, &graph()).unwrap();
+
+        for n in 2..=6 {
+            let (original, reversed) = (writes(&ast, &[("n", n)]), writes(&reversed, &[("n", n)]));
+            assert_ne!(reversed, original, "n = {}", n);
+            assert_eq!(reversed[1], ("a".to_owned(), vec![2, 9]), "n = {}", n);
+
+            let (mut original, mut reversed) = (original, reversed);
+            original.sort();
+            reversed.sort();
+            assert_eq!(reversed, original, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn carried_anti_dependencies_prevent_reversal() {
+        let ast = parse(NEST);
+        let mut graph = graph();
+        let assignment = graph.node_indices().find(|n| graph[*n] == 4).unwrap();
+        let anti = LevelDependency(2, DependencyType::Anti, Attribution::default());
+        graph.add_edge(assignment, assignment, vec![anti]);
+
+        let reversed = reverse(&ast, &graph).unwrap();
+        assert_eq!(writes(&reversed, &[("n", 4)]), writes(&ast, &[("n", 4)]));
+    }
+}
@@ -0,0 +1,288 @@
+use super::*;
+
+/// Simplify an integer expression such as a subscript or a loop bound.
+///
+/// Sums are brought into the canonical form of `Affine`: like terms are
+/// combined, constants are collected at the end and identities like `+ 0` or
+/// `1 *` are dropped. Subexpressions which are not affine, e.g. array
+/// accesses or calls of `min`, are simplified on their own and treated as
+/// terms of the sum. Operations on integer literals are folded, unless they
+/// overflow or divide by zero.
+pub fn simplify(expr: &Expression) -> Expression {
+    match expr {
+        Expression::Integer(_) | Expression::Real(_) => expr.clone(),
+        Expression::Variable(var) => Expression::Variable(Variable {
+            name: var.name.to_owned(),
+            indices: var.indices.iter().map(simplify).collect(),
+        }),
+        Expression::Expression(expr) => simplify(expr),
+        Expression::UnOp(op) => match op.op {
+            OpType::Plus => simplify(&op.right),
+            OpType::Minus => simplify_sum(expr),
+            OpType::Min | OpType::Max => simplify(&op.right),
+            _ => Expression::UnOp(Box::new(UnOp {
+                op: op.op.clone(),
+                right: simplify(&op.right),
+            })),
+        },
+        Expression::BinOp(op) => match op.op {
+            OpType::Plus | OpType::Minus | OpType::Mul => simplify_sum(expr),
+            _ => simplify_binop(op),
+        },
+    }
+}
+
+fn simplify_sum(expr: &Expression) -> Expression {
+    match Sum::from_expr(expr) {
+        Some(sum) => sum.into_expr(),
+        // Overflowing constants are left as they are
+        None => match expr {
+            Expression::UnOp(op) => Expression::UnOp(Box::new(UnOp {
+                op: op.op.clone(),
+                right: simplify(&op.right),
+            })),
+            Expression::BinOp(op) => binop(op.op.clone(), simplify(&op.left), simplify(&op.right)),
+            _ => simplify(expr),
+        },
+    }
+}
+
+fn simplify_binop(op: &BinOp) -> Expression {
+    let left = simplify(&op.left);
+    let right = simplify(&op.right);
+
+    let folded = match (&op.op, &left, &right) {
+        (OpType::Div, Expression::Integer(l), Expression::Integer(r)) => l.checked_div(*r),
+        (OpType::Min, Expression::Integer(l), Expression::Integer(r)) => Some(*l.min(r)),
+        (OpType::Max, Expression::Integer(l), Expression::Integer(r)) => Some(*l.max(r)),
+        (OpType::Div, _, Expression::Integer(1)) => return left,
+        (OpType::Min, _, _) | (OpType::Max, _, _) if left == right => return left,
+        _ => None,
+    };
+
+    match folded {
+        Some(i) => Expression::Integer(i),
+        None => binop(op.op.clone(), left, right),
+    }
+}
+
+/// Linear combination of terms which are not affine themselves
+struct Sum {
+    terms: Vec<(Expression, i32)>,
+    constant: i32,
+}
+
+impl Sum {
+    fn constant(constant: i32) -> Self {
+        Sum {
+            terms: Vec::new(),
+            constant,
+        }
+    }
+
+    fn term(expr: Expression) -> Self {
+        Sum {
+            terms: vec![(expr, 1)],
+            constant: 0,
+        }
+    }
+
+    fn from_expr(expr: &Expression) -> Option<Self> {
+        match expr {
+            Expression::Integer(i) => Some(Sum::constant(*i)),
+            Expression::Expression(expr) => Sum::from_expr(expr),
+            Expression::UnOp(op) => match op.op {
+                OpType::Plus => Sum::from_expr(&op.right),
+                OpType::Minus => Sum::from_expr(&op.right)?.scale(-1),
+                _ => Some(Sum::term(simplify(expr))),
+            },
+            Expression::BinOp(op) => match op.op {
+                OpType::Plus => Sum::from_expr(&op.left)?.add(Sum::from_expr(&op.right)?),
+                OpType::Minus => {
+                    Sum::from_expr(&op.left)?.add(Sum::from_expr(&op.right)?.scale(-1)?)
+                }
+                OpType::Mul => {
+                    let left = Sum::from_expr(&op.left)?;
+                    let right = Sum::from_expr(&op.right)?;
+                    if left.terms.is_empty() {
+                        right.scale(left.constant)
+                    } else if right.terms.is_empty() {
+                        left.scale(right.constant)
+                    } else {
+                        Some(Sum::term(binop(
+                            OpType::Mul,
+                            left.into_expr(),
+                            right.into_expr(),
+                        )))
+                    }
+                }
+                _ => Some(Sum::term(simplify(expr))),
+            },
+            _ => Some(Sum::term(simplify(expr))),
+        }
+    }
+
+    fn add(mut self, other: Sum) -> Option<Self> {
+        self.constant = self.constant.checked_add(other.constant)?;
+
+        for (expr, c) in other.terms {
+            match self.terms.iter_mut().find(|(e, _)| *e == expr) {
+                Some((_, coefficient)) => *coefficient = coefficient.checked_add(c)?,
+                None => self.terms.push((expr, c)),
+            }
+        }
+        self.terms.retain(|(_, c)| *c != 0);
+
+        Some(self)
+    }
+
+    fn scale(mut self, factor: i32) -> Option<Self> {
+        if factor == 0 {
+            return Some(Sum::constant(0));
+        }
+
+        for (_, c) in self.terms.iter_mut() {
+            *c = c.checked_mul(factor)?;
+        }
+        self.constant = self.constant.checked_mul(factor)?;
+
+        Some(self)
+    }
+
+    /// Variables come first ordered by name like in `Affine`, other terms
+    /// keep their order
+    fn into_expr(mut self) -> Expression {
+        self.terms.sort_by_key(|(e, _)| match e {
+            Expression::Variable(v) => (0, v.name.to_owned()),
+            _ => (1, String::new()),
+        });
+
+        let mut expr: Option<Expression> = None;
+        for (term, c) in self.terms {
+            let term = match c.checked_abs() {
+                Some(1) => term,
+                Some(a) => binop(OpType::Mul, Expression::Integer(a), term),
+                None => binop(OpType::Mul, Expression::Integer(c), term),
+            };
+
+            expr = Some(match expr {
+                None if c < 0 && c != i32::MIN => Expression::UnOp(Box::new(UnOp {
+                    op: OpType::Minus,
+                    right: term,
+                })),
+                None => term,
+                Some(e) if c < 0 && c != i32::MIN => binop(OpType::Minus, e, term),
+                Some(e) => binop(OpType::Plus, e, term),
+            });
+        }
+
+        match expr {
+            None => Expression::Integer(self.constant),
+            Some(e) if self.constant == 0 => e,
+            Some(e) => match self.constant.checked_neg() {
+                Some(c) if c > 0 => binop(OpType::Minus, e, Expression::Integer(c)),
+                _ => binop(OpType::Plus, e, Expression::Integer(self.constant)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codegen::expression_source;
+    use std::collections::HashMap;
+    use transform::tests::eval;
+
+    fn int(i: i32) -> Expression {
+        Expression::Integer(i)
+    }
+
+    #[test]
+    fn simplifies_to_equal_expressions() {
+        let (i, j, n) = (var_expr("i"), var_expr("j"), var_expr("n"));
+        let cases = vec![
+            // 2 * (i + 1) - i - 2
+            (
+                binop(
+                    OpType::Minus,
+                    binop(
+                        OpType::Minus,
+                        binop(
+                            OpType::Mul,
+                            int(2),
+                            paren(&binop(OpType::Plus, i.clone(), int(1))),
+                        ),
+                        i.clone(),
+                    ),
+                    int(2),
+                ),
+                "i",
+            ),
+            // j + i + 3 - 3
+            (
+                binop(
+                    OpType::Minus,
+                    binop(
+                        OpType::Plus,
+                        binop(OpType::Plus, j.clone(), i.clone()),
+                        int(3),
+                    ),
+                    int(3),
+                ),
+                "i+j",
+            ),
+            // -(i - 3) * 2
+            (
+                binop(
+                    OpType::Mul,
+                    Expression::UnOp(Box::new(UnOp {
+                        op: OpType::Minus,
+                        right: paren(&binop(OpType::Minus, i.clone(), int(3))),
+                    })),
+                    int(2),
+                ),
+                "-2*i+6",
+            ),
+            (binop(OpType::Min, n.clone(), n.clone()), "n"),
+            (binop(OpType::Max, int(3), int(-4)), "3"),
+            (binop(OpType::Div, n.clone(), int(1)), "n"),
+            (binop(OpType::Div, int(7), int(2)), "3"),
+            (binop(OpType::Div, n.clone(), int(0)), "n/0"),
+            (binop(OpType::Plus, int(i32::MAX), int(1)), "2147483647+1"),
+        ];
+
+        for (expr, source) in cases {
+            let simplified = simplify(&expr);
+            assert_eq!(expression_source(&simplified), source);
+
+            for values in [(0, 0, 0), (1, 2, 3), (-5, 7, 4)].iter() {
+                let values = [("i", values.0), ("j", values.1), ("n", values.2)]
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), *value))
+                    .collect::<HashMap<_, _>>();
+                assert_eq!(
+                    eval(&simplified, &values),
+                    eval(&expr, &values),
+                    "{}",
+                    source
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn simplifies_subscripts_of_terms() {
+        // a(i + 0) + 1 * k
+        let access = Expression::Variable(Variable {
+            name: "a".to_owned(),
+            indices: vec![binop(OpType::Plus, var_expr("i"), int(0))],
+        });
+        let expr = binop(
+            OpType::Plus,
+            access,
+            binop(OpType::Mul, int(1), var_expr("k")),
+        );
+        assert_eq!(expression_source(&simplify(&expr)), "a(i)+k");
+    }
+}
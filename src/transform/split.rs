@@ -0,0 +1,296 @@
+use super::*;
+use std::io;
+
+/// Split loops carrying a dependency at the iterations where reflected
+/// accesses like `a(i)` and `a(n-i+1)` cross.
+///
+/// All dependencies between such accesses connect an iteration before the
+/// crossing point with one after it. Splitting the iteration space there
+/// leaves pieces that can be analysed and vectorized independently. Every
+/// piece but the first receives fresh labels.
+pub fn split(ast: &Ast, graph: &Graph<Label, Vec<LevelDependency>>) -> io::Result<Ast> {
+    let mut splitter = Splitter {
+        graph,
+        label: max_label(&ast.statements.0),
+    };
+    let statements = splitter.stmtlist(&ast.statements.0, 0);
+
+    Ok(Ast {
+        name: ast.name.to_owned(),
+        vardef: ast.vardef.clone(),
+        statements,
+    })
+}
+
+struct Splitter<'a> {
+    graph: &'a Graph<Label, Vec<LevelDependency>>,
+    label: i32,
+}
+
+/// Crossing point `numerator / denominator` of two reflected subscripts
+#[derive(PartialEq)]
+struct SplitPoint {
+    numerator: Affine,
+    denominator: i32,
+}
+
+impl<'a> Splitter<'a> {
+    fn stmtlist(&mut self, statements: &[Statement], depth: Level) -> StatementList {
+        let mut result = Vec::new();
+
+        for s in statements {
+            match s {
+                Statement::Loop(l) => {
+                    let l = Loop {
+                        statements: self.stmtlist(&l.statements.0, depth + 1),
+                        ..l.clone()
+                    };
+                    result.extend(self.split_loop(l, depth));
+                }
+                Statement::If(i) => result.push(Statement::If(If {
+                    label: i.label,
+                    expr: i.expr.clone(),
+                    then_branch: self.stmtlist(&i.then_branch.0, depth),
+                    else_branch: self.stmtlist(&i.else_branch.0, depth),
+                })),
+                Statement::Assignment(_) => result.push(s.clone()),
+            }
+        }
+
+        StatementList(result)
+    }
+
+    fn split_loop(&mut self, l: Loop, depth: Level) -> Vec<Statement> {
+        let mut labels = HashSet::new();
+        statement_labels(&l.statements.0, &mut labels);
+        if l.step.is_some() || !dependency_levels(self.graph, &labels).contains(&(depth + 1)) {
+            return vec![Statement::Loop(l)];
+        }
+
+        let points = split_points(&l);
+        if points.is_empty() {
+            return vec![Statement::Loop(l)];
+        }
+
+        // Split each piece at every point, bounds outside of a piece leave
+        // the respective part empty
+        let mut pieces = vec![(l.lower.clone(), l.upper.clone())];
+        for point in points.iter() {
+            let (last, next) = point.to_exprs();
+            pieces = pieces
+                .into_iter()
+                .flat_map(|(lower, upper)| {
+                    vec![
+                        (
+                            lower.clone(),
+                            bound(OpType::Min, upper.clone(), last.clone()),
+                        ),
+                        (bound(OpType::Max, lower, next.clone()), upper),
+                    ]
+                })
+                .collect();
+        }
+
+        let mut result = Vec::new();
+        for (i, (lower, upper)) in pieces.into_iter().enumerate() {
+            let (label, statements) = if i == 0 {
+                (l.label, l.statements.clone())
+            } else {
+                self.label += 1;
+                (self.label, relabel(&l.statements.0, &mut self.label))
+            };
+
+            result.push(Statement::Loop(Loop {
+                label,
+                var: l.var.to_owned(),
+                lower,
+                upper,
+                step: None,
+                statements,
+            }));
+        }
+
+        result
+    }
+}
+
+impl SplitPoint {
+    /// Last iteration before the crossing point and first one after it. As
+    /// `/` truncates towards zero, symbolic points are rounded down by
+    /// `max(n, 0)/d + (min(n, 0) - d + 1)/d` like constant ones.
+    fn to_exprs(&self) -> (Expression, Expression) {
+        if self.numerator.is_constant() {
+            let last = self.numerator.constant.div_euclid(self.denominator);
+            return (Expression::Integer(last), Expression::Integer(last + 1));
+        }
+
+        let n = self.numerator.to_expr();
+        let div = |numerator| {
+            binop(
+                OpType::Div,
+                Expression::Expression(Box::new(numerator)),
+                Expression::Integer(self.denominator),
+            )
+        };
+        let positive = binop(OpType::Max, n.clone(), Expression::Integer(0));
+        let negative = binop(
+            OpType::Minus,
+            binop(OpType::Min, n, Expression::Integer(0)),
+            Expression::Integer(self.denominator - 1),
+        );
+        let last = binop(OpType::Plus, div(positive), div(negative));
+        let next = binop(OpType::Plus, last.clone(), Expression::Integer(1));
+        (last, next)
+    }
+}
+
+/// Minimum or maximum of two bounds, folded if both are constant
+fn bound(op: OpType, left: Expression, right: Expression) -> Expression {
+    match (&left, &right) {
+        (Expression::Integer(l), Expression::Integer(r)) => match op {
+            OpType::Max => Expression::Integer(*l.max(r)),
+            _ => Expression::Integer(*l.min(r)),
+        },
+        _ => binop(op, left, right),
+    }
+}
+
+fn split_points(l: &Loop) -> Vec<SplitPoint> {
+    let mut accesses = Vec::new();
+    collect_accesses(&l.statements.0, &mut accesses);
+
+    // Subscript parts varying inside the loop prevent a fixed crossing point
+    let mut variant = HashSet::new();
+    variant.insert(l.var.to_owned());
+    variant_names(&l.statements.0, &mut variant);
+
+    let mut points = Vec::new();
+    for w in accesses.iter().filter(|a| a.write) {
+        for a in accesses.iter() {
+            if a.var.name != w.var.name || a.var.indices.len() != w.var.indices.len() {
+                continue;
+            }
+
+            for (e1, e2) in w.var.indices.iter().zip(a.var.indices.iter()) {
+                let (f, g) = match (Affine::from_expr(e1), Affine::from_expr(e2)) {
+                    (Some(f), Some(g)) => (f, g),
+                    _ => continue,
+                };
+
+                let (a1, a2) = (f.coefficient(&l.var), g.coefficient(&l.var));
+                if a1 == 0 || a1.checked_neg() != Some(a2) {
+                    continue;
+                }
+
+                // a1*i + r1 = -a1*i + r2 crosses at i = (r2 - r1) / (2*a1)
+                let (r1, r2) = (f.without(&l.var), g.without(&l.var));
+                if variant.iter().any(|v| r1.uses(v) || r2.uses(v)) {
+                    continue;
+                }
+
+                let point = r1
+                    .scale(-1)
+                    .and_then(|r| r2.add(&r))
+                    .and_then(|n| a1.checked_mul(2).map(|d| (n, d)))
+                    .and_then(|(n, d)| {
+                        if d < 0 {
+                            n.scale(-1).map(|n| (n, -d))
+                        } else {
+                            Some((n, d))
+                        }
+                    });
+
+                if let Some((numerator, denominator)) = point {
+                    let point = SplitPoint {
+                        numerator,
+                        denominator,
+                    };
+                    if !points.contains(&point) {
+                        points.push(point);
+                    }
+                }
+            }
+        }
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dependencies::{Attribution, DependencyType};
+    use std::collections::HashMap;
+    use transform::tests::{eval, parse, writes};
+
+    const REFLECTED: &str = "reflected
+a FLOAT 1 1 20
+i INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 1 EXPR VAR ENTRY n STMTLIST
+  ASSIGN @ 2 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR VAR ENTRY a EXPRLIST EXPR BINOP + EXPR BINOP - EXPR VAR ENTRY n EXPR VAR ENTRY i EXPR INT 1 /EXPRLIST
+/STMTLIST
+/STMTLIST
+";
+
+    #[test]
+    fn splits_reflected_accesses() {
+        let ast = parse(REFLECTED);
+        let mut graph = Graph::new();
+        let node = graph.add_node(2);
+        let carried = LevelDependency(1, DependencyType::Anti, Attribution::default());
+        graph.add_edge(node, node, vec![carried]);
+
+        let split = split(&ast, &graph).unwrap();
+        assert_eq!(split.statements.0.len(), 2);
+
+        for n in 0..=12 {
+            assert_eq!(
+                writes(&split, &[("n", n)]),
+                writes(&ast, &[("n", n)]),
+                "n = {}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn loops_without_carried_dependencies_stay() {
+        let ast = parse(REFLECTED);
+        let split = split(&ast, &Graph::new()).unwrap();
+        assert_eq!(split.statements.0.len(), 1);
+    }
+
+    #[test]
+    fn symbolic_points_round_down() {
+        for denominator in 2..=6 {
+            let point = SplitPoint {
+                numerator: Affine::var("n").add(&Affine::constant(1)).unwrap(),
+                denominator,
+            };
+            let (last, next) = point.to_exprs();
+
+            for n in -20..=20 {
+                let constant = SplitPoint {
+                    numerator: Affine::constant(n + 1),
+                    denominator,
+                };
+                let values = vec![("n".to_owned(), n)]
+                    .into_iter()
+                    .collect::<HashMap<_, _>>();
+                let expected = (n + 1).div_euclid(denominator);
+
+                assert_eq!(eval(&last, &values), Some(expected), "n = {}", n);
+                assert_eq!(eval(&next, &values), Some(expected + 1), "n = {}", n);
+                assert_eq!(
+                    constant.to_exprs(),
+                    (
+                        Expression::Integer(expected),
+                        Expression::Integer(expected + 1)
+                    )
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use super::*;
+use std::io;
+
+/// Tile every perfectly nested loop band of `sizes.len()` loops.
+///
+/// The trace based dependence graph only records the level carrying a
+/// dependency, the directions of the deeper levels are unknown. Tiling a band
+/// is therefore only accepted if no dependency is carried by any of its loops
+/// except the innermost one, which keeps all dependencies non-negative in the
+/// tiled dimensions.
+pub fn tile(
+    ast: &Ast,
+    graph: &Graph<Label, Vec<LevelDependency>>,
+    sizes: &[i32],
+) -> io::Result<Ast> {
+    if sizes.is_empty() || sizes.iter().any(|s| *s < 1) {
+        return Err(io::Error::other("Tile sizes have to be positive integers"));
+    }
+
+    let mut tiler = Tiler {
+        graph,
+        sizes,
+        names: used_names(ast),
+        vardef: ast.vardef.clone(),
+        label: max_label(&ast.statements.0),
+    };
+    let statements = tiler.stmtlist(&ast.statements.0, 0);
+
+    Ok(Ast {
+        name: ast.name.to_owned(),
+        vardef: tiler.vardef,
+        statements,
+    })
+}
+
+struct Tiler<'a> {
+    graph: &'a Graph<Label, Vec<LevelDependency>>,
+    sizes: &'a [i32],
+    names: HashSet<String>,
+    vardef: Vec<Definition>,
+    label: i32,
+}
+
+impl<'a> Tiler<'a> {
+    fn stmtlist(&mut self, statements: &[Statement], depth: Level) -> StatementList {
+        StatementList(
+            statements
+                .iter()
+                .map(|s| self.statement(s, depth))
+                .collect(),
+        )
+    }
+
+    fn statement(&mut self, statement: &Statement, depth: Level) -> Statement {
+        match statement {
+            Statement::Loop(l) => {
+                if let Some(band) = self.band(l) {
+                    if self.legal(&band, depth) {
+                        return self.tile_band(&band);
+                    }
+
+                    eprintln!(
+                        "Loop {} carries dependencies inside the tiled band, not tiling",
+                        l.label
+                    );
+                }
+
+                Statement::Loop(Loop {
+                    statements: self.stmtlist(&l.statements.0, depth + 1),
+                    ..l.clone()
+                })
+            }
+            Statement::If(i) => Statement::If(If {
+                label: i.label,
+                expr: i.expr.clone(),
+                then_branch: self.stmtlist(&i.then_branch.0, depth),
+                else_branch: self.stmtlist(&i.else_branch.0, depth),
+            }),
+            Statement::Assignment(_) => statement.clone(),
+        }
+    }
+
+    /// Perfectly nested loops starting at `loop_node` with rectangular bounds
+    fn band<'l>(&self, loop_node: &'l Loop) -> Option<Vec<&'l Loop>> {
+        let mut band = vec![loop_node];
+
+        while band.len() < self.sizes.len() {
+            let inner = match band.last().map(|l| &l.statements.0[..]) {
+                Some([Statement::Loop(inner)]) => inner,
+                _ => return None,
+            };
+
+            let bounds = [&inner.lower, &inner.upper];
+            if band
+                .iter()
+                .any(|l| bounds.iter().any(|b| uses_var(b, &l.var)))
+            {
+                return None;
+            }
+
+            band.push(inner);
+        }
+
+        if band.iter().any(|l| l.step.is_some()) {
+            return None;
+        }
+
+        Some(band)
+    }
+
+    fn legal(&self, band: &[&Loop], depth: Level) -> bool {
+        let mut labels = HashSet::new();
+        if let Some(inner) = band.last() {
+            statement_labels(&inner.statements.0, &mut labels);
+        }
+
+        let outer = depth + 1;
+        let inner = depth + band.len() as Level;
+        dependency_levels(self.graph, &labels)
+            .into_iter()
+            .all(|level| level < outer || level >= inner)
+    }
+
+    fn tile_band(&mut self, band: &[&Loop]) -> Statement {
+        let tile_vars = band
+            .iter()
+            .map(|l| {
+                let name = fresh_name(&format!("{0}{0}", &l.var), &mut self.names);
+                self.vardef.push(Definition {
+                    name: name.to_owned(),
+                    dimensions: Vec::new(),
+                    dtype: DefinitionType::Integer,
+                });
+                name
+            })
+            .collect::<Vec<_>>();
+
+        let labels = band
+            .iter()
+            .map(|_| {
+                self.label += 1;
+                self.label
+            })
+            .collect::<Vec<_>>();
+
+        let mut statements = match band.last() {
+            Some(inner) => inner.statements.clone(),
+            None => StatementList(Vec::new()),
+        };
+
+        // Element loops run from the tile start to the end of the tile,
+        // bounded by the original upper bound
+        for ((l, tile_var), size) in band.iter().zip(tile_vars.iter()).zip(self.sizes).rev() {
+            let tile_start = var_expr(tile_var);
+            let tile_end = binop(
+                OpType::Plus,
+                tile_start.clone(),
+                Expression::Integer(size - 1),
+            );
+
+            statements = StatementList(vec![Statement::Loop(Loop {
+                label: l.label,
+                var: l.var.to_owned(),
+                lower: tile_start,
+                upper: binop(OpType::Min, tile_end, l.upper.clone()),
+                step: None,
+                statements,
+            })]);
+        }
+
+        // Tile loops step through the original iteration space
+        for (((l, tile_var), size), label) in
+            band.iter().zip(tile_vars).zip(self.sizes).zip(labels).rev()
+        {
+            statements = StatementList(vec![Statement::Loop(Loop {
+                label,
+                var: tile_var,
+                lower: l.lower.clone(),
+                upper: l.upper.clone(),
+                step: Some(Expression::Integer(*size)),
+                statements,
+            })]);
+        }
+
+        statements.0.remove(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::tests::{parse, writes};
+
+    const NEST: &str = "nest
+a FLOAT 2 1 20 1 20
+i INT
+j INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 1 EXPR VAR ENTRY n STMTLIST
+  FOR @ 2 ENTRY j EXPR INT 2 EXPR INT 8 STMTLIST
+    ASSIGN @ 3 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST EXPR INT 0
+  /STMTLIST
+/STMTLIST
+/STMTLIST
+";
+
+    #[test]
+    fn tiles_end_at_the_upper_bound() {
+        let ast = parse(NEST);
+        let tiled = tile(&ast, &Graph::new(), &[4, 3]).unwrap();
+
+        for n in 0..=10 {
+            let mut expected = writes(&ast, &[("n", n)]);
+            let mut actual = writes(&tiled, &[("n", n)]);
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn element_loops_are_bounded_by_min() {
+        let tiled = tile(&parse(NEST), &Graph::new(), &[4]).unwrap();
+
+        let tile_loop = match &tiled.statements.0[0] {
+            Statement::Loop(l) => l,
+            s => panic!("Expected tile loop, found {:?}", s),
+        };
+        assert_eq!(tile_loop.step, Some(Expression::Integer(4)));
+        match &tile_loop.statements.0[0] {
+            Statement::Loop(l) => assert_eq!(
+                l.upper,
+                binop(
+                    OpType::Min,
+                    binop(
+                        OpType::Plus,
+                        var_expr(&tile_loop.var),
+                        Expression::Integer(3)
+                    ),
+                    var_expr("n"),
+                )
+            ),
+            s => panic!("Expected element loop, found {:?}", s),
+        }
+    }
+}
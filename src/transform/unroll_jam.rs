@@ -0,0 +1,174 @@
+use super::*;
+
+/// Unroll the loop with the given label by `factor` and jam the copies into
+/// the loops nested inside of it.
+///
+/// Copies of different iterations of the unrolled loop are interleaved at
+/// every inner level, which is only legal if the loop carries no dependency.
+/// Iterations not covered by the unrolled loop are executed by a remainder
+/// loop. Duplicated statements and loops receive fresh labels.
+pub fn unroll_and_jam(
+    ast: &Ast,
+    graph: &Graph<Label, Vec<LevelDependency>>,
+    label: LoopLabel,
+    factor: i32,
+) -> io::Result<Ast> {
+    if factor < 1 {
+        return Err(io::Error::other(
+            "Unroll factor has to be a positive integer",
+        ));
+    }
+
+    let mut next_label = max_label(&ast.statements.0);
+
+    rewrite_loop(ast, label, |l, depth| {
+        if l.step.is_some() {
+            return Err(io::Error::other(format!(
+                "Loop {} has a step and cannot be unrolled",
+                l.label
+            )));
+        }
+
+        let mut labels = HashSet::new();
+        statement_labels(&l.statements.0, &mut labels);
+        if dependency_levels(graph, &labels).contains(&(depth + 1)) {
+            return Err(io::Error::other(format!(
+                "Loop {} carries a dependency",
+                l.label
+            )));
+        }
+
+        let unrolled = Loop {
+            label: l.label,
+            var: l.var.to_owned(),
+            lower: l.lower.clone(),
+            upper: binop(
+                OpType::Minus,
+                l.upper.clone(),
+                Expression::Integer(factor - 1),
+            ),
+            step: Some(Expression::Integer(factor)),
+            statements: jam(&l.statements.0, &l.var, factor, &mut next_label)?,
+        };
+
+        // The remainder starts after the last complete group of iterations
+        let trip_count = binop(
+            OpType::Plus,
+            binop(OpType::Minus, l.upper.clone(), l.lower.clone()),
+            Expression::Integer(1),
+        );
+        let groups = binop(
+            OpType::Div,
+            Expression::Expression(Box::new(trip_count)),
+            Expression::Integer(factor),
+        );
+        next_label += 1;
+        let remainder = Loop {
+            label: next_label,
+            var: l.var.to_owned(),
+            lower: binop(
+                OpType::Plus,
+                l.lower.clone(),
+                binop(
+                    OpType::Mul,
+                    Expression::Expression(Box::new(groups)),
+                    Expression::Integer(factor),
+                ),
+            ),
+            upper: l.upper.clone(),
+            step: None,
+            statements: relabel(&l.statements.0, &mut next_label),
+        };
+
+        Ok(vec![Statement::Loop(unrolled), Statement::Loop(remainder)])
+    })
+}
+
+fn jam(
+    statements: &[Statement],
+    var: &str,
+    factor: i32,
+    label: &mut i32,
+) -> io::Result<StatementList> {
+    let mut result = Vec::new();
+    let mut run = Vec::new();
+
+    for s in statements {
+        match s {
+            Statement::Loop(l) => {
+                unroll(&run, var, factor, label, &mut result);
+                run.clear();
+
+                let bounds = [Some(&l.lower), Some(&l.upper), l.step.as_ref()];
+                if bounds.iter().flatten().any(|b| uses_var(b, var)) {
+                    return Err(io::Error::other(format!(
+                        "Bounds of loop {} depend on {}",
+                        l.label, var
+                    )));
+                }
+
+                result.push(Statement::Loop(Loop {
+                    statements: jam(&l.statements.0, var, factor, label)?,
+                    ..l.clone()
+                }));
+            }
+            _ => run.push(s.clone()),
+        }
+    }
+    unroll(&run, var, factor, label, &mut result);
+
+    Ok(StatementList(result))
+}
+
+/// Append one copy of the straight-line statements per unrolled iteration,
+/// the first copy keeps the original labels
+fn unroll(run: &[Statement], var: &str, factor: i32, label: &mut i32, out: &mut Vec<Statement>) {
+    if run.is_empty() {
+        return;
+    }
+
+    out.extend(run.iter().cloned());
+    for k in 1..factor {
+        let offset = binop(OpType::Plus, var_expr(var), Expression::Integer(k));
+        let copy = substitute_stmtlist(run, var, &offset);
+        out.extend(relabel(&copy.0, label).0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::tests::{parse, writes};
+
+    const NEST: &str = "nest
+a FLOAT 2 1 20 1 20
+b FLOAT 1 1 20
+i INT
+j INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 3 EXPR VAR ENTRY n STMTLIST
+  ASSIGN @ 2 VAR ENTRY b EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR INT 0
+  FOR @ 3 ENTRY j EXPR INT 1 EXPR INT 2 STMTLIST
+    ASSIGN @ 4 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST EXPR INT 0
+  /STMTLIST
+/STMTLIST
+/STMTLIST
+";
+
+    #[test]
+    fn remainder_starts_after_last_group() {
+        let ast = parse(NEST);
+
+        for factor in 1..=4 {
+            let unrolled = unroll_and_jam(&ast, &Graph::new(), 1, factor).unwrap();
+            for n in 2..=12 {
+                let mut expected = writes(&ast, &[("n", n)]);
+                let mut actual = writes(&unrolled, &[("n", n)]);
+                expected.sort();
+                actual.sort();
+                assert_eq!(actual, expected, "factor {}, n = {}", factor, n);
+            }
+        }
+    }
+}
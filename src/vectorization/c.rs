@@ -0,0 +1,85 @@
+use super::*;
+use codegen::c::{constant_step, CCodegen, CProgram};
+
+/// C loops, the innermost loop of a statement without carried dependencies
+/// is annotated for vectorization or uses vector types
+pub(super) struct CTarget<W> {
+    pub codegen: CCodegen<CProgram, W>,
+    mode: Mode,
+}
+
+impl<W: io::Write> CTarget<W> {
+    pub fn new(ast: &Ast, out: W, mode: Mode) -> Self {
+        let codegen = match mode {
+            Mode::CVector(width) => CCodegen::new_vector(CProgram, ast, out, width),
+            _ => CCodegen::new(CProgram, ast, out),
+        };
+
+        CTarget { codegen, mode }
+    }
+}
+
+impl<'a, W: io::Write> Target<'a> for CTarget<W> {
+    fn loop_start(
+        &mut self,
+        l: &'a Loop,
+        c: Level,
+        _private: Option<&[&'a str]>,
+    ) -> io::Result<()> {
+        self.codegen.generate_loop_start(l, c as u8)
+    }
+
+    fn loop_end(&mut self, l: &'a Loop, c: Level) -> io::Result<()> {
+        self.codegen.generate_loop_end(l, c as u8)
+    }
+
+    fn statement(
+        &mut self,
+        assign: &'a Assign,
+        loops: &[&'a Loop],
+        c: Level,
+        _parallel: bool,
+    ) -> io::Result<()> {
+        if let (Mode::CVector(_), Some((innermost, outer))) = (self.mode, loops.split_last()) {
+            for (i, l) in outer.iter().enumerate() {
+                self.codegen.generate_loop_start(l, c as u8 + i as u8)?;
+            }
+
+            self.codegen.generate_vector_loop(
+                assign,
+                innermost,
+                (c as usize + outer.len()) as u8,
+            )?;
+
+            for (i, l) in outer.iter().enumerate().rev() {
+                self.codegen.generate_loop_end(l, c as u8 + i as u8)?;
+            }
+
+            return Ok(());
+        }
+
+        for (i, l) in loops.iter().enumerate() {
+            let indent = c as u8 + i as u8;
+            // Loops with a step of unknown direction are not in canonical form
+            if i == loops.len() - 1 && constant_step(l).is_some() {
+                let pragma = if self.mode == Mode::CIvdep {
+                    "#pragma GCC ivdep"
+                } else {
+                    "#pragma omp simd"
+                };
+                self.codegen.generate_line(pragma, indent)?;
+            }
+
+            self.codegen.generate_loop_start(l, indent)?;
+        }
+
+        self.codegen
+            .generate_assignment(assign, (c as usize + loops.len()) as u8)?;
+
+        for (i, l) in loops.iter().enumerate().rev() {
+            self.codegen.generate_loop_end(l, c as u8 + i as u8)?;
+        }
+
+        Ok(())
+    }
+}
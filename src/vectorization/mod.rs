@@ -1,5 +1,9 @@
-use codegen::{Codegen, Generator, Vectorizer};
-use dependencies::{Level, LevelDependency, LoopLabel, Statement};
+mod c;
+mod numpy;
+mod rust;
+
+use codegen::{Codegen, Vectorizer};
+use dependencies::{DependencyType, Level, LevelDependency, LoopLabel, Statement};
 use ir::{Assign, Ast, Loop};
 use petgraph::{
     algo::{is_cyclic_directed, tarjan_scc},
@@ -8,6 +12,60 @@ use petgraph::{
 use std::collections::{HashMap, HashSet};
 use std::io;
 
+/// Code generated for statements without loop carried dependencies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Fortran array sections
+    Array,
+    /// Loops annotated with `!$omp parallel do` and `!$omp simd`
+    OpenMp,
+    /// `do concurrent` constructs
+    Concurrent,
+    /// `forall` constructs
+    Forall,
+    /// C loops annotated with `#pragma omp simd`
+    C,
+    /// C loops annotated with `#pragma GCC ivdep`
+    CIvdep,
+    /// C loops using GCC vector types with the given number of elements
+    CVector(usize),
+    /// Rust function iterating over slices
+    Rust,
+    /// Python function using NumPy slices
+    NumPy,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "array" => Some(Mode::Array),
+            "omp" => Some(Mode::OpenMp),
+            "concurrent" => Some(Mode::Concurrent),
+            "forall" => Some(Mode::Forall),
+            "c" => Some(Mode::C),
+            "c-ivdep" => Some(Mode::CIvdep),
+            "c-vector" => Some(Mode::CVector(4)),
+            "rust" => Some(Mode::Rust),
+            "numpy" => Some(Mode::NumPy),
+            _ => None,
+        }
+    }
+
+    /// Suffix of the output file
+    pub fn file_suffix(&self) -> &'static str {
+        match self {
+            Mode::Array => "vectorized.f90",
+            Mode::OpenMp => "omp.f90",
+            Mode::Concurrent => "concurrent.f90",
+            Mode::Forall => "forall.f90",
+            Mode::C | Mode::CIvdep => "vectorized.c",
+            Mode::CVector(_) => "simd.c",
+            Mode::Rust => "vectorized.rs",
+            Mode::NumPy => "vectorized.py",
+        }
+    }
+}
+
 pub fn vectorize<W>(
     graph: &Graph<Statement, Vec<LevelDependency>>,
     ast: &Ast,
@@ -17,56 +75,188 @@ pub fn vectorize<W>(
 where
     W: io::Write,
 {
-    let mut loop_map = HashMap::new();
-    ast_loops(&ast.statements.0, &mut loop_map);
+    vectorize_with(graph, ast, writer, fold, Mode::Array)
+}
+
+pub fn vectorize_with<W>(
+    graph: &Graph<Statement, Vec<LevelDependency>>,
+    ast: &Ast,
+    writer: W,
+    fold: bool,
+    mode: Mode,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let mut schedule = Schedule {
+        loop_map: HashMap::new(),
+        stat_map: HashMap::new(),
+        stat_lps: HashMap::new(),
+    };
+    ast_loops(&ast.statements.0, &mut schedule.loop_map);
 
     let mut loop_cur = Vec::new();
-    let mut stat_map = HashMap::new();
-    let mut stat_lps = HashMap::new();
     ast_statements(
         &ast.statements.0,
         &mut loop_cur,
-        &mut stat_map,
-        &mut stat_lps,
+        &mut schedule.stat_map,
+        &mut schedule.stat_lps,
     );
 
     // Generate code
-    let mut cg: Codegen<Vectorizer<_>, _> = if fold {
-        Codegen::new_folding(writer)
-    } else {
-        Codegen::new(writer)
-    };
-    cg.generate_header(ast)?;
-    allen_kennedy(&mut cg, graph, ast, &loop_map, &stat_map, &stat_lps, 0)?;
-    cg.generate_footer(ast)
+    match mode {
+        Mode::C | Mode::CIvdep | Mode::CVector(_) => {
+            let mut target = c::CTarget::new(ast, writer, mode);
+            target.codegen.generate_header(ast)?;
+            allen_kennedy(&mut target, graph, &schedule, 0, false)?;
+            target.codegen.generate_footer(ast)
+        }
+        Mode::NumPy => {
+            let mut target = numpy::NumpyTarget::new(ast, writer);
+            target.codegen.generate_header(ast)?;
+            allen_kennedy(&mut target, graph, &schedule, 0, false)?;
+            target.codegen.generate_footer(ast)
+        }
+        Mode::Rust => {
+            let mut target = rust::RustTarget::new(ast, writer);
+            target.codegen.generate_header(ast)?;
+            allen_kennedy(&mut target, graph, &schedule, 0, false)?;
+            target.codegen.generate_footer(ast)
+        }
+        _ => {
+            let codegen = if fold {
+                Codegen::new_folding(writer)
+            } else {
+                Codegen::new(writer)
+            };
+            let mut target = Fortran { codegen, mode };
+            target.codegen.generate_header(ast)?;
+            allen_kennedy(&mut target, graph, &schedule, 0, false)?;
+            target.codegen.generate_footer(ast)
+        }
+    }
+}
+
+/// Loops and statements of the AST
+struct Schedule<'a> {
+    loop_map: HashMap<LoopLabel, &'a Loop>,
+    stat_map: HashMap<Statement, &'a Assign>,
+    stat_lps: HashMap<Statement, Vec<LoopLabel>>,
+}
+
+/// Output generated from the Allen-Kennedy schedule
+trait Target<'a> {
+    /// Whether loops around dependency cycles, which carry no dependency
+    /// themselves, are run in parallel
+    fn parallel_loops(&self) -> bool {
+        false
+    }
+
+    /// Sequential loop at level `c`, `private` holds the inner loop
+    /// variables if it is run in parallel
+    fn loop_start(&mut self, l: &'a Loop, c: Level, private: Option<&[&'a str]>) -> io::Result<()>;
+
+    fn loop_end(&mut self, l: &'a Loop, c: Level) -> io::Result<()>;
+
+    /// Statement without carried dependencies inside `loops`, which are
+    /// already inside a parallel loop if `parallel` is set
+    fn statement(
+        &mut self,
+        assign: &'a Assign,
+        loops: &[&'a Loop],
+        c: Level,
+        parallel: bool,
+    ) -> io::Result<()>;
+
+    /// Reduction of a scalar by a statement, which only depends on itself.
+    /// Returns false, if the statement is generated with loops instead.
+    fn reduction(
+        &mut self,
+        _assign: &'a Assign,
+        _loops: &[&'a Loop],
+        _c: Level,
+    ) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+struct Fortran<'a, W: 'a> {
+    codegen: Codegen<Vectorizer<'a, W>, W>,
+    mode: Mode,
+}
+
+impl<'a, W: io::Write> Target<'a> for Fortran<'a, W> {
+    fn parallel_loops(&self) -> bool {
+        self.mode == Mode::OpenMp
+    }
+
+    fn loop_start(&mut self, l: &'a Loop, c: Level, private: Option<&[&'a str]>) -> io::Result<()> {
+        if let Some(private) = private {
+            self.codegen.generate_omp_parallel_do(private, false, c)?;
+        }
+
+        self.codegen.generate_loop_vec_start(l, c)
+    }
+
+    fn loop_end(&mut self, l: &'a Loop, c: Level) -> io::Result<()> {
+        self.codegen.generate_loop_vec_end(l, c)
+    }
+
+    fn statement(
+        &mut self,
+        assign: &'a Assign,
+        loops: &[&'a Loop],
+        c: Level,
+        parallel: bool,
+    ) -> io::Result<()> {
+        match self.mode {
+            Mode::OpenMp => {
+                self.codegen.set_loop_data(HashMap::new());
+                self.codegen.generate_omp_loops(assign, loops, c, parallel)
+            }
+            Mode::Concurrent | Mode::Forall => {
+                self.codegen.set_loop_data(HashMap::new());
+                self.codegen
+                    .generate_concurrent_loops(assign, loops, c, self.mode == Mode::Forall)
+            }
+            _ => {
+                let mut stat_loops = HashMap::new();
+                for l in loops {
+                    stat_loops.insert(l.var.to_owned(), *l);
+                }
+
+                self.codegen.set_loop_data(stat_loops);
+                self.codegen.generate_assignment(assign, c as u8)
+            }
+        }
+    }
 }
 
-fn allen_kennedy<'a, G, W>(
-    codegen: &mut Codegen<G, W>,
+/// `parallel` is set inside of a loop which is already run in parallel
+fn allen_kennedy<'a, T>(
+    target: &mut T,
     graph: &Graph<Statement, Vec<LevelDependency>>,
-    ast: &'a Ast,
-    loop_map: &HashMap<LoopLabel, &'a Loop>,
-    stat_map: &HashMap<Statement, &'a Assign>,
-    stat_lps: &HashMap<Statement, Vec<LoopLabel>>,
+    schedule: &Schedule<'a>,
     c: Level,
+    parallel: bool,
 ) -> io::Result<()>
 where
-    G: Generator<'a, G, W>,
-    W: io::Write,
+    T: Target<'a>,
 {
     // Filter dependencies for adequate loop level
     let graph = graph.filter_map(
         |_, n| Some(*n),
         |_, e| {
-            // Filter dependencies by depth
+            // Filter dependencies by depth, input dependencies constrain no
+            // order
             let mut new_ldeps = Vec::new();
             for ldep in e.iter() {
-                if ldep.0 == 0 || ldep.0 > c {
+                if (ldep.0 == 0 || ldep.0 > c) && ldep.1 != DependencyType::Input {
                     new_ldeps.push(ldep.clone());
                 }
             }
 
-            if new_ldeps.len() > 0 {
+            if !new_ldeps.is_empty() {
                 Some(new_ldeps)
             } else {
                 None
@@ -98,84 +288,83 @@ where
         );
 
         if is_cyclic_directed(&subgraph) {
+            if sub_nodes.len() == 1 {
+                let stat = graph.node_weight(sub_nodes[0]).unwrap_or(&-1);
+                let (assign, nest) = statement_nest(schedule, *stat, c)?;
+                if target.reduction(assign, &nest, c)? {
+                    continue;
+                }
+            }
+
             if let Some(n) = sub_nodes.first() {
                 let stat = graph.node_weight(*n).unwrap_or(&-1);
-                let l = match stat_lps.get(stat) {
+                let l = match schedule.stat_lps.get(stat) {
                     Some(l) => l,
                     None => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Could not lookup loops for statement {}", stat),
-                        ))
+                        return Err(io::Error::other(format!(
+                            "Could not lookup loops for statement {}",
+                            stat
+                        )))
                     }
                 };
 
                 if l.len() <= c as usize {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("Not enough loops for statement {}", stat),
-                    ));
+                    return Err(io::Error::other(format!(
+                        "Not enough loops for statement {}",
+                        stat
+                    )));
                 }
 
-                let l = match loop_map.get(&l[c as usize]) {
+                let l = match schedule.loop_map.get(&l[c as usize]) {
                     Some(l) => l,
                     None => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Could not lookup loop with label {}", l[c as usize]),
-                        ))
+                        return Err(io::Error::other(format!(
+                            "Could not lookup loop with label {}",
+                            l[c as usize]
+                        )))
                     }
                 };
 
-                codegen.generate_loop_vec_start(l, c)?;
-                allen_kennedy(codegen, &subgraph, ast, loop_map, stat_map, stat_lps, c + 1)?;
-                codegen.generate_loop_vec_end(l, c)?;
+                // The loop may still run in parallel, if the cycle is formed
+                // by dependencies carried by inner loops only
+                let run_parallel = target.parallel_loops()
+                    && !parallel
+                    && subgraph
+                        .edge_weights()
+                        .all(|e| e.iter().all(|ldep| ldep.0 != c + 1));
+                if run_parallel {
+                    let mut private = Vec::new();
+                    for n in sub_nodes.iter() {
+                        let labels = graph
+                            .node_weight(*n)
+                            .and_then(|stat| schedule.stat_lps.get(stat));
+                        for label in labels.iter().flat_map(|l| l.iter().skip(c as usize + 1)) {
+                            if let Some(inner) = schedule.loop_map.get(label) {
+                                if !private.contains(&inner.var.as_str()) {
+                                    private.push(inner.var.as_str());
+                                }
+                            }
+                        }
+                    }
+
+                    target.loop_start(l, c, Some(&private))?;
+                } else {
+                    target.loop_start(l, c, None)?;
+                }
+
+                allen_kennedy(target, &subgraph, schedule, c + 1, parallel || run_parallel)?;
+                target.loop_end(l, c)?;
             } else {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("No nodes in subgraph at level {}", c + 1),
-                ));
+                return Err(io::Error::other(format!(
+                    "No nodes in subgraph at level {}",
+                    c + 1
+                )));
             }
         } else {
             for node in sub_nodes.iter() {
                 if let Some(stat) = graph.node_weight(*node) {
-                    let assign = match stat_map.get(stat) {
-                        Some(s) => s,
-                        None => {
-                            return Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                format!("Could not lookup statement {}", stat),
-                            ))
-                        }
-                    };
-                    let loops = match stat_lps.get(stat) {
-                        Some(l) => l,
-                        None => {
-                            return Err(io::Error::new(
-                                io::ErrorKind::Other,
-                                format!("Could not lookup loops for statement {}", stat),
-                            ))
-                        }
-                    };
-
-                    let mut stat_loops = HashMap::new();
-                    if loops.len() > c as usize {
-                        for label in &loops[c as usize..] {
-                            let l = match loop_map.get(label) {
-                                Some(l) => *l,
-                                None => {
-                                    return Err(io::Error::new(
-                                        io::ErrorKind::Other,
-                                        format!("Could not lookup loop with label: {}", label),
-                                    ))
-                                }
-                            };
-                            stat_loops.insert(l.var.to_owned(), l);
-                        }
-                    }
-
-                    codegen.set_loop_data(stat_loops);
-                    codegen.generate_assignment(assign, c as u8)?;
+                    let (assign, nest) = statement_nest(schedule, *stat, c)?;
+                    target.statement(assign, &nest, c, parallel)?;
                 }
             }
         }
@@ -184,6 +373,50 @@ where
     Ok(())
 }
 
+/// Statement together with its loops starting at level `c`
+fn statement_nest<'a>(
+    schedule: &Schedule<'a>,
+    stat: Statement,
+    c: Level,
+) -> io::Result<(&'a Assign, Vec<&'a Loop>)> {
+    let assign = match schedule.stat_map.get(&stat) {
+        Some(s) => *s,
+        None => {
+            return Err(io::Error::other(format!(
+                "Could not lookup statement {}",
+                stat
+            )))
+        }
+    };
+    let loops = match schedule.stat_lps.get(&stat) {
+        Some(l) => l,
+        None => {
+            return Err(io::Error::other(format!(
+                "Could not lookup loops for statement {}",
+                stat
+            )))
+        }
+    };
+
+    let mut nest = Vec::new();
+    if loops.len() > c as usize {
+        for label in &loops[c as usize..] {
+            let l = match schedule.loop_map.get(label) {
+                Some(l) => *l,
+                None => {
+                    return Err(io::Error::other(format!(
+                        "Could not lookup loop with label: {}",
+                        label
+                    )))
+                }
+            };
+            nest.push(l);
+        }
+    }
+
+    Ok((assign, nest))
+}
+
 fn ast_loops<'a>(statements: &'a [::ir::Statement], loop_map: &mut HashMap<LoopLabel, &'a Loop>) {
     for s in statements {
         if let ::ir::Statement::Loop(l) = s {
@@ -216,3 +449,115 @@ pub fn ast_statements<'a>(
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use dependencies::Attribution;
+    use transform::tests::parse;
+
+    /// Recurrence on `b` in the outer loop and an independent nest writing `a`
+    pub const NEST: &str = "nest
+a FLOAT 2 1 20 1 20
+b FLOAT 1 1 20
+s FLOAT
+i INT
+j INT
+n INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 2 EXPR VAR ENTRY n STMTLIST
+  ASSIGN @ 2 VAR ENTRY b EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR BINOP + EXPR VAR ENTRY b EXPRLIST EXPR BINOP - EXPR VAR ENTRY i EXPR INT 1 /EXPRLIST EXPR FLOAT 1.0
+  FOR @ 3 ENTRY j EXPR INT 1 EXPR INT 10 STMTLIST
+    ASSIGN @ 4 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST EXPR BINOP * EXPR VAR ENTRY a EXPRLIST EXPR VAR ENTRY i EXPR VAR ENTRY j /EXPRLIST EXPR FLOAT 2.0
+  /STMTLIST
+  ASSIGN @ 5 VAR ENTRY s EXPR BINOP + EXPR VAR ENTRY s EXPR VAR ENTRY b EXPRLIST EXPR VAR ENTRY i /EXPRLIST
+/STMTLIST
+/STMTLIST
+";
+
+    /// Independent loop reading a shifted element
+    pub const SHIFTED: &str = "shifted
+a FLOAT 1 1 40
+b FLOAT 1 1 40
+i INT
+STMTLIST
+FOR @ 1 ENTRY i EXPR INT 1 EXPR INT 18 STMTLIST
+  ASSIGN @ 2 VAR ENTRY a EXPRLIST EXPR VAR ENTRY i /EXPRLIST EXPR BINOP * EXPR VAR ENTRY b EXPRLIST EXPR BINOP + EXPR VAR ENTRY i EXPR INT 1 /EXPRLIST EXPR FLOAT 2.0
+/STMTLIST
+/STMTLIST
+";
+
+    /// Dependencies of `NEST`, the recurrence and the reduction carried by
+    /// the outer loop, and the true dependency between them
+    pub fn graph() -> Graph<Statement, Vec<LevelDependency>> {
+        let dependency = |level, dep| LevelDependency(level, dep, Attribution::default());
+        let mut graph = Graph::new();
+        let recurrence = graph.add_node(2);
+        graph.add_node(4);
+        let reduction = graph.add_node(5);
+        graph.add_edge(
+            recurrence,
+            recurrence,
+            vec![dependency(1, DependencyType::True)],
+        );
+        graph.add_edge(
+            reduction,
+            reduction,
+            vec![
+                dependency(1, DependencyType::True),
+                dependency(1, DependencyType::Anti),
+                dependency(1, DependencyType::Output),
+            ],
+        );
+        graph.add_edge(
+            recurrence,
+            reduction,
+            vec![dependency(0, DependencyType::True)],
+        );
+        graph
+    }
+
+    /// Vectorized program in the given mode
+    pub fn generate(
+        text: &str,
+        graph: &Graph<Statement, Vec<LevelDependency>>,
+        mode: Mode,
+    ) -> String {
+        let mut out = Vec::new();
+        vectorize_with(graph, &parse(text), &mut out, false, mode).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn openmp_runs_independent_nests_in_parallel() {
+        let omp = generate(NEST, &graph(), Mode::OpenMp);
+        assert!(omp.contains(
+            "    !$omp parallel do private(j)
+    do i = 2, n
+        !$omp simd
+        do j = 1, 10
+004         a(i,j) = a(i,j)*2
+"
+        ));
+
+        // The recurrence and the reduction stay sequential
+        assert_eq!(omp.matches("!$omp").count(), 2);
+        assert!(omp.contains("    do i = 2, n\n002     b(i) = b(i-1)+1\n    end do\n"));
+    }
+    #[test]
+    fn concurrent_and_forall_cover_the_whole_nest() {
+        let concurrent = generate(NEST, &graph(), Mode::Concurrent);
+        assert!(concurrent.contains(
+            "    do concurrent (i = 2:n, j = 1:10)\n004     a(i,j) = a(i,j)*2\n    end do\n"
+        ));
+
+        let forall = generate(NEST, &graph(), Mode::Forall);
+        assert!(forall.contains(
+            "    forall (i = 2:n, j = 1:10)\n004     a(i,j) = a(i,j)*2\n    end forall\n"
+        ));
+
+        for out in [concurrent, forall].iter() {
+            assert!(out.contains("    do i = 2, n\n002     b(i) = b(i-1)+1\n    end do\n"));
+        }
+    }
+}
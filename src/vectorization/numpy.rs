@@ -0,0 +1,44 @@
+use super::*;
+use codegen::numpy::NumpyCodegen;
+
+/// Python function, statements without carried dependencies use slices
+pub(super) struct NumpyTarget<W> {
+    pub codegen: NumpyCodegen<W>,
+}
+
+impl<W: io::Write> NumpyTarget<W> {
+    pub fn new(ast: &Ast, out: W) -> Self {
+        NumpyTarget {
+            codegen: NumpyCodegen::new(ast, out),
+        }
+    }
+}
+
+impl<'a, W: io::Write> Target<'a> for NumpyTarget<W> {
+    fn loop_start(
+        &mut self,
+        l: &'a Loop,
+        c: Level,
+        _private: Option<&[&'a str]>,
+    ) -> io::Result<()> {
+        self.codegen.generate_loop_start(l, c as u8)
+    }
+
+    fn loop_end(&mut self, l: &'a Loop, c: Level) -> io::Result<()> {
+        self.codegen.generate_loop_end(l, c as u8)
+    }
+
+    fn statement(
+        &mut self,
+        assign: &'a Assign,
+        loops: &[&'a Loop],
+        c: Level,
+        _parallel: bool,
+    ) -> io::Result<()> {
+        self.codegen.generate_statement(assign, loops, c as u8)
+    }
+
+    fn reduction(&mut self, assign: &'a Assign, loops: &[&'a Loop], c: Level) -> io::Result<bool> {
+        self.codegen.generate_reduction(assign, loops, c as u8)
+    }
+}
@@ -0,0 +1,57 @@
+use super::*;
+use codegen::rust::{RustCodegen, RustProgram};
+
+/// Rust function, the innermost loop of a statement without carried
+/// dependencies iterates over slices
+pub(super) struct RustTarget<W> {
+    pub codegen: RustCodegen<RustProgram, W>,
+}
+
+impl<W: io::Write> RustTarget<W> {
+    pub fn new(ast: &Ast, out: W) -> Self {
+        RustTarget {
+            codegen: RustCodegen::new_kernel(RustProgram, ast, out),
+        }
+    }
+}
+
+impl<'a, W: io::Write> Target<'a> for RustTarget<W> {
+    fn loop_start(
+        &mut self,
+        l: &'a Loop,
+        c: Level,
+        _private: Option<&[&'a str]>,
+    ) -> io::Result<()> {
+        self.codegen.generate_loop_start(l, c as u8)
+    }
+
+    fn loop_end(&mut self, l: &'a Loop, c: Level) -> io::Result<()> {
+        self.codegen.generate_loop_end(l, c as u8)
+    }
+
+    fn statement(
+        &mut self,
+        assign: &'a Assign,
+        loops: &[&'a Loop],
+        c: Level,
+        _parallel: bool,
+    ) -> io::Result<()> {
+        let (innermost, outer) = match loops.split_last() {
+            Some(l) => l,
+            None => return self.codegen.generate_assignment(assign, c as u8),
+        };
+
+        for (i, l) in outer.iter().enumerate() {
+            self.codegen.generate_loop_start(l, c as u8 + i as u8)?;
+        }
+
+        self.codegen
+            .generate_iterator_loop(assign, innermost, (c as usize + outer.len()) as u8)?;
+
+        for (i, l) in outer.iter().enumerate().rev() {
+            self.codegen.generate_loop_end(l, c as u8 + i as u8)?;
+        }
+
+        Ok(())
+    }
+}